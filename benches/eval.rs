@@ -0,0 +1,36 @@
+use std::fs;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use nolana::{
+    Parser,
+    diagnostic::Result,
+    eval::{Interpreter, Value, query::QueryCall},
+    vm::{Compiler, Vm},
+};
+
+fn queries(call: QueryCall) -> Result<Value> {
+    Ok(match call.name {
+        "life_time" => 10.0.into(),
+        "is_on_ground" | "is_alive" => true.into(),
+        "position_delta" => 0.05.into(),
+        _ => return Err(call.unknown()),
+    })
+}
+
+fn bench_eval(c: &mut Criterion) {
+    let source_code = fs::read_to_string("benches/sample.molang").unwrap();
+    let ret = Parser::new(&source_code).parse();
+    let mut interpreter = Interpreter::new().with_queries(queries);
+    c.bench_function("interpreter", |b| {
+        b.iter(|| interpreter.eval(&ret.program).unwrap());
+    });
+
+    let chunk = Compiler::default().compile(&ret.program).unwrap();
+    let mut vm = Vm::new().with_queries(queries);
+    c.bench_function("vm", |b| {
+        b.iter(|| vm.run(&chunk).unwrap());
+    });
+}
+
+criterion_group!(eval, bench_eval);
+criterion_main!(eval);
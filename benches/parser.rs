@@ -2,11 +2,18 @@ use std::fs;
 
 use criterion::{Criterion, criterion_group, criterion_main};
 use nolana::Parser;
+#[cfg(feature = "arena")]
+use nolana::arena::{self, Bump};
 
 fn parse(source: &str) {
     let _ = Parser::new(source).parse();
 }
 
+#[cfg(feature = "arena")]
+fn parse_arena(arena: &Bump, source: &str) {
+    let _ = arena::Parser::new(arena, source).parse();
+}
+
 fn bench_parser(c: &mut Criterion) {
     let source_code = fs::read_to_string("benches/sample.molang").unwrap();
     c.bench_function("parser", |b| {
@@ -14,6 +21,18 @@ fn bench_parser(c: &mut Criterion) {
             parse(&source_code);
         });
     });
+    // The arena is reset rather than dropped, the same way a caller parsing
+    // many programs would reuse it.
+    #[cfg(feature = "arena")]
+    {
+        let mut arena = Bump::new();
+        c.bench_function("parser_arena", |b| {
+            b.iter(|| {
+                parse_arena(&arena, &source_code);
+                arena.reset();
+            });
+        });
+    }
 }
 
 criterion_group!(parser, bench_parser);
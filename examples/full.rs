@@ -9,7 +9,7 @@ use nolana::{
 fn main() {
     let source_text = fs::read_to_string("examples/sample.molang").unwrap();
 
-    let ParseResult { mut program, errors } = Parser::new(&source_text).parse();
+    let ParseResult { program, errors } = Parser::new(&source_text).parse();
 
     if !errors.is_empty() {
         for error in errors {
@@ -19,7 +19,7 @@ fn main() {
         return;
     }
 
-    let errors = SemanticChecker::default().check(&mut program);
+    let errors = SemanticChecker::default().check(&program);
     if !errors.is_empty() {
         for error in errors {
             let error = error.with_source_code(source_text.clone());
@@ -30,7 +30,9 @@ fn main() {
 
     println!("AST: {program:#?}");
 
-    let output = Codegen::default().with_options(CodegenOptions { minify: true }).build(&program);
+    let output = Codegen::default()
+        .with_options(CodegenOptions { minify: true, ..Default::default() })
+        .build(&program);
 
     println!("Printed Molang: {output}");
 }
@@ -2,7 +2,8 @@ use std::fs;
 
 use nolana::{
     ast::{CallExpression, CallKind, Program},
-    traverse::{Traverse, traverse},
+    traverse::Control,
+    visit::{Visit, visit},
     {ParseResult, Parser},
 };
 
@@ -13,26 +14,27 @@ struct MolangStats {
 }
 
 impl MolangStats {
-    pub fn new(program: &mut Program) -> Self {
+    pub fn new(program: &Program) -> Self {
         let mut stats = Self { math_functions: 0, queries: 0 };
-        traverse(&mut stats, program);
+        visit(&mut stats, program);
         stats
     }
 }
 
-impl<'a> Traverse<'a> for MolangStats {
-    fn enter_call_expression(&mut self, it: &mut CallExpression<'a>) {
+impl<'a> Visit<'a> for MolangStats {
+    fn enter_call_expression(&mut self, it: &CallExpression<'a>) -> Control {
         match it.kind {
             CallKind::Math => self.math_functions += 1,
             CallKind::Query => self.queries += 1,
         }
+        Control::Continue
     }
 }
 
 fn main() {
     let source_text = fs::read_to_string("examples/sample.molang").unwrap();
 
-    let ParseResult { mut program, errors } = Parser::new(&source_text).parse();
+    let ParseResult { program, errors } = Parser::new(&source_text).parse();
 
     if !errors.is_empty() {
         for error in errors {
@@ -42,6 +44,6 @@ fn main() {
         return;
     }
 
-    let molang_stats = MolangStats::new(&mut program);
+    let molang_stats = MolangStats::new(&program);
     println!("{molang_stats:?}");
 }
@@ -0,0 +1,303 @@
+//! An arena-backed variant of the [AST](crate::ast).
+//!
+//! The nodes in [`crate::ast`] are boxed and their lists are [`Vec`]s, so
+//! parsing a program makes an allocation for almost every node. The nodes in
+//! this module are allocated in a [`Bump`] owned by the caller instead, and
+//! refer to each other with `&'arena` references. Allocating in a bump arena
+//! is little more than a pointer increment, and everything is freed at once
+//! when the arena is dropped or [reset](Bump::reset), which makes this layout
+//! a good fit for parsing thousands of programs, such as every Molang
+//! expression in a resource pack.
+//!
+//! Nodes without children, like [`Identifier`] and [`NumericLiteral`], are
+//! shared with [`crate::ast`].
+//!
+//! The arena AST can be visited with [`visit`]. Everything else, like the
+//! [`SemanticChecker`](crate::semantic::SemanticChecker) or the
+//! [`Codegen`](crate::Codegen), takes the boxed AST, which a [`Program`] can be
+//! converted into with [`From`].
+//!
+//! This module is only available with the `arena` feature.
+//!
+//! ```
+//! use nolana::arena::{Bump, Expression, Parser, ProgramBody};
+//!
+//! let arena = Bump::new();
+//! let result = Parser::new(&arena, "math.sin(q.anim_time * 90)").parse();
+//! assert!(result.errors.is_empty());
+//! assert!(matches!(result.program.body, ProgramBody::Simple(Expression::Call(_))));
+//!
+//! let program = nolana::ast::Program::from(&result.program);
+//! assert_eq!(nolana::Codegen::default().build(&program), "math.sin(q.anim_time*90)");
+//! ```
+
+mod convert;
+pub mod visit;
+
+pub use bumpalo::{Bump, collections::Vec};
+
+use crate::span::Span;
+pub use crate::{
+    ast::{
+        AssignmentOperator, BinaryOperator, BooleanLiteral, BreakStatement, CallKind,
+        ContinueStatement, EmptyStatement, ErrorExpression, ErrorStatement, Identifier,
+        NumericLiteral, ResourceExpression, ResourceSection, StringLiteral, ThisExpression,
+        UnaryOperator, UpdateOperator, VariableLifetime,
+    },
+    parser::arena::{ParseResult, Parser},
+};
+
+/// See [`ast::Program`](crate::ast::Program).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program<'arena, 'src> {
+    pub span: Span,
+    pub source: &'src str,
+    pub body: ProgramBody<'arena, 'src>,
+}
+
+/// See [`ast::ProgramBody`](crate::ast::ProgramBody).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramBody<'arena, 'src> {
+    Simple(Expression<'arena, 'src>),
+    Complex(Vec<'arena, Statement<'arena, 'src>>),
+    Empty,
+}
+
+impl ProgramBody<'_, '_> {
+    pub fn is_simple(&self) -> bool {
+        matches!(self, ProgramBody::Simple(_))
+    }
+
+    pub fn is_complex(&self) -> bool {
+        matches!(self, ProgramBody::Complex(_))
+    }
+}
+
+/// See [`ast::Statement`](crate::ast::Statement).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statement<'arena, 'src> {
+    Expression(&'arena Expression<'arena, 'src>),
+    Assignment(&'arena AssignmentStatement<'arena, 'src>),
+    Loop(&'arena LoopStatement<'arena, 'src>),
+    ForEach(&'arena ForEachStatement<'arena, 'src>),
+    Return(&'arena ReturnStatement<'arena, 'src>),
+    Break(&'arena BreakStatement),
+    Continue(&'arena ContinueStatement),
+    Empty(&'arena EmptyStatement),
+    Error(&'arena ErrorStatement),
+}
+
+impl Statement<'_, '_> {
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Self::Empty(_))
+    }
+
+    /// Returns the span of the underlying node.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Expression(stmt) => stmt.span(),
+            Self::Assignment(stmt) => stmt.span,
+            Self::Loop(stmt) => stmt.span,
+            Self::ForEach(stmt) => stmt.span,
+            Self::Return(stmt) => stmt.span,
+            Self::Break(stmt) => stmt.span,
+            Self::Continue(stmt) => stmt.span,
+            Self::Empty(stmt) => stmt.span,
+            Self::Error(stmt) => stmt.span,
+        }
+    }
+}
+
+/// See [`ast::AssignmentStatement`](crate::ast::AssignmentStatement).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssignmentStatement<'arena, 'src> {
+    pub span: Span,
+    pub left: VariableExpression<'arena, 'src>,
+    pub operator: AssignmentOperator,
+    pub right: Expression<'arena, 'src>,
+}
+
+/// See [`ast::LoopStatement`](crate::ast::LoopStatement).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopStatement<'arena, 'src> {
+    pub span: Span,
+    pub count: Expression<'arena, 'src>,
+    pub block: BlockExpression<'arena, 'src>,
+}
+
+/// See [`ast::ForEachStatement`](crate::ast::ForEachStatement).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForEachStatement<'arena, 'src> {
+    pub span: Span,
+    pub variable: VariableExpression<'arena, 'src>,
+    pub array: Expression<'arena, 'src>,
+    pub block: BlockExpression<'arena, 'src>,
+}
+
+/// See [`ast::ReturnStatement`](crate::ast::ReturnStatement).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnStatement<'arena, 'src> {
+    pub span: Span,
+    pub argument: Expression<'arena, 'src>,
+}
+
+/// See [`ast::Expression`](crate::ast::Expression).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expression<'arena, 'src> {
+    NumericLiteral(&'arena NumericLiteral<'src>),
+    BooleanLiteral(&'arena BooleanLiteral),
+    StringLiteral(&'arena StringLiteral<'src>),
+    Variable(&'arena VariableExpression<'arena, 'src>),
+    Parenthesized(&'arena ParenthesizedExpression<'arena, 'src>),
+    Block(&'arena BlockExpression<'arena, 'src>),
+    Binary(&'arena BinaryExpression<'arena, 'src>),
+    Unary(&'arena UnaryExpression<'arena, 'src>),
+    Update(&'arena UpdateExpression<'arena, 'src>),
+    Ternary(&'arena TernaryExpression<'arena, 'src>),
+    Conditional(&'arena ConditionalExpression<'arena, 'src>),
+    Resource(&'arena ResourceExpression<'src>),
+    ArrayAccess(&'arena ArrayAccessExpression<'arena, 'src>),
+    ArrowAccess(&'arena ArrowAccessExpression<'arena, 'src>),
+    Call(&'arena CallExpression<'arena, 'src>),
+    This(&'arena ThisExpression),
+    Error(&'arena ErrorExpression),
+}
+
+impl Expression<'_, '_> {
+    /// Returns the span of the underlying node.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::NumericLiteral(expr) => expr.span,
+            Self::BooleanLiteral(expr) => expr.span,
+            Self::StringLiteral(expr) => expr.span,
+            Self::Variable(expr) => expr.span,
+            Self::Parenthesized(expr) => expr.span,
+            Self::Block(expr) => expr.span,
+            Self::Binary(expr) => expr.span,
+            Self::Unary(expr) => expr.span,
+            Self::Update(expr) => expr.span,
+            Self::Ternary(expr) => expr.span,
+            Self::Conditional(expr) => expr.span,
+            Self::Resource(expr) => expr.span,
+            Self::ArrayAccess(expr) => expr.span,
+            Self::ArrowAccess(expr) => expr.span,
+            Self::Call(expr) => expr.span,
+            Self::This(expr) => expr.span,
+            Self::Error(expr) => expr.span,
+        }
+    }
+}
+
+/// See [`ast::VariableExpression`](crate::ast::VariableExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableExpression<'arena, 'src> {
+    pub span: Span,
+    pub lifetime: VariableLifetime,
+    pub member: VariableMember<'arena, 'src>,
+}
+
+impl VariableExpression<'_, '_> {
+    /// A struct is defined when an object or more are specified: `v.foo.bar`.
+    pub fn is_struct(&self) -> bool {
+        matches!(self.member, VariableMember::Object { .. })
+    }
+}
+
+/// See [`ast::VariableMember`](crate::ast::VariableMember).
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableMember<'arena, 'src> {
+    /// `foo.bar` in `v.foo.bar`
+    Object { object: &'arena VariableMember<'arena, 'src>, property: Identifier<'src> },
+    /// `foo` in `v.foo`
+    Property { property: Identifier<'src> },
+}
+
+/// See [`ast::ParenthesizedExpression`](crate::ast::ParenthesizedExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParenthesizedExpression<'arena, 'src> {
+    pub span: Span,
+    pub body: ParenthesizedBody<'arena, 'src>,
+}
+
+/// See [`ast::ParenthesizedBody`](crate::ast::ParenthesizedBody).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParenthesizedBody<'arena, 'src> {
+    /// `(1 + 1)` in `(1 + 1) * 2`
+    Single(Expression<'arena, 'src>),
+    /// `(v.a = 1;)` in `(v.b = 'B'; v.a = 1;);`
+    Multiple(Vec<'arena, Statement<'arena, 'src>>),
+}
+
+/// See [`ast::BlockExpression`](crate::ast::BlockExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockExpression<'arena, 'src> {
+    pub span: Span,
+    pub statements: Vec<'arena, Statement<'arena, 'src>>,
+}
+
+/// See [`ast::BinaryExpression`](crate::ast::BinaryExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryExpression<'arena, 'src> {
+    pub span: Span,
+    pub left: Expression<'arena, 'src>,
+    pub operator: BinaryOperator,
+    pub right: Expression<'arena, 'src>,
+}
+
+/// See [`ast::UnaryExpression`](crate::ast::UnaryExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnaryExpression<'arena, 'src> {
+    pub span: Span,
+    pub operator: UnaryOperator,
+    pub argument: Expression<'arena, 'src>,
+}
+
+/// See [`ast::UpdateExpression`](crate::ast::UpdateExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateExpression<'arena, 'src> {
+    pub span: Span,
+    pub variable: VariableExpression<'arena, 'src>,
+    pub operator: UpdateOperator,
+}
+
+/// See [`ast::TernaryExpression`](crate::ast::TernaryExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TernaryExpression<'arena, 'src> {
+    pub span: Span,
+    pub test: Expression<'arena, 'src>,
+    pub consequent: Expression<'arena, 'src>,
+    pub alternate: Expression<'arena, 'src>,
+}
+
+/// See [`ast::ConditionalExpression`](crate::ast::ConditionalExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConditionalExpression<'arena, 'src> {
+    pub span: Span,
+    pub test: Expression<'arena, 'src>,
+    pub consequent: Expression<'arena, 'src>,
+}
+
+/// See [`ast::ArrayAccessExpression`](crate::ast::ArrayAccessExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayAccessExpression<'arena, 'src> {
+    pub span: Span,
+    pub name: Identifier<'src>,
+    pub index: Expression<'arena, 'src>,
+}
+
+/// See [`ast::ArrowAccessExpression`](crate::ast::ArrowAccessExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrowAccessExpression<'arena, 'src> {
+    pub span: Span,
+    pub left: Expression<'arena, 'src>,
+    pub right: Expression<'arena, 'src>,
+}
+
+/// See [`ast::CallExpression`](crate::ast::CallExpression).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallExpression<'arena, 'src> {
+    pub span: Span,
+    pub kind: CallKind,
+    pub callee: Identifier<'src>,
+    pub arguments: Option<Vec<'arena, Expression<'arena, 'src>>>,
+}
@@ -0,0 +1,197 @@
+//! Conversions from the arena-backed AST to the boxed one, so that it can be
+//! passed to everything that takes a [`crate::ast::Program`].
+
+use super::*;
+use crate::ast;
+
+impl<'src> From<&Program<'_, 'src>> for ast::Program<'src> {
+    fn from(it: &Program<'_, 'src>) -> Self {
+        Self { span: it.span, source: it.source.into(), body: (&it.body).into() }
+    }
+}
+
+impl<'src> From<&ProgramBody<'_, 'src>> for ast::ProgramBody<'src> {
+    fn from(it: &ProgramBody<'_, 'src>) -> Self {
+        match it {
+            ProgramBody::Simple(expr) => Self::Simple(expr.into()),
+            ProgramBody::Complex(stmts) => Self::Complex(statements(stmts)),
+            ProgramBody::Empty => Self::Empty,
+        }
+    }
+}
+
+fn statements<'src>(it: &[Statement<'_, 'src>]) -> std::vec::Vec<ast::Statement<'src>> {
+    it.iter().map(Into::into).collect()
+}
+
+impl<'src> From<&Statement<'_, 'src>> for ast::Statement<'src> {
+    fn from(it: &Statement<'_, 'src>) -> Self {
+        match *it {
+            Statement::Expression(it) => Self::Expression(Box::new(it.into())),
+            Statement::Assignment(it) => Self::Assignment(Box::new(it.into())),
+            Statement::Loop(it) => Self::Loop(Box::new(it.into())),
+            Statement::ForEach(it) => Self::ForEach(Box::new(it.into())),
+            Statement::Return(it) => Self::Return(Box::new(it.into())),
+            Statement::Break(it) => Self::Break(Box::new(it.clone())),
+            Statement::Continue(it) => Self::Continue(Box::new(it.clone())),
+            Statement::Empty(it) => Self::Empty(Box::new(it.clone())),
+            Statement::Error(it) => Self::Error(Box::new(it.clone())),
+        }
+    }
+}
+
+impl<'src> From<&AssignmentStatement<'_, 'src>> for ast::AssignmentStatement<'src> {
+    fn from(it: &AssignmentStatement<'_, 'src>) -> Self {
+        Self {
+            span: it.span,
+            left: (&it.left).into(),
+            operator: it.operator,
+            right: (&it.right).into(),
+        }
+    }
+}
+
+impl<'src> From<&LoopStatement<'_, 'src>> for ast::LoopStatement<'src> {
+    fn from(it: &LoopStatement<'_, 'src>) -> Self {
+        Self { span: it.span, count: (&it.count).into(), block: (&it.block).into() }
+    }
+}
+
+impl<'src> From<&ForEachStatement<'_, 'src>> for ast::ForEachStatement<'src> {
+    fn from(it: &ForEachStatement<'_, 'src>) -> Self {
+        Self {
+            span: it.span,
+            variable: (&it.variable).into(),
+            array: (&it.array).into(),
+            block: (&it.block).into(),
+        }
+    }
+}
+
+impl<'src> From<&ReturnStatement<'_, 'src>> for ast::ReturnStatement<'src> {
+    fn from(it: &ReturnStatement<'_, 'src>) -> Self {
+        Self { span: it.span, argument: (&it.argument).into() }
+    }
+}
+
+impl<'src> From<&Expression<'_, 'src>> for ast::Expression<'src> {
+    fn from(it: &Expression<'_, 'src>) -> Self {
+        match *it {
+            Expression::NumericLiteral(it) => it.clone().into(),
+            Expression::BooleanLiteral(it) => it.clone().into(),
+            Expression::StringLiteral(it) => it.clone().into(),
+            Expression::Variable(it) => ast::VariableExpression::from(it).into(),
+            Expression::Parenthesized(it) => ast::ParenthesizedExpression::from(it).into(),
+            Expression::Block(it) => ast::BlockExpression::from(it).into(),
+            Expression::Binary(it) => ast::BinaryExpression::from(it).into(),
+            Expression::Unary(it) => ast::UnaryExpression::from(it).into(),
+            Expression::Update(it) => Self::Update(Box::new(it.into())),
+            Expression::Ternary(it) => ast::TernaryExpression::from(it).into(),
+            Expression::Conditional(it) => ast::ConditionalExpression::from(it).into(),
+            Expression::Resource(it) => it.clone().into(),
+            Expression::ArrayAccess(it) => ast::ArrayAccessExpression::from(it).into(),
+            Expression::ArrowAccess(it) => ast::ArrowAccessExpression::from(it).into(),
+            Expression::Call(it) => ast::CallExpression::from(it).into(),
+            Expression::This(it) => Self::This(Box::new(*it)),
+            Expression::Error(it) => Self::Error(Box::new(*it)),
+        }
+    }
+}
+
+impl<'src> From<&VariableExpression<'_, 'src>> for ast::VariableExpression<'src> {
+    fn from(it: &VariableExpression<'_, 'src>) -> Self {
+        Self { span: it.span, lifetime: it.lifetime, member: (&it.member).into() }
+    }
+}
+
+impl<'src> From<&VariableMember<'_, 'src>> for ast::VariableMember<'src> {
+    fn from(it: &VariableMember<'_, 'src>) -> Self {
+        match it {
+            VariableMember::Object { object, property } => {
+                Self::Object { object: Box::new((*object).into()), property: property.clone() }
+            }
+            VariableMember::Property { property } => Self::Property { property: property.clone() },
+        }
+    }
+}
+
+impl<'src> From<&ParenthesizedExpression<'_, 'src>> for ast::ParenthesizedExpression<'src> {
+    fn from(it: &ParenthesizedExpression<'_, 'src>) -> Self {
+        let body = match &it.body {
+            ParenthesizedBody::Single(expr) => ast::ParenthesizedBody::Single(expr.into()),
+            ParenthesizedBody::Multiple(stmts) => {
+                ast::ParenthesizedBody::Multiple(statements(stmts))
+            }
+        };
+        Self { span: it.span, body }
+    }
+}
+
+impl<'src> From<&BlockExpression<'_, 'src>> for ast::BlockExpression<'src> {
+    fn from(it: &BlockExpression<'_, 'src>) -> Self {
+        Self { span: it.span, statements: statements(&it.statements) }
+    }
+}
+
+impl<'src> From<&BinaryExpression<'_, 'src>> for ast::BinaryExpression<'src> {
+    fn from(it: &BinaryExpression<'_, 'src>) -> Self {
+        Self {
+            span: it.span,
+            left: (&it.left).into(),
+            operator: it.operator,
+            right: (&it.right).into(),
+        }
+    }
+}
+
+impl<'src> From<&UnaryExpression<'_, 'src>> for ast::UnaryExpression<'src> {
+    fn from(it: &UnaryExpression<'_, 'src>) -> Self {
+        Self { span: it.span, operator: it.operator, argument: (&it.argument).into() }
+    }
+}
+
+impl<'src> From<&UpdateExpression<'_, 'src>> for ast::UpdateExpression<'src> {
+    fn from(it: &UpdateExpression<'_, 'src>) -> Self {
+        Self { span: it.span, variable: (&it.variable).into(), operator: it.operator }
+    }
+}
+
+impl<'src> From<&TernaryExpression<'_, 'src>> for ast::TernaryExpression<'src> {
+    fn from(it: &TernaryExpression<'_, 'src>) -> Self {
+        Self {
+            span: it.span,
+            test: (&it.test).into(),
+            consequent: (&it.consequent).into(),
+            alternate: (&it.alternate).into(),
+        }
+    }
+}
+
+impl<'src> From<&ConditionalExpression<'_, 'src>> for ast::ConditionalExpression<'src> {
+    fn from(it: &ConditionalExpression<'_, 'src>) -> Self {
+        Self { span: it.span, test: (&it.test).into(), consequent: (&it.consequent).into() }
+    }
+}
+
+impl<'src> From<&ArrayAccessExpression<'_, 'src>> for ast::ArrayAccessExpression<'src> {
+    fn from(it: &ArrayAccessExpression<'_, 'src>) -> Self {
+        Self { span: it.span, name: it.name.clone(), index: (&it.index).into() }
+    }
+}
+
+impl<'src> From<&ArrowAccessExpression<'_, 'src>> for ast::ArrowAccessExpression<'src> {
+    fn from(it: &ArrowAccessExpression<'_, 'src>) -> Self {
+        Self { span: it.span, left: (&it.left).into(), right: (&it.right).into() }
+    }
+}
+
+impl<'src> From<&CallExpression<'_, 'src>> for ast::CallExpression<'src> {
+    fn from(it: &CallExpression<'_, 'src>) -> Self {
+        Self {
+            span: it.span,
+            kind: it.kind,
+            callee: it.callee.clone(),
+            arguments: it.arguments.as_ref().map(|args| args.iter().map(Into::into).collect()),
+        }
+    }
+}
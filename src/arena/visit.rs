@@ -0,0 +1,725 @@
+//! Visits the [arena-backed AST](super) the same way [`crate::visit`] visits
+//! the boxed one.
+
+use std::ops::ControlFlow::{self, Continue};
+
+use super::*;
+use crate::traverse::{Ancestry, Control, NodeKind, enter};
+
+/// Visits the arena-backed AST using an implementer of [`Visit`].
+pub fn visit<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    program: &Program<'arena, 'src>,
+) {
+    if let Some(ancestry) = visitor.ancestry() {
+        ancestry.clear();
+    }
+    let _ = walk_program(visitor, program);
+}
+
+/// See [`visit::Visit`](crate::visit::Visit). The hooks are the same, and
+/// they are called in the same order.
+#[expect(unused_variables)]
+pub trait Visit<'arena, 'src>: Sized {
+    /// Returns the ancestry to keep up to date while visiting, if the
+    /// implementer needs to know the parents of nodes.
+    #[inline]
+    fn ancestry(&mut self) -> Option<&mut Ancestry> {
+        None
+    }
+
+    #[inline]
+    fn enter_program(&mut self, it: &Program<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_program(&mut self, it: &Program<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_statements(&mut self, it: &[Statement<'arena, 'src>]) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_statements(&mut self, it: &[Statement<'arena, 'src>]) {}
+
+    #[inline]
+    fn enter_statement(&mut self, it: &Statement<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_statement(&mut self, it: &Statement<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_assignment_statement(&mut self, it: &AssignmentStatement<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_assignment_statement(&mut self, it: &AssignmentStatement<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_loop_statement(&mut self, it: &LoopStatement<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_loop_statement(&mut self, it: &LoopStatement<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_for_each_statement(&mut self, it: &ForEachStatement<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_for_each_statement(&mut self, it: &ForEachStatement<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_return_statement(&mut self, it: &ReturnStatement<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_return_statement(&mut self, it: &ReturnStatement<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_break_statement(&mut self, it: &BreakStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_break_statement(&mut self, it: &BreakStatement) {}
+
+    #[inline]
+    fn enter_continue_statement(&mut self, it: &ContinueStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_continue_statement(&mut self, it: &ContinueStatement) {}
+
+    #[inline]
+    fn enter_empty_statement(&mut self, it: &EmptyStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_empty_statement(&mut self, it: &EmptyStatement) {}
+
+    #[inline]
+    fn enter_error_statement(&mut self, it: &ErrorStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_error_statement(&mut self, it: &ErrorStatement) {}
+
+    #[inline]
+    fn enter_expression(&mut self, it: &Expression<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_expression(&mut self, it: &Expression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_identifier_reference(&mut self, it: &Identifier<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_identifier_reference(&mut self, it: &Identifier<'src>) {}
+
+    #[inline]
+    fn enter_numeric_literal(&mut self, it: &NumericLiteral<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_numeric_literal(&mut self, it: &NumericLiteral<'src>) {}
+
+    #[inline]
+    fn enter_boolean_literal(&mut self, it: &BooleanLiteral) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_boolean_literal(&mut self, it: &BooleanLiteral) {}
+
+    #[inline]
+    fn enter_string_literal(&mut self, it: &StringLiteral<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_string_literal(&mut self, it: &StringLiteral<'src>) {}
+
+    #[inline]
+    fn enter_variable_expression(&mut self, it: &VariableExpression<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_variable_expression(&mut self, it: &VariableExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_variable_member(&mut self, it: &VariableMember<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_variable_member(&mut self, it: &VariableMember<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_parenthesized_expression(
+        &mut self,
+        it: &ParenthesizedExpression<'arena, 'src>,
+    ) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_parenthesized_expression(&mut self, it: &ParenthesizedExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_block_expression(&mut self, it: &BlockExpression<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_block_expression(&mut self, it: &BlockExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_binary_expression(&mut self, it: &BinaryExpression<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_binary_expression(&mut self, it: &BinaryExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_unary_expression(&mut self, it: &UnaryExpression<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_unary_expression(&mut self, it: &UnaryExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_update_expression(&mut self, it: &UpdateExpression<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_update_expression(&mut self, it: &UpdateExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_ternary_expression(&mut self, it: &TernaryExpression<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_ternary_expression(&mut self, it: &TernaryExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_conditional_expression(
+        &mut self,
+        it: &ConditionalExpression<'arena, 'src>,
+    ) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_conditional_expression(&mut self, it: &ConditionalExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_resource_expression(&mut self, it: &ResourceExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_resource_expression(&mut self, it: &ResourceExpression<'src>) {}
+
+    #[inline]
+    fn enter_array_access_expression(
+        &mut self,
+        it: &ArrayAccessExpression<'arena, 'src>,
+    ) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_array_access_expression(&mut self, it: &ArrayAccessExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_arrow_access_expression(
+        &mut self,
+        it: &ArrowAccessExpression<'arena, 'src>,
+    ) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_arrow_access_expression(&mut self, it: &ArrowAccessExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_call_expression(&mut self, it: &CallExpression<'arena, 'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_call_expression(&mut self, it: &CallExpression<'arena, 'src>) {}
+
+    #[inline]
+    fn enter_this_expression(&mut self, it: &ThisExpression) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_this_expression(&mut self, it: &ThisExpression) {}
+
+    #[inline]
+    fn enter_error_expression(&mut self, it: &ErrorExpression) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_error_expression(&mut self, it: &ErrorExpression) {}
+}
+
+fn walk_program<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &Program<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_program(it))? {
+        push(visitor, NodeKind::Program);
+        match &it.body {
+            ProgramBody::Simple(expr) => walk_expression(visitor, expr)?,
+            ProgramBody::Complex(stmts) => walk_statements(visitor, stmts)?,
+            ProgramBody::Empty => (),
+        }
+        pop(visitor);
+    }
+    visitor.exit_program(it);
+    Continue(())
+}
+
+fn walk_statements<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &[Statement<'arena, 'src>],
+) -> ControlFlow<()> {
+    if enter(visitor.enter_statements(it))? {
+        for stmt in it.iter() {
+            walk_statement(visitor, stmt)?;
+        }
+    }
+    visitor.exit_statements(it);
+    Continue(())
+}
+
+fn walk_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &Statement<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_statement(it))? {
+        match it {
+            Statement::Expression(it) => walk_expression(visitor, it)?,
+            Statement::Assignment(it) => walk_assignment_statement(visitor, it)?,
+            Statement::Loop(it) => walk_loop_statement(visitor, it)?,
+            Statement::ForEach(it) => walk_for_each_statement(visitor, it)?,
+            Statement::Return(it) => walk_return_statement(visitor, it)?,
+            Statement::Break(it) => walk_break_statement(visitor, it)?,
+            Statement::Continue(it) => walk_continue_statement(visitor, it)?,
+            Statement::Empty(it) => walk_empty_statement(visitor, it)?,
+            Statement::Error(it) => walk_error_statement(visitor, it)?,
+        }
+    }
+    visitor.exit_statement(it);
+    Continue(())
+}
+
+fn walk_assignment_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &AssignmentStatement<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_assignment_statement(it))? {
+        push(visitor, NodeKind::AssignmentStatement);
+        walk_variable_expression(visitor, &it.left)?;
+        walk_expression(visitor, &it.right)?;
+        pop(visitor);
+    }
+    visitor.exit_assignment_statement(it);
+    Continue(())
+}
+
+fn walk_loop_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &LoopStatement<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_loop_statement(it))? {
+        push(visitor, NodeKind::LoopStatement);
+        walk_expression(visitor, &it.count)?;
+        walk_block_expression(visitor, &it.block)?;
+        pop(visitor);
+    }
+    visitor.exit_loop_statement(it);
+    Continue(())
+}
+
+fn walk_for_each_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ForEachStatement<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_for_each_statement(it))? {
+        push(visitor, NodeKind::ForEachStatement);
+        walk_variable_expression(visitor, &it.variable)?;
+        walk_expression(visitor, &it.array)?;
+        walk_block_expression(visitor, &it.block)?;
+        pop(visitor);
+    }
+    visitor.exit_for_each_statement(it);
+    Continue(())
+}
+
+fn walk_return_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ReturnStatement<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_return_statement(it))? {
+        push(visitor, NodeKind::ReturnStatement);
+        walk_expression(visitor, &it.argument)?;
+        pop(visitor);
+    }
+    visitor.exit_return_statement(it);
+    Continue(())
+}
+
+fn walk_break_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &BreakStatement,
+) -> ControlFlow<()> {
+    enter(visitor.enter_break_statement(it))?;
+    visitor.exit_break_statement(it);
+    Continue(())
+}
+
+fn walk_continue_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ContinueStatement,
+) -> ControlFlow<()> {
+    enter(visitor.enter_continue_statement(it))?;
+    visitor.exit_continue_statement(it);
+    Continue(())
+}
+
+fn walk_empty_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &EmptyStatement,
+) -> ControlFlow<()> {
+    enter(visitor.enter_empty_statement(it))?;
+    visitor.exit_empty_statement(it);
+    Continue(())
+}
+
+fn walk_error_statement<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ErrorStatement,
+) -> ControlFlow<()> {
+    enter(visitor.enter_error_statement(it))?;
+    visitor.exit_error_statement(it);
+    Continue(())
+}
+
+fn walk_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &Expression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_expression(it))? {
+        match it {
+            Expression::NumericLiteral(it) => walk_numeric_literal(visitor, it)?,
+            Expression::BooleanLiteral(it) => walk_boolean_literal(visitor, it)?,
+            Expression::StringLiteral(it) => walk_string_literal(visitor, it)?,
+            Expression::Variable(it) => walk_variable_expression(visitor, it)?,
+            Expression::Parenthesized(it) => walk_parenthesized_expression(visitor, it)?,
+            Expression::Block(it) => walk_block_expression(visitor, it)?,
+            Expression::Binary(it) => walk_binary_expression(visitor, it)?,
+            Expression::Unary(it) => walk_unary_expression(visitor, it)?,
+            Expression::Update(it) => walk_update_expression(visitor, it)?,
+            Expression::Ternary(it) => walk_ternary_expression(visitor, it)?,
+            Expression::Conditional(it) => walk_conditional_expression(visitor, it)?,
+            Expression::Resource(it) => walk_resource_expression(visitor, it)?,
+            Expression::ArrayAccess(it) => walk_array_access_expression(visitor, it)?,
+            Expression::ArrowAccess(it) => walk_arrow_access_expression(visitor, it)?,
+            Expression::Call(it) => walk_call_expression(visitor, it)?,
+            Expression::This(it) => walk_this_expression(visitor, it)?,
+            Expression::Error(it) => walk_error_expression(visitor, it)?,
+        }
+    }
+    visitor.exit_expression(it);
+    Continue(())
+}
+
+fn walk_identifier_reference<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &Identifier<'src>,
+) -> ControlFlow<()> {
+    enter(visitor.enter_identifier_reference(it))?;
+    visitor.exit_identifier_reference(it);
+    Continue(())
+}
+
+fn walk_boolean_literal<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &BooleanLiteral,
+) -> ControlFlow<()> {
+    enter(visitor.enter_boolean_literal(it))?;
+    visitor.exit_boolean_literal(it);
+    Continue(())
+}
+
+fn walk_numeric_literal<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &NumericLiteral<'src>,
+) -> ControlFlow<()> {
+    enter(visitor.enter_numeric_literal(it))?;
+    visitor.exit_numeric_literal(it);
+    Continue(())
+}
+
+fn walk_string_literal<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &StringLiteral<'src>,
+) -> ControlFlow<()> {
+    enter(visitor.enter_string_literal(it))?;
+    visitor.exit_string_literal(it);
+    Continue(())
+}
+
+fn walk_variable_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &VariableExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_variable_expression(it))? {
+        push(visitor, NodeKind::VariableExpression);
+        walk_variable_member(visitor, &it.member)?;
+        pop(visitor);
+    }
+    visitor.exit_variable_expression(it);
+    Continue(())
+}
+
+fn walk_variable_member<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &VariableMember<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_variable_member(it))? {
+        push(visitor, NodeKind::VariableMember);
+        match it {
+            VariableMember::Object { object, property, .. } => {
+                walk_variable_member(visitor, object)?;
+                walk_identifier_reference(visitor, property)?;
+            }
+            VariableMember::Property { property, .. } => {
+                walk_identifier_reference(visitor, property)?;
+            }
+        }
+        pop(visitor);
+    }
+    visitor.exit_variable_member(it);
+    Continue(())
+}
+
+fn walk_parenthesized_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ParenthesizedExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_parenthesized_expression(it))? {
+        push(visitor, NodeKind::ParenthesizedExpression);
+        match &it.body {
+            ParenthesizedBody::Single(expression) => {
+                walk_expression(visitor, expression)?;
+            }
+            ParenthesizedBody::Multiple(statements) => {
+                walk_statements(visitor, statements)?;
+            }
+        }
+        pop(visitor);
+    }
+    visitor.exit_parenthesized_expression(it);
+    Continue(())
+}
+
+fn walk_block_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &BlockExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_block_expression(it))? {
+        push(visitor, NodeKind::BlockExpression);
+        walk_statements(visitor, &it.statements)?;
+        pop(visitor);
+    }
+    visitor.exit_block_expression(it);
+    Continue(())
+}
+
+fn walk_binary_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &BinaryExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_binary_expression(it))? {
+        push(visitor, NodeKind::BinaryExpression);
+        walk_expression(visitor, &it.left)?;
+        walk_expression(visitor, &it.right)?;
+        pop(visitor);
+    }
+    visitor.exit_binary_expression(it);
+    Continue(())
+}
+
+fn walk_unary_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &UnaryExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_unary_expression(it))? {
+        push(visitor, NodeKind::UnaryExpression);
+        walk_expression(visitor, &it.argument)?;
+        pop(visitor);
+    }
+    visitor.exit_unary_expression(it);
+    Continue(())
+}
+
+fn walk_update_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &UpdateExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_update_expression(it))? {
+        push(visitor, NodeKind::UpdateExpression);
+        walk_variable_expression(visitor, &it.variable)?;
+        pop(visitor);
+    }
+    visitor.exit_update_expression(it);
+    Continue(())
+}
+
+fn walk_ternary_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &TernaryExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_ternary_expression(it))? {
+        push(visitor, NodeKind::TernaryExpression);
+        walk_expression(visitor, &it.test)?;
+        walk_expression(visitor, &it.consequent)?;
+        walk_expression(visitor, &it.alternate)?;
+        pop(visitor);
+    }
+    visitor.exit_ternary_expression(it);
+    Continue(())
+}
+
+fn walk_conditional_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ConditionalExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_conditional_expression(it))? {
+        push(visitor, NodeKind::ConditionalExpression);
+        walk_expression(visitor, &it.test)?;
+        walk_expression(visitor, &it.consequent)?;
+        pop(visitor);
+    }
+    visitor.exit_conditional_expression(it);
+    Continue(())
+}
+
+fn walk_resource_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ResourceExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_resource_expression(it))? {
+        push(visitor, NodeKind::ResourceExpression);
+        walk_identifier_reference(visitor, &it.name)?;
+        pop(visitor);
+    }
+    visitor.exit_resource_expression(it);
+    Continue(())
+}
+
+fn walk_array_access_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ArrayAccessExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_array_access_expression(it))? {
+        push(visitor, NodeKind::ArrayAccessExpression);
+        walk_identifier_reference(visitor, &it.name)?;
+        walk_expression(visitor, &it.index)?;
+        pop(visitor);
+    }
+    visitor.exit_array_access_expression(it);
+    Continue(())
+}
+
+fn walk_arrow_access_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ArrowAccessExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_arrow_access_expression(it))? {
+        push(visitor, NodeKind::ArrowAccessExpression);
+        walk_expression(visitor, &it.left)?;
+        walk_expression(visitor, &it.right)?;
+        pop(visitor);
+    }
+    visitor.exit_arrow_access_expression(it);
+    Continue(())
+}
+
+fn walk_call_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &CallExpression<'arena, 'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_call_expression(it))? {
+        push(visitor, NodeKind::CallExpression);
+        walk_identifier_reference(visitor, &it.callee)?;
+        if let Some(args) = &it.arguments {
+            for arg in args {
+                walk_expression(visitor, arg)?;
+            }
+        }
+        pop(visitor);
+    }
+    visitor.exit_call_expression(it);
+    Continue(())
+}
+
+fn walk_this_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ThisExpression,
+) -> ControlFlow<()> {
+    enter(visitor.enter_this_expression(it))?;
+    visitor.exit_this_expression(it);
+    Continue(())
+}
+
+fn walk_error_expression<'arena, 'src>(
+    visitor: &mut impl Visit<'arena, 'src>,
+    it: &ErrorExpression,
+) -> ControlFlow<()> {
+    enter(visitor.enter_error_expression(it))?;
+    visitor.exit_error_expression(it);
+    Continue(())
+}
+
+#[inline]
+fn push<'arena, 'src>(visitor: &mut impl Visit<'arena, 'src>, kind: NodeKind) {
+    if let Some(ancestry) = visitor.ancestry() {
+        ancestry.push(kind);
+    }
+}
+
+#[inline]
+fn pop<'arena, 'src>(visitor: &mut impl Visit<'arena, 'src>) {
+    if let Some(ancestry) = visitor.ancestry() {
+        ancestry.pop();
+    }
+}
@@ -4,15 +4,25 @@ use crate::{span::Span, token::Kind};
 
 /// Represents the root of a Molang expression AST, containing all the top-level
 /// information.
+///
+/// With the `serde` feature, every node implements `Serialize` and
+/// `Deserialize`. Deserializing borrows the source code, names and strings
+/// from the input where it can, and owns them where it can't, such as for JSON
+/// strings with escape sequences.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct Program<'src> {
     pub span: Span,
-    pub source: &'src str,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub source: Cow<'src, str>,
     pub body: ProgramBody<'src>,
 }
 
 /// A program is considered complex if it contains any statement.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub enum ProgramBody<'src> {
     Simple(Expression<'src>),
     Complex(Vec<Statement<'src>>),
@@ -30,6 +40,8 @@ impl ProgramBody<'_> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub enum Statement<'src> {
     Expression(Box<Expression<'src>>),
     Assignment(Box<AssignmentStatement<'src>>),
@@ -39,16 +51,34 @@ pub enum Statement<'src> {
     Break(Box<BreakStatement>),
     Continue(Box<ContinueStatement>),
     Empty(Box<EmptyStatement>),
+    Error(Box<ErrorStatement>),
 }
 
 impl Statement<'_> {
     pub fn is_empty(&self) -> bool {
         matches!(self, Self::Empty(_))
     }
+
+    /// Returns the span of the underlying node.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Expression(stmt) => stmt.span(),
+            Self::Assignment(stmt) => stmt.span,
+            Self::Loop(stmt) => stmt.span,
+            Self::ForEach(stmt) => stmt.span,
+            Self::Return(stmt) => stmt.span,
+            Self::Break(stmt) => stmt.span,
+            Self::Continue(stmt) => stmt.span,
+            Self::Empty(stmt) => stmt.span,
+            Self::Error(stmt) => stmt.span,
+        }
+    }
 }
 
 /// `v.a = 0;`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct AssignmentStatement<'src> {
     pub span: Span,
     pub left: VariableExpression<'src>,
@@ -63,6 +93,7 @@ impl<'src> From<AssignmentStatement<'src>> for Statement<'src> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AssignmentOperator {
     /// `=`
     Assign,
@@ -146,6 +177,8 @@ impl From<Kind> for AssignmentOperator {
 ///
 /// `loop(10, { v.x = v.x + 1; });`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct LoopStatement<'src> {
     pub span: Span,
     pub count: Expression<'src>,
@@ -162,6 +195,8 @@ impl<'src> From<LoopStatement<'src>> for Statement<'src> {
 ///
 /// `for_each(t.foo, q.baz, { v.x = v.x + 1; });`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct ForEachStatement<'src> {
     pub span: Span,
     pub variable: VariableExpression<'src>,
@@ -177,6 +212,8 @@ impl<'src> From<ForEachStatement<'src>> for Statement<'src> {
 
 /// `return` in `v.a = 1; return v.a;`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct ReturnStatement<'src> {
     pub span: Span,
     pub argument: Expression<'src>,
@@ -192,6 +229,7 @@ impl<'src> From<ReturnStatement<'src>> for Statement<'src> {
 ///
 /// `break` in `loop(10, { v.x = v.x + 1; (v.x > 20) ? break; });`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BreakStatement {
     pub span: Span,
 }
@@ -206,6 +244,7 @@ impl From<BreakStatement> for Statement<'_> {
 ///
 /// `continue` in `loop(10, { (v.x > 5) ? continue; v.x = v.x + 1; });`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ContinueStatement {
     pub span: Span,
 }
@@ -217,6 +256,7 @@ impl From<ContinueStatement> for Statement<'_> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EmptyStatement {
     pub span: Span,
 }
@@ -227,8 +267,24 @@ impl From<EmptyStatement> for Statement<'_> {
     }
 }
 
+/// A statement that could not be parsed. Its span covers the skipped source
+/// code.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorStatement {
+    pub span: Span,
+}
+
+impl From<ErrorStatement> for Statement<'_> {
+    fn from(value: ErrorStatement) -> Self {
+        Self::Error(value.into())
+    }
+}
+
 /// <https://bedrock.dev/docs/stable/Molang#Lexical%20Structure>
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub enum Expression<'src> {
     NumericLiteral(Box<NumericLiteral<'src>>),
     BooleanLiteral(Box<BooleanLiteral>),
@@ -246,6 +302,32 @@ pub enum Expression<'src> {
     ArrowAccess(Box<ArrowAccessExpression<'src>>),
     Call(Box<CallExpression<'src>>),
     This(Box<ThisExpression>),
+    Error(Box<ErrorExpression>),
+}
+
+impl Expression<'_> {
+    /// Returns the span of the underlying node.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::NumericLiteral(expr) => expr.span,
+            Self::BooleanLiteral(expr) => expr.span,
+            Self::StringLiteral(expr) => expr.span,
+            Self::Variable(expr) => expr.span,
+            Self::Parenthesized(expr) => expr.span,
+            Self::Block(expr) => expr.span,
+            Self::Binary(expr) => expr.span,
+            Self::Unary(expr) => expr.span,
+            Self::Update(expr) => expr.span,
+            Self::Ternary(expr) => expr.span,
+            Self::Conditional(expr) => expr.span,
+            Self::Resource(expr) => expr.span,
+            Self::ArrayAccess(expr) => expr.span,
+            Self::ArrowAccess(expr) => expr.span,
+            Self::Call(expr) => expr.span,
+            Self::This(expr) => expr.span,
+            Self::Error(expr) => expr.span,
+        }
+    }
 }
 
 impl<'src> From<Expression<'src>> for Statement<'src> {
@@ -256,10 +338,13 @@ impl<'src> From<Expression<'src>> for Statement<'src> {
 
 /// `1.23` in `v.a = 1.23;`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct NumericLiteral<'src> {
     pub span: Span,
     pub value: f32,
-    pub raw: &'src str,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub raw: Cow<'src, str>,
 }
 
 impl<'src> From<NumericLiteral<'src>> for Expression<'src> {
@@ -270,6 +355,7 @@ impl<'src> From<NumericLiteral<'src>> for Expression<'src> {
 
 /// `true` or `false`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BooleanLiteral {
     pub span: Span,
     pub value: bool,
@@ -292,9 +378,12 @@ impl BooleanLiteral {
 ///
 /// `'foo bar'` in `v.a = 'foo bar';`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct StringLiteral<'src> {
     pub span: Span,
-    pub value: &'src str,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub value: Cow<'src, str>,
 }
 
 impl<'src> From<StringLiteral<'src>> for Expression<'src> {
@@ -305,13 +394,18 @@ impl<'src> From<StringLiteral<'src>> for Expression<'src> {
 
 /// `foo` in `v.foo.bar`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct Identifier<'src> {
     pub span: Span,
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub name: Cow<'src, str>,
 }
 
 /// <https://bedrock.dev/docs/stable/Molang#Variables>
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct VariableExpression<'src> {
     pub span: Span,
     pub lifetime: VariableLifetime,
@@ -333,6 +427,7 @@ impl<'src> From<VariableExpression<'src>> for Expression<'src> {
 
 /// The variable lifetime associated with [`VariableExpression`].
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VariableLifetime {
     /// `temp` in `temp.foo`
     Temporary,
@@ -373,6 +468,8 @@ impl From<Kind> for VariableLifetime {
 
 /// <https://bedrock.dev/docs/stable/Molang#Structs>
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub enum VariableMember<'src> {
     /// `foo.bar` in `v.foo.bar`
     Object { object: Box<VariableMember<'src>>, property: Identifier<'src> },
@@ -381,6 +478,8 @@ pub enum VariableMember<'src> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct ParenthesizedExpression<'src> {
     pub span: Span,
     pub body: ParenthesizedBody<'src>,
@@ -393,6 +492,8 @@ impl<'src> From<ParenthesizedExpression<'src>> for Expression<'src> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub enum ParenthesizedBody<'src> {
     /// `(1 + 1)` in `(1 + 1) * 2`
     Single(Expression<'src>),
@@ -402,6 +503,8 @@ pub enum ParenthesizedBody<'src> {
 
 /// `{ v.a = 0; }` in `loop(10, { v.a = 0; })`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct BlockExpression<'src> {
     pub span: Span,
     pub statements: Vec<Statement<'src>>,
@@ -415,6 +518,8 @@ impl<'src> From<BlockExpression<'src>> for Expression<'src> {
 
 /// `1 + 1` in `v.a = 1 + 1;`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct BinaryExpression<'src> {
     pub span: Span,
     pub left: Expression<'src>,
@@ -430,6 +535,7 @@ impl<'src> From<BinaryExpression<'src>> for Expression<'src> {
 
 /// Operators used in [`BinaryExpression`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOperator {
     /// `==`
     Equality,
@@ -500,6 +606,12 @@ impl BinaryOperator {
         }
     }
 
+    /// The left and right binding powers of this operator, as used by the
+    /// parser.
+    pub(crate) fn binding_power(self) -> (u8, u8) {
+        Kind::from(self).binding_power().expect("binary operators have a binding power")
+    }
+
     pub fn is_custom(&self) -> bool {
         !matches!(
             self,
@@ -548,6 +660,33 @@ impl From<Kind> for BinaryOperator {
     }
 }
 
+impl From<BinaryOperator> for Kind {
+    fn from(operator: BinaryOperator) -> Self {
+        match operator {
+            BinaryOperator::Equality => Self::Eq2,
+            BinaryOperator::Inequality => Self::Neq,
+            BinaryOperator::LessThan => Self::Lt,
+            BinaryOperator::GreaterThan => Self::Gt,
+            BinaryOperator::LessEqualThan => Self::LtEq,
+            BinaryOperator::GreaterEqualThan => Self::GtEq,
+            BinaryOperator::Or => Self::Pipe2,
+            BinaryOperator::And => Self::Amp2,
+            BinaryOperator::Coalesce => Self::Question2,
+            BinaryOperator::Subtraction => Self::Minus,
+            BinaryOperator::Addition => Self::Plus,
+            BinaryOperator::Multiplication => Self::Star,
+            BinaryOperator::Division => Self::Slash,
+            BinaryOperator::Remainder => Self::Percent,
+            BinaryOperator::Exponential => Self::Star2,
+            BinaryOperator::ShiftLeft => Self::ShiftLeft,
+            BinaryOperator::ShiftRight => Self::ShiftRight,
+            BinaryOperator::BitwiseOr => Self::Pipe,
+            BinaryOperator::BitwiseAnd => Self::Amp,
+            BinaryOperator::BitwiseXor => Self::Caret,
+        }
+    }
+}
+
 impl From<AssignmentOperator> for BinaryOperator {
     fn from(op: AssignmentOperator) -> Self {
         match op {
@@ -569,6 +708,8 @@ impl From<AssignmentOperator> for BinaryOperator {
 
 /// `-1` in `q.foo(-1)`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct UnaryExpression<'src> {
     pub span: Span,
     pub operator: UnaryOperator,
@@ -583,6 +724,7 @@ impl<'src> From<UnaryExpression<'src>> for Expression<'src> {
 
 /// Operators used in [`UnaryExpression`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOperator {
     /// `-`
     Negate,
@@ -601,6 +743,21 @@ impl UnaryOperator {
             Self::BitwiseNot => "~",
         }
     }
+
+    /// The right binding power of this operator, as used by the parser.
+    pub(crate) fn binding_power(self) -> u8 {
+        Kind::from(self).prefix_binding_power()
+    }
+}
+
+impl From<UnaryOperator> for Kind {
+    fn from(operator: UnaryOperator) -> Self {
+        match operator {
+            UnaryOperator::Negate => Self::Minus,
+            UnaryOperator::Not => Self::Bang,
+            UnaryOperator::BitwiseNot => Self::Tilde,
+        }
+    }
 }
 
 impl From<Kind> for UnaryOperator {
@@ -615,6 +772,8 @@ impl From<Kind> for UnaryOperator {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct UpdateExpression<'src> {
     pub span: Span,
     pub variable: VariableExpression<'src>,
@@ -622,6 +781,7 @@ pub struct UpdateExpression<'src> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UpdateOperator {
     /// `++`
     Increment,
@@ -662,6 +822,8 @@ impl From<UpdateOperator> for BinaryOperator {
 ///
 /// `q.foo ? 0 : 1`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct TernaryExpression<'src> {
     pub span: Span,
     pub test: Expression<'src>,
@@ -679,6 +841,8 @@ impl<'src> From<TernaryExpression<'src>> for Expression<'src> {
 ///
 /// `q.foo ? 0`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct ConditionalExpression<'src> {
     pub span: Span,
     pub test: Expression<'src>,
@@ -693,6 +857,8 @@ impl<'src> From<ConditionalExpression<'src>> for Expression<'src> {
 
 /// <https://bedrock.dev/docs/stable/Molang#Resource%20Expression>
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct ResourceExpression<'src> {
     pub span: Span,
     pub section: ResourceSection,
@@ -707,6 +873,7 @@ impl<'src> From<ResourceExpression<'src>> for Expression<'src> {
 
 /// The resource section in [`ResourceExpression`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ResourceSection {
     /// `geometry` in `geometry.foo`
     Geometry,
@@ -742,6 +909,8 @@ impl From<Kind> for ResourceSection {
 ///
 /// `array.foo[0]`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct ArrayAccessExpression<'src> {
     pub span: Span,
     pub name: Identifier<'src>,
@@ -758,6 +927,8 @@ impl<'src> From<ArrayAccessExpression<'src>> for Expression<'src> {
 ///
 /// `v.foo->q.bar`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct ArrowAccessExpression<'src> {
     pub span: Span,
     pub left: Expression<'src>,
@@ -775,6 +946,8 @@ impl<'src> From<ArrowAccessExpression<'src>> for Expression<'src> {
 ///
 /// `math.random(1, 2)` or `math.random`
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'src")))]
 pub struct CallExpression<'src> {
     pub span: Span,
     pub kind: CallKind,
@@ -790,6 +963,7 @@ impl<'src> From<CallExpression<'src>> for Expression<'src> {
 
 /// The call kind for [`CallExpression`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CallKind {
     /// `math` in `math.foo`
     Math,
@@ -825,6 +999,7 @@ impl From<Kind> for CallKind {
 
 /// `this` in `q.foo(this)`
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ThisExpression {
     pub span: Span,
 }
@@ -834,3 +1009,17 @@ impl From<ThisExpression> for Expression<'_> {
         Self::This(value.into())
     }
 }
+
+/// An expression that could not be parsed, such as `+` in `q.foo(1, +)`. Its
+/// span covers the skipped source code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorExpression {
+    pub span: Span,
+}
+
+impl From<ErrorExpression> for Expression<'_> {
+    fn from(value: ErrorExpression) -> Self {
+        Self::Error(value.into())
+    }
+}
@@ -0,0 +1,587 @@
+//! Language server for Molang.
+//!
+//! Talks the [Language Server Protocol] over stdio and provides diagnostics,
+//! quick fixes, formatting, hover and document symbols for `.molang` files. It is only
+//! built with the `lsp` feature:
+//!
+//! ```sh
+//! cargo install nolana --features lsp
+//! ```
+//!
+//! Formatting always indents with spaces, even if the editor asks for tabs.
+//!
+//! [Language Server Protocol]: <https://microsoft.github.io/language-server-protocol>
+
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, DocumentFormattingParams, DocumentSymbol, DocumentSymbolParams,
+    DocumentSymbolResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, InitializeResult, MarkupContent, MarkupKind, NumberOrString, OneOf, Position,
+    PositionEncodingKind, PublishDiagnosticsParams, Range, ServerCapabilities, ServerInfo,
+    SymbolKind, TextDocumentContentChangeEvent, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextEdit, Uri, WorkspaceEdit,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{CodeActionRequest, DocumentSymbolRequest, Formatting, HoverRequest, Request as _},
+};
+use miette::Severity;
+use nolana::{
+    Codegen, CodegenOptions, Formatter, FormatterOptions, ParseResult, Parser,
+    ast::*,
+    diagnostic::{Applicability, Diagnostic},
+    eval::math::MathFunction,
+    line_index::{Encoding, LineColumn, LineIndex, LineRange},
+    lint::Linter,
+    semantic::SemanticChecker,
+    span::{SPAN, Span},
+    traverse::Control,
+    visit::{Visit, visit},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let (id, params) = connection.initialize_start()?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+    let encoding = negotiate_encoding(&params);
+    let result = InitializeResult {
+        capabilities: ServerCapabilities {
+            position_encoding: Some(match encoding {
+                Encoding::Utf8 => PositionEncodingKind::UTF8,
+                Encoding::Utf16 => PositionEncodingKind::UTF16,
+            }),
+            text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                TextDocumentSyncKind::INCREMENTAL,
+            )),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            ..Default::default()
+        },
+        server_info: Some(ServerInfo {
+            name: "nolana-lsp".to_string(),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        }),
+    };
+    connection.initialize_finish(id, serde_json::to_value(result)?)?;
+
+    Server { connection: &connection, encoding, documents: HashMap::new() }.run()?;
+    // The writer thread only stops once the connection is dropped.
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Uses UTF-8 positions if the client supports them, since they don't need
+/// to be converted. UTF-16 is the default of the protocol.
+fn negotiate_encoding(params: &InitializeParams) -> Encoding {
+    let encodings = params
+        .capabilities
+        .general
+        .as_ref()
+        .and_then(|general| general.position_encodings.as_ref());
+    match encodings {
+        Some(encodings) if encodings.contains(&PositionEncodingKind::UTF8) => Encoding::Utf8,
+        _ => Encoding::Utf16,
+    }
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    encoding: Encoding,
+    documents: HashMap<Uri, Document>,
+}
+
+struct Document {
+    source: String,
+    index: LineIndex,
+    /// The parsed source code, which answers requests and is reparsed after
+    /// each change.
+    result: ParseResult<'static>,
+}
+
+impl Document {
+    fn new(source: String) -> Self {
+        let index = LineIndex::new(&source);
+        let result = Parser::new(&source).parse().into_owned();
+        Self { source, index, result }
+    }
+
+    /// Applies a change, reparsing only the statements it touches.
+    fn change(self, change: TextDocumentContentChangeEvent, encoding: Encoding) -> Self {
+        let Some(range) = change.range else { return Self::new(change.text) };
+        let span = self.index.span(from_lsp_range(range), encoding);
+        let Some(span) = span.filter(|span| span.start <= span.end) else {
+            eprintln!("nolana-lsp: change outside of the document: {range:?}");
+            return self;
+        };
+        let mut source = self.source;
+        source.replace_range(span.start as usize..span.end as usize, &change.text);
+        let edit = nolana::TextEdit { span, new_len: change.text.len() as u32 };
+        let result = Parser::new(&source).reparse(self.result, edit).into_owned();
+        let index = LineIndex::new(&source);
+        Self { source, index, result }
+    }
+}
+
+impl Server<'_> {
+    fn run(mut self) -> Result<()> {
+        for message in &self.connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        break;
+                    }
+                    let response = self.handle_request(request);
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            Formatting::METHOD => self.respond::<Formatting>(request, Self::formatting),
+            HoverRequest::METHOD => self.respond::<HoverRequest>(request, Self::hover),
+            DocumentSymbolRequest::METHOD => {
+                self.respond::<DocumentSymbolRequest>(request, Self::document_symbols)
+            }
+            CodeActionRequest::METHOD => {
+                self.respond::<CodeActionRequest>(request, Self::code_actions)
+            }
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request: {method}"),
+            ),
+        }
+    }
+
+    /// Answers `request` with `handler`, or with an error if its params are
+    /// malformed, so that one bad request does not stop the server.
+    fn respond<R: lsp_types::request::Request>(
+        &self,
+        request: Request,
+        handler: fn(&Self, R::Params) -> R::Result,
+    ) -> Response {
+        let id = request.id.clone();
+        match request.extract(R::METHOD) {
+            Ok((id, params)) => Response::new_ok(id, handler(self, params)),
+            Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string()),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Some(params) = params::<DidOpenTextDocument>(notification) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                self.documents.insert(uri.clone(), Document::new(params.text_document.text));
+                self.publish_diagnostics(uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let Some(params) = params::<DidChangeTextDocument>(notification) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                let Some(mut document) = self.documents.remove(&uri) else { return Ok(()) };
+                for change in params.content_changes {
+                    document = document.change(change, self.encoding);
+                }
+                self.documents.insert(uri.clone(), document);
+                self.publish_diagnostics(uri)?;
+            }
+            DidCloseTextDocument::METHOD => {
+                let Some(params) = params::<DidCloseTextDocument>(notification) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.send_diagnostics(uri, Vec::new())?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn publish_diagnostics(&self, uri: Uri) -> Result<()> {
+        let Some(document) = self.documents.get(&uri) else { return Ok(()) };
+        let diagnostics = check(&document.result)
+            .iter()
+            .map(|error| to_lsp_diagnostic(error, document, self.encoding))
+            .collect();
+        self.send_diagnostics(uri, diagnostics)
+    }
+
+    fn send_diagnostics(&self, uri: Uri, diagnostics: Vec<lsp_types::Diagnostic>) -> Result<()> {
+        let params = PublishDiagnosticsParams { uri, diagnostics, version: None };
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+
+    /// Formats the whole document. Documents with syntax errors are left
+    /// alone.
+    ///
+    /// The formatter only indents with spaces, so `insert_spaces` is ignored
+    /// and `tab_size` is the number of spaces per indentation level.
+    fn formatting(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let options = FormatterOptions {
+            indent_width: params.options.tab_size as usize,
+            ..Default::default()
+        };
+        let formatted = Formatter::default().with_options(options).format(&document.source).ok()?;
+        if formatted == document.source {
+            return Some(Vec::new());
+        }
+        let span = Span::new(0, document.source.len() as u32);
+        let range = to_lsp_range(document.index.range(span, self.encoding));
+        Some(vec![TextEdit { range, new_text: formatted }])
+    }
+
+    /// Offers the fixes of the diagnostics within the requested range.
+    fn code_actions(&self, params: CodeActionParams) -> Option<Vec<CodeActionOrCommand>> {
+        let uri = params.text_document.uri;
+        let document = self.documents.get(&uri)?;
+        let requested = document.index.span(from_lsp_range(params.range), self.encoding)?;
+        let range = |span| to_lsp_range(document.index.range(span, self.encoding));
+        let mut actions = Vec::new();
+        for error in check(&document.result) {
+            let Some(span) = error.span() else { continue };
+            if span.end < requested.start || requested.end < span.start {
+                continue;
+            }
+            for fix in &error.fixes {
+                let edits = fix
+                    .edits
+                    .iter()
+                    .map(|edit| TextEdit::new(range(edit.span), edit.replacement.to_string()))
+                    .collect();
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.message.to_string(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![to_lsp_diagnostic(&error, document, self.encoding)]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri.clone(), edits)])),
+                        ..Default::default()
+                    }),
+                    is_preferred: Some(fix.applicability == Applicability::MachineApplicable),
+                    ..Default::default()
+                }));
+            }
+        }
+        Some(actions)
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = document.index.offset(from_lsp_position(position.position), self.encoding)?;
+        let (span, contents) = hover_at(&document.result.program, offset)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: contents,
+            }),
+            range: Some(to_lsp_range(document.index.range(span, self.encoding))),
+        })
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let ProgramBody::Complex(stmts) = &document.result.program.body else {
+            return Some(DocumentSymbolResponse::Nested(Vec::new()));
+        };
+        let range = |span| to_lsp_range(document.index.range(span, self.encoding));
+        Some(DocumentSymbolResponse::Nested(symbols(stmts, &range)))
+    }
+}
+
+/// Extracts the params of `notification`, or logs why they are malformed.
+/// Notifications have no response to report an error in.
+fn params<N: lsp_types::notification::Notification>(
+    notification: Notification,
+) -> Option<N::Params> {
+    notification.extract(N::METHOD).inspect_err(|error| eprintln!("nolana-lsp: {error}")).ok()
+}
+
+/// Returns the syntax and semantic errors and the default lints of the
+/// parsed source code.
+fn check(result: &ParseResult) -> Vec<Diagnostic> {
+    let mut errors = result.errors.clone();
+    errors.extend(SemanticChecker::default().check(&result.program));
+    errors.extend(Linter::default().lint(&result.program));
+    errors
+}
+
+fn to_lsp_diagnostic(
+    error: &Diagnostic,
+    document: &Document,
+    encoding: Encoding,
+) -> lsp_types::Diagnostic {
+    let range = error.range(&document.index, encoding).unwrap_or_default();
+    let severity = match error.severity {
+        Severity::Advice => lsp_types::DiagnosticSeverity::HINT,
+        Severity::Warning => lsp_types::DiagnosticSeverity::WARNING,
+        Severity::Error => lsp_types::DiagnosticSeverity::ERROR,
+    };
+    let message = match &error.help {
+        Some(help) => format!("{}\nhelp: {help}", error.message),
+        None => error.message.to_string(),
+    };
+    lsp_types::Diagnostic {
+        range: to_lsp_range(range),
+        severity: Some(severity),
+        code: error.code_id().map(|code| NumberOrString::String(code.to_string())),
+        source: Some("nolana".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+fn to_lsp_range(range: LineRange) -> Range {
+    Range::new(to_lsp_position(range.start), to_lsp_position(range.end))
+}
+
+fn to_lsp_position(position: LineColumn) -> Position {
+    Position::new(position.line, position.column)
+}
+
+fn from_lsp_range(range: Range) -> LineRange {
+    LineRange { start: from_lsp_position(range.start), end: from_lsp_position(range.end) }
+}
+
+fn from_lsp_position(position: Position) -> LineColumn {
+    LineColumn { line: position.line, column: position.character }
+}
+
+/// Describes the variable, call or resource at `offset`.
+fn hover_at(program: &Program, offset: u32) -> Option<(Span, String)> {
+    let mut finder = HoverFinder { offset, found: None };
+    visit(&mut finder, program);
+    let (span, code, description) = finder.found?;
+    Some((span, format!("```molang\n{code}\n```\n{description}")))
+}
+
+/// Finds the innermost node with something to say about it.
+struct HoverFinder {
+    offset: u32,
+    found: Option<(Span, String, String)>,
+}
+
+impl HoverFinder {
+    fn contains(&self, span: Span) -> bool {
+        span.start <= self.offset && self.offset <= span.end
+    }
+
+    fn found_at<'src>(&mut self, span: Span, expr: impl FnOnce() -> Expression<'src>, text: &str) {
+        if self.contains(span) {
+            self.found = Some((span, print_expression(expr()), text.to_string()));
+        }
+    }
+}
+
+impl<'src> Visit<'src> for HoverFinder {
+    fn enter_statement(&mut self, it: &Statement<'src>) -> Control {
+        if self.contains(it.span()) { Control::Continue } else { Control::SkipChildren }
+    }
+
+    fn enter_expression(&mut self, it: &Expression<'src>) -> Control {
+        if self.contains(it.span()) { Control::Continue } else { Control::SkipChildren }
+    }
+
+    fn enter_variable_expression(&mut self, it: &VariableExpression<'src>) -> Control {
+        let description = match it.lifetime {
+            VariableLifetime::Temporary => "Temporary variable, cleared after the expression runs.",
+            VariableLifetime::Variable => "Variable, kept by the entity between expressions.",
+            VariableLifetime::Context => "Read-only variable provided by the game.",
+        };
+        let expr = || it.clone().into();
+        self.found_at(it.span, expr, description);
+        Control::Continue
+    }
+
+    fn enter_call_expression(&mut self, it: &CallExpression<'src>) -> Control {
+        let description = match it.kind {
+            // Math functions are case-insensitive, like in `math::resolve`.
+            CallKind::Math => match MathFunction::from_name(&it.callee.name.to_ascii_lowercase()) {
+                Some(function) if function.arity() == 1 => "Math function with 1 argument.".into(),
+                Some(function) => format!("Math function with {} arguments.", function.arity()),
+                None => "Unknown math function.".into(),
+            },
+            CallKind::Query => "Query function, answered by the game.".to_string(),
+        };
+        // Only the name is shown, not the arguments.
+        let expr = || CallExpression { callee: it.callee.clone(), arguments: None, ..*it }.into();
+        self.found_at(it.span, expr, &description);
+        Control::Continue
+    }
+
+    fn enter_resource_expression(&mut self, it: &ResourceExpression<'src>) -> Control {
+        let description = match it.section {
+            ResourceSection::Geometry => "Geometry defined by the entity.",
+            ResourceSection::Material => "Material defined by the entity.",
+            ResourceSection::Texture => "Texture defined by the entity.",
+        };
+        let expr = || it.clone().into();
+        self.found_at(it.span, expr, description);
+        Control::Continue
+    }
+}
+
+fn print_expression(expr: Expression) -> String {
+    let program = Program { span: SPAN, source: "".into(), body: ProgramBody::Simple(expr) };
+    let options = CodegenOptions { minify: false, ..Default::default() };
+    Codegen::default().with_options(options).build(&program)
+}
+
+/// Lists the assignments in the statements, nested in the loops they are in.
+fn symbols(stmts: &[Statement], range: &impl Fn(Span) -> Range) -> Vec<DocumentSymbol> {
+    let mut list = Vec::new();
+    for stmt in stmts {
+        let (name, kind, selection, children) = match stmt {
+            Statement::Assignment(stmt) => {
+                let name = print_expression(stmt.left.clone().into());
+                (name, SymbolKind::VARIABLE, stmt.left.span, None)
+            }
+            Statement::Loop(stmt) => {
+                let children = symbols(&stmt.block.statements, range);
+                ("loop".to_string(), SymbolKind::NAMESPACE, stmt.span, Some(children))
+            }
+            Statement::ForEach(stmt) => {
+                let children = symbols(&stmt.block.statements, range);
+                ("for_each".to_string(), SymbolKind::NAMESPACE, stmt.span, Some(children))
+            }
+            // Bare blocks don't have a name, so their assignments are listed
+            // as if they were not in a block.
+            Statement::Expression(expr) => {
+                if let Expression::Block(block) = &**expr {
+                    list.extend(symbols(&block.statements, range));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        #[expect(deprecated)]
+        list.push(DocumentSymbol {
+            name,
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: range(stmt.span()),
+            selection_range: range(selection),
+            children,
+        });
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hover() {
+        let program = Parser::new("v.a = math.clamp(q.foo, 0, t.b.c);").parse().program;
+        let hover = |offset| hover_at(&program, offset).map(|(span, text)| (span.start, text));
+        assert_eq!(
+            hover(1),
+            Some((
+                0,
+                "```molang\nvariable.a\n```\nVariable, kept by the entity between expressions."
+                    .into()
+            ))
+        );
+        assert_eq!(
+            hover(8),
+            Some((6, "```molang\nmath.clamp\n```\nMath function with 3 arguments.".into()))
+        );
+        assert_eq!(hover(30).map(|(start, _)| start), Some(27));
+        assert_eq!(hover(4), None);
+        let hover = hover_at(&Parser::new("Math.Sin(1)").parse().program, 1).unwrap().1;
+        assert!(hover.ends_with("Math function with 1 argument."), "{hover}");
+    }
+
+    #[test]
+    fn test_changes() {
+        let change = |(line, start), (end_line, end), text: &str| TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(line, start), Position::new(end_line, end))),
+            range_length: None,
+            text: text.to_string(),
+        };
+        let mut document = Document::new("t.é = 'ü';\nloop(2, { v.b = 2; });".to_string());
+        for change in [
+            change((1, 16), (1, 17), "20"),
+            change((0, 10), (0, 10), " v.c = t.é;"),
+            change((1, 0), (1, 4), "for_each(t.x, q.all,"),
+        ] {
+            document = document.change(change, Encoding::Utf16);
+        }
+        assert_eq!(
+            document.source,
+            "t.é = 'ü'; v.c = t.é;\nfor_each(t.x, q.all,(2, { v.b = 20; });"
+        );
+        assert_eq!(document.result.program, Parser::new(&document.source).parse().program);
+        assert_eq!(document.result.errors, Parser::new(&document.source).parse().errors);
+        let document = document.change(
+            TextDocumentContentChangeEvent { range: None, range_length: None, text: "1".into() },
+            Encoding::Utf16,
+        );
+        assert_eq!(document.source, "1");
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        let (server, client) = Connection::memory();
+        let bad_params = serde_json::json!({ "textDocument": 1 });
+        let messages = [
+            Notification::new(DidOpenTextDocument::METHOD.into(), bad_params.clone()).into(),
+            Request::new(1.into(), HoverRequest::METHOD.into(), bad_params).into(),
+            Request::new(2.into(), "unknown".into(), serde_json::Value::Null).into(),
+        ];
+        for message in messages {
+            client.sender.send(message).unwrap();
+        }
+        // The server stops once every message was handled.
+        drop(client.sender);
+        Server { connection: &server, encoding: Encoding::Utf16, documents: HashMap::new() }
+            .run()
+            .unwrap();
+        let codes: Vec<_> = client
+            .receiver
+            .try_iter()
+            .map(|message| match message {
+                Message::Response(response) => response.error.unwrap().code,
+                message => panic!("unexpected message: {message:?}"),
+            })
+            .collect();
+        assert_eq!(codes, [ErrorCode::InvalidParams as i32, ErrorCode::MethodNotFound as i32]);
+    }
+
+    #[test]
+    fn test_symbols() {
+        let source = "v.a = 1; loop(2, { t.b = 2; }); { v.c = 3; };";
+        let program = Parser::new(source).parse().program;
+        let ProgramBody::Complex(stmts) = &program.body else { unreachable!() };
+        let range =
+            |span: Span| Range::new(Position::new(0, span.start), Position::new(0, span.end));
+        let symbols = symbols(stmts, &range);
+        let names: Vec<_> = symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, ["variable.a", "loop", "variable.c"]);
+        assert_eq!(symbols[1].children.as_ref().unwrap()[0].name, "temp.b");
+    }
+}
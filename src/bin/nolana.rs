@@ -0,0 +1,461 @@
+//! Command-line tool for Molang.
+//!
+//! Checks, formats, minifies and transforms `.molang` files. It is only built
+//! with the `cli` feature:
+//!
+//! ```sh
+//! cargo install nolana --features cli
+//! ```
+
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use miette::{NamedSource, Severity};
+use nolana::{
+    Codegen, CodegenOptions, Formatter, MolangTransformer, ParseResult, Parser,
+    diagnostic::{Code, Diagnostic, Report, apply_fixes},
+    json,
+    line_index::LineIndex,
+    lint::Linter,
+    semantic::SemanticChecker,
+};
+
+const USAGE: &str = "\
+Usage: nolana <COMMAND> [OPTIONS] [FILES]...
+
+Reads standard input when no files are given, or when a file is `-`.
+
+Commands:
+  parse      Print the AST
+  check      Report syntax and semantic errors and lints, including the
+             Molang in resource pack `.json` files
+  fmt        Format the source code while keeping comments
+  minify     Print the source code with all optional whitespace removed
+  transform  Rewrite operators that Minecraft doesn't support
+  explain    Explain a diagnostic code, or list every code if none is given
+
+Options:
+  --check            (fmt) Fail if a file is not formatted instead of printing it
+  --write            (fmt) Format files in place
+  --fix              (check) Apply the fixes that are safe to apply automatically,
+                     in place or to stdout for stdin, and report what is left
+  --config <FILE>    (check) Configure the lint rules with a config file
+  --format <FORMAT>  How diagnostics are printed [default: human]
+                     - human: with the source code, to stderr
+                     - short: `path:line:column: severity: message`, to stdout
+                     - json: a JSON array, to stdout
+                     - sarif: a SARIF log, to stdout
+  -h, --help         Print this message
+
+Exit codes: 0 on success, 1 if errors were found or a file could not be read or
+written, 2 on invalid usage.";
+
+/// Exit code for invalid usage.
+const USAGE_ERROR: u8 = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Parse,
+    Check { fix: bool, config: Option<PathBuf> },
+    Fmt { check: bool, write: bool },
+    Minify,
+    Transform,
+    Explain(Option<String>),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    #[default]
+    Human,
+    Short,
+    Json,
+    Sarif,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    fn name(&self) -> String {
+        match self {
+            Input::Stdin => "<stdin>".to_string(),
+            Input::File(path) => path.display().to_string(),
+        }
+    }
+
+    /// Whether the input is a resource pack JSON file with embedded Molang.
+    fn is_json(&self) -> bool {
+        matches!(self, Input::File(path) if path.extension().is_some_and(|ext| ext == "json"))
+    }
+
+    fn read(&self) -> io::Result<String> {
+        match self {
+            Input::Stdin => {
+                let mut source = String::new();
+                io::stdin().read_to_string(&mut source)?;
+                Ok(source)
+            }
+            Input::File(path) => fs::read_to_string(path),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    command: Command,
+    format: Format,
+    inputs: Vec<Input>,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+
+    if let Command::Explain(code) = &args.command {
+        return explain(code.as_deref());
+    }
+
+    let mut linter = Linter::default();
+    if let Command::Check { config: Some(path), .. } = &args.command {
+        let result = fs::read_to_string(path).map(|config| (linter.configure(&config), config));
+        match result {
+            Ok((Ok(()), _)) => (),
+            Ok((Err(error), config)) => {
+                let source = NamedSource::new(path.display().to_string(), config);
+                eprintln!("{:?}", error.with_source_code(source));
+                return ExitCode::from(USAGE_ERROR);
+            }
+            Err(error) => {
+                eprintln!("error: {}: {error}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let mut reporter = Reporter { format: args.format, report: Report::default() };
+    let success = run_inputs(&args, &mut linter, &mut reporter);
+    reporter.finish();
+    if success { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+/// Runs the command on every input and returns whether all of them succeeded.
+/// Inputs that can't be read or written are skipped after printing the error,
+/// so that the other inputs are still reported.
+fn run_inputs(args: &Args, linter: &mut Linter, reporter: &mut Reporter) -> bool {
+    let mut success = true;
+    for input in &args.inputs {
+        let result =
+            input.read().and_then(|source| run(&args.command, input, &source, linter, reporter));
+        match result {
+            Ok(ok) => success &= ok,
+            Err(error) => {
+                eprintln!("error: {}: {error}", input.name());
+                success = false;
+            }
+        }
+    }
+    success
+}
+
+fn explain(code: Option<&str>) -> ExitCode {
+    let Some(code) = code else {
+        for code in Code::ALL {
+            println!("{code}");
+        }
+        return ExitCode::SUCCESS;
+    };
+    match code.parse::<Code>() {
+        Ok(code) => {
+            println!("{code}\n\n{}", code.explanation());
+            ExitCode::SUCCESS
+        }
+        Err(()) => {
+            eprintln!("error: unknown code `{code}`");
+            ExitCode::from(USAGE_ERROR)
+        }
+    }
+}
+
+/// Returns `None` if help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut command = match args.next().as_deref() {
+        Some("parse") => Command::Parse,
+        Some("check") => Command::Check { fix: false, config: None },
+        Some("fmt") => Command::Fmt { check: false, write: false },
+        Some("minify") => Command::Minify,
+        Some("transform") => Command::Transform,
+        Some("explain") => {
+            let code = args.next();
+            if let Some(arg) = args.next() {
+                return Err(format!("unexpected argument `{arg}`"));
+            }
+            return Ok(Some(Args {
+                command: Command::Explain(code),
+                format: Format::default(),
+                inputs: Vec::new(),
+            }));
+        }
+        Some("-h" | "--help") => return Ok(None),
+        Some(command) => return Err(format!("unknown command `{command}`")),
+        None => return Err("missing command".to_string()),
+    };
+    let mut format = Format::default();
+    let mut inputs = Vec::new();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), &mut command) {
+            ("-h" | "--help", _) => return Ok(None),
+            ("--format", _) => {
+                format = match args.next().as_deref() {
+                    Some("human") => Format::Human,
+                    Some("short") => Format::Short,
+                    Some("json") => Format::Json,
+                    Some("sarif") => Format::Sarif,
+                    Some(format) => return Err(format!("unknown format `{format}`")),
+                    None => return Err("missing format after `--format`".to_string()),
+                }
+            }
+            ("--check", Command::Fmt { check, .. }) => *check = true,
+            ("--write", Command::Fmt { write, .. }) => *write = true,
+            ("--fix", Command::Check { fix, .. }) => *fix = true,
+            ("--config", Command::Check { config, .. }) => match args.next() {
+                Some(path) => *config = Some(path.into()),
+                None => return Err("missing file after `--config`".to_string()),
+            },
+            ("-", _) => inputs.push(Input::Stdin),
+            (option, _) if option.starts_with('-') => {
+                return Err(format!("unexpected option `{option}`"));
+            }
+            (path, _) => inputs.push(Input::File(path.into())),
+        }
+    }
+    if let Command::Fmt { check: true, write: true } = command {
+        return Err("`--check` and `--write` can't be used together".to_string());
+    }
+    if inputs.is_empty() {
+        inputs.push(Input::Stdin);
+    }
+    Ok(Some(Args { command, format, inputs }))
+}
+
+/// Runs the command on one input and returns whether it succeeded.
+fn run(
+    command: &Command,
+    input: &Input,
+    source: &str,
+    linter: &mut Linter,
+    reporter: &mut Reporter,
+) -> io::Result<bool> {
+    match command {
+        Command::Parse => {
+            let ParseResult { program, errors } = Parser::new(source).parse();
+            println!("{program:#?}");
+            Ok(reporter.report(input, source, errors))
+        }
+        Command::Check { fix: false, .. } => {
+            Ok(reporter.report(input, source, check(input, source, linter)))
+        }
+        Command::Check { fix: true, .. } => {
+            let fixed = apply_fixes(source, &check(input, source, linter));
+            match input {
+                Input::File(path) if fixed != source => fs::write(path, &fixed)?,
+                Input::File(_) => (),
+                Input::Stdin => print!("{fixed}"),
+            }
+            // Only the problems that could not be fixed are reported.
+            Ok(reporter.report(input, &fixed, check(input, &fixed, linter)))
+        }
+        Command::Fmt { check, write } => {
+            let formatted = match Formatter::default().format(source) {
+                Ok(formatted) => formatted,
+                Err(errors) => return Ok(reporter.report(input, source, errors)),
+            };
+            match (input, check, write) {
+                (_, true, _) if formatted != source => {
+                    eprintln!("{} is not formatted", input.name());
+                    return Ok(false);
+                }
+                (_, true, _) => (),
+                (Input::File(path), _, true) if formatted != source => {
+                    fs::write(path, formatted)?;
+                }
+                (Input::File(_), _, true) => (),
+                _ => print!("{formatted}"),
+            }
+            Ok(true)
+        }
+        Command::Minify => {
+            let ParseResult { program, errors } = Parser::new(source).parse();
+            if !errors.is_empty() {
+                return Ok(reporter.report(input, source, errors));
+            }
+            let options = CodegenOptions { minify: true, ..Default::default() };
+            println!("{}", Codegen::default().with_options(options).build(&program));
+            Ok(true)
+        }
+        Command::Explain(_) => unreachable!("explained before reading inputs"),
+        Command::Transform => {
+            let ParseResult { mut program, errors } = Parser::new(source).parse();
+            if !errors.is_empty() {
+                return Ok(reporter.report(input, source, errors));
+            }
+            MolangTransformer::default().transform(&mut program);
+            let options = CodegenOptions { minify: false, ..Default::default() };
+            let output = Codegen::default().with_options(options).build(&program);
+            if output.ends_with('\n') {
+                print!("{output}")
+            } else {
+                println!("{output}")
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// Returns the syntax and semantic errors and the lints of the input.
+fn check(input: &Input, source: &str, linter: &mut Linter) -> Vec<Diagnostic> {
+    if input.is_json() {
+        let mut errors = json::check(source);
+        for molang in json::extract(source).unwrap_or_default() {
+            let program = molang.parse().program;
+            let lints = linter.lint(&program);
+            errors.extend(lints.into_iter().map(|lint| molang.to_json_diagnostic(lint)));
+        }
+        return errors;
+    }
+    let ParseResult { program, mut errors } = Parser::new(source).parse();
+    errors.extend(SemanticChecker::default().check(&program));
+    errors.extend(linter.lint(&program));
+    errors
+}
+
+/// Prints diagnostics in the requested format.
+struct Reporter {
+    format: Format,
+    /// Diagnostics of every input, for formats printed all at once.
+    report: Report,
+}
+
+impl Reporter {
+    /// Prints or collects the diagnostics and returns whether none of them
+    /// are errors.
+    fn report(&mut self, input: &Input, source: &str, errors: Vec<Diagnostic>) -> bool {
+        let success = errors.iter().all(|error| error.severity != Severity::Error);
+        match self.format {
+            Format::Human => {
+                for error in errors {
+                    let source = NamedSource::new(input.name(), source.to_string());
+                    eprintln!("{:?}", error.with_source_code(source));
+                }
+            }
+            Format::Short => {
+                let index = LineIndex::new(source);
+                for error in errors {
+                    println!("{}", error.to_short_string(&input.name(), &index));
+                }
+            }
+            Format::Json | Format::Sarif => {
+                self.report.add_file(input.name(), source, errors);
+            }
+        }
+        success
+    }
+
+    fn finish(self) {
+        match self.format {
+            Format::Human | Format::Short => (),
+            Format::Json => println!("{}", self.report.to_json()),
+            Format::Sarif => println!("{}", self.report.to_sarif()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Option<Args>, String> {
+        parse_args(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse("check a.molang - b.molang"),
+            Ok(Some(Args {
+                command: Command::Check { fix: false, config: None },
+                format: Format::Human,
+                inputs: vec![
+                    Input::File("a.molang".into()),
+                    Input::Stdin,
+                    Input::File("b.molang".into())
+                ],
+            }))
+        );
+        assert_eq!(
+            parse("fmt --check --format sarif"),
+            Ok(Some(Args {
+                command: Command::Fmt { check: true, write: false },
+                format: Format::Sarif,
+                inputs: vec![Input::Stdin],
+            }))
+        );
+        assert_eq!(parse("minify --help"), Ok(None));
+        assert!(parse("").is_err());
+        assert!(parse("lint").is_err());
+        assert!(parse("minify --write").is_err());
+        assert_eq!(
+            parse("check --fix").map(|args| args.unwrap().command),
+            Ok(Command::Check { fix: true, config: None })
+        );
+        assert_eq!(
+            parse("check --config lint.cfg").map(|args| args.unwrap().command),
+            Ok(Command::Check { fix: false, config: Some("lint.cfg".into()) })
+        );
+        assert!(parse("check --config").is_err());
+        assert!(parse("fmt --config lint.cfg").is_err());
+        assert!(parse("fmt --check --write").is_err());
+        assert!(parse("check --format").is_err());
+        assert!(parse("check --format xml").is_err());
+        assert_eq!(
+            parse("explain nolana::parse::unexpected_token"),
+            Ok(Some(Args {
+                command: Command::Explain(Some("nolana::parse::unexpected_token".into())),
+                format: Format::Human,
+                inputs: Vec::new(),
+            }))
+        );
+        assert!(parse("explain a b").is_err());
+    }
+
+    #[test]
+    fn test_io_error_skips_input() {
+        let path = env::temp_dir().join(format!("nolana-test-{}.molang", std::process::id()));
+        fs::write(&path, "v.a = ;").unwrap();
+        let args = Args {
+            command: Command::Check { fix: false, config: None },
+            format: Format::Json,
+            inputs: vec![Input::File(path.with_extension("missing")), Input::File(path.clone())],
+        };
+        let mut reporter = Reporter { format: args.format, report: Report::default() };
+        let success = run_inputs(&args, &mut Linter::default(), &mut reporter);
+        fs::remove_file(path).unwrap();
+        assert!(!success);
+        assert!(!reporter.report.is_empty());
+    }
+}
@@ -1,14 +1,51 @@
 use std::iter;
 
-use crate::ast::*;
+use crate::{ast::*, cst::Cst, span::Span};
 
 pub struct CodegenOptions {
+    /// Removes all optional whitespace.
     pub minify: bool,
+    /// Prints `v`, `t`, `c` and `q` instead of `variable`, `temp`, `context`
+    /// and `query`. Defaults to [`minify`](Self::minify) when not set.
+    pub short_names: Option<bool>,
+    /// The number of spaces per indentation level.
+    pub indent_width: usize,
+    /// Long call argument lists and binary chains are broken up into multiple
+    /// lines to fit within this width. `0` disables wrapping.
+    ///
+    /// Has no effect when minifying.
+    pub max_line_width: usize,
 }
 
 impl Default for CodegenOptions {
     fn default() -> Self {
-        Self { minify: true }
+        Self { minify: true, short_names: None, indent_width: 4, max_line_width: 0 }
+    }
+}
+
+impl CodegenOptions {
+    pub fn with_minify(mut self, minify: bool) -> Self {
+        self.minify = minify;
+        self
+    }
+
+    pub fn with_short_names(mut self, short_names: bool) -> Self {
+        self.short_names = Some(short_names);
+        self
+    }
+
+    pub fn with_indent_width(mut self, indent_width: usize) -> Self {
+        self.indent_width = indent_width;
+        self
+    }
+
+    pub fn with_max_line_width(mut self, max_line_width: usize) -> Self {
+        self.max_line_width = max_line_width;
+        self
+    }
+
+    fn short_names(&self) -> bool {
+        self.short_names.unwrap_or(self.minify)
     }
 }
 
@@ -18,6 +55,20 @@ pub struct Codegen {
     code: String,
     is_complex: bool,
     indent: usize,
+    comments: Vec<Comment>,
+    /// Index of the first comment that has not been printed yet.
+    next_comment: usize,
+    /// Set while trying to print an expression on a single line.
+    is_flat: bool,
+}
+
+/// A comment collected from a [`Cst`].
+struct Comment {
+    span: Span,
+    text: String,
+    /// For comments on the same line after a statement, the end of that
+    /// statement.
+    trailing: Option<u32>,
 }
 
 impl Codegen {
@@ -33,6 +84,35 @@ impl Codegen {
         self
     }
 
+    /// Keeps the comments of the source code the program was parsed from.
+    ///
+    /// Comments are printed on their own line before the statement that
+    /// follows them, or after a statement if they were on the same line.
+    /// They are ignored when minifying.
+    pub fn with_comments(mut self, cst: &Cst) -> Self {
+        let tokens = cst.tokens();
+        for (index, token) in tokens.iter().enumerate() {
+            let comments = token.leading_trivia.iter().filter(|trivia| trivia.kind.is_comment());
+            self.comments.extend(comments.map(|trivia| Comment {
+                span: trivia.span,
+                text: trivia.text.to_string(),
+                trailing: None,
+            }));
+            // A trailing comment after `;` belongs to the statement before it.
+            let statement_end = match token.text {
+                ";" if index > 0 => tokens[index - 1].span.end,
+                _ => token.span.end,
+            };
+            let comments = token.trailing_trivia.iter().filter(|trivia| trivia.kind.is_comment());
+            self.comments.extend(comments.map(|trivia| Comment {
+                span: trivia.span,
+                text: trivia.text.to_string(),
+                trailing: Some(statement_end),
+            }));
+        }
+        self
+    }
+
     #[inline]
     fn indent(&mut self) {
         self.indent += 1;
@@ -46,10 +126,15 @@ impl Codegen {
     #[inline]
     fn print_indent(&mut self) {
         if !self.options.minify && self.is_complex {
-            self.code.extend(iter::repeat_n("    ", self.indent))
+            self.print_indent_unchecked();
         }
     }
 
+    #[inline]
+    fn print_indent_unchecked(&mut self) {
+        self.code.extend(iter::repeat_n(' ', self.indent * self.options.indent_width))
+    }
+
     #[inline]
     fn print_str(&mut self, s: &str) {
         self.code.push_str(s);
@@ -67,6 +152,12 @@ impl Codegen {
         }
     }
 
+    /// Starts a new line when wrapping an expression.
+    fn print_line_break(&mut self) {
+        self.code.push('\n');
+        self.print_indent_unchecked();
+    }
+
     #[inline]
     fn print_space(&mut self) {
         if !self.options.minify {
@@ -111,6 +202,16 @@ impl Codegen {
         self.print_char(close);
     }
 
+    /// Prints an operand, wrapping it in parentheses if the parser would not
+    /// read it back as a single operand.
+    fn print_operand(&mut self, expr: &Expression, needs_parens: bool) {
+        if needs_parens {
+            self.print_wrapped('(', ')', |c| expr.print(c));
+        } else {
+            expr.print(self);
+        }
+    }
+
     fn print_scope(&mut self, open: char, close: char, f: impl FnOnce(&mut Self)) {
         self.print_wrapped(open, close, |c| {
             c.print_newline();
@@ -120,6 +221,74 @@ impl Codegen {
             c.print_indent();
         });
     }
+
+    /// Prints statements along with the comments before `end`.
+    fn print_statements(&mut self, stmts: &[Statement], end: u32) {
+        for stmt in stmts {
+            self.print_leading_comments(stmt.span().start);
+            stmt.print(self);
+        }
+        self.print_leading_comments(end);
+    }
+
+    /// Prints the comments before `offset`, each on its own line.
+    fn print_leading_comments(&mut self, offset: u32) {
+        if self.options.minify {
+            return;
+        }
+        while let Some(comment) = self.comments.get(self.next_comment)
+            && comment.span.start < offset
+        {
+            let text = comment.text.clone();
+            if self.code.ends_with(|ch| ch != '\n') {
+                self.code.push('\n');
+            }
+            self.print_indent_unchecked();
+            self.print_str(&text);
+            self.code.push('\n');
+            self.next_comment += 1;
+        }
+    }
+
+    /// Prints the comments on the same line after a statement ending at
+    /// `end`.
+    fn print_trailing_comments(&mut self, end: u32) {
+        if self.options.minify {
+            return;
+        }
+        while let Some(comment) = self.comments.get(self.next_comment)
+            && comment.trailing == Some(end)
+        {
+            let text = comment.text.clone();
+            self.code.push(' ');
+            self.print_str(&text);
+            self.next_comment += 1;
+        }
+    }
+
+    /// The width of the current line.
+    fn column(&self) -> usize {
+        self.code.len() - self.code.rfind('\n').map_or(0, |index| index + 1)
+    }
+
+    /// Prints with `flat`, then reprints with `broken` if the line is longer
+    /// than [`CodegenOptions::max_line_width`].
+    fn print_fitting(&mut self, flat: impl FnOnce(&mut Self), broken: impl FnOnce(&mut Self)) {
+        if self.options.minify || self.options.max_line_width == 0 || self.is_flat {
+            flat(self);
+            return;
+        }
+        let (start, column, next_comment) = (self.code.len(), self.column(), self.next_comment);
+        self.is_flat = true;
+        flat(self);
+        self.is_flat = false;
+        let printed = &self.code[start..];
+        if column + printed.find('\n').unwrap_or(printed.len()) > self.options.max_line_width {
+            self.code.truncate(start);
+            self.next_comment = next_comment;
+            broken(self);
+        }
+    }
 }
 
 /// Generate code for an AST node.
@@ -130,20 +299,25 @@ trait Print {
 impl Print for Program<'_> {
     fn print(&self, c: &mut Codegen) {
         match &self.body {
-            ProgramBody::Simple(expr) => expr.print(c),
-            ProgramBody::Complex(stmts) => {
-                for stmt in stmts {
-                    stmt.print(c);
-                }
+            ProgramBody::Simple(expr) => {
+                c.print_leading_comments(expr.span().start);
+                expr.print(c);
+                c.print_trailing_comments(expr.span().end);
             }
+            ProgramBody::Complex(stmts) => c.print_statements(stmts, u32::MAX),
             ProgramBody::Empty => (),
         }
+        c.print_leading_comments(u32::MAX);
     }
 }
 
 impl Print for Statement<'_> {
     fn print(&self, c: &mut Codegen) {
-        c.print_indent();
+        // Syntax errors are left out, like empty statements.
+        let is_empty = matches!(self, Statement::Empty(_) | Statement::Error(_));
+        if !is_empty {
+            c.print_indent();
+        }
         match self {
             Statement::Expression(stmt) => stmt.print(c),
             Statement::Assignment(stmt) => stmt.print(c),
@@ -153,9 +327,11 @@ impl Print for Statement<'_> {
             Statement::Break(stmt) => stmt.print(c),
             Statement::Continue(stmt) => stmt.print(c),
             Statement::Empty(stmt) => stmt.print(c),
+            Statement::Error(_) => (),
         }
-        if c.is_complex && !matches!(self, Statement::Empty(_)) {
+        if c.is_complex && !is_empty {
             c.print_semi();
+            c.print_trailing_comments(self.span().end);
             c.print_newline();
         }
     }
@@ -246,6 +422,7 @@ impl Print for Expression<'_> {
             Self::ArrowAccess(expr) => expr.print(c),
             Self::Call(expr) => expr.print(c),
             Self::This(expr) => expr.print(c),
+            Self::Error(_) => (),
         }
     }
 }
@@ -258,7 +435,7 @@ impl Print for Identifier<'_> {
 
 impl Print for NumericLiteral<'_> {
     fn print(&self, c: &mut Codegen) {
-        c.print_str(self.raw);
+        c.print_str(&self.raw);
     }
 }
 
@@ -270,7 +447,7 @@ impl Print for BooleanLiteral {
 
 impl Print for StringLiteral<'_> {
     fn print(&self, c: &mut Codegen) {
-        c.print_wrapped('\'', '\'', |c| c.print_str(self.value));
+        c.print_wrapped('\'', '\'', |c| c.print_str(&self.value));
     }
 }
 
@@ -284,7 +461,7 @@ impl Print for VariableExpression<'_> {
 
 impl Print for VariableLifetime {
     fn print(&self, c: &mut Codegen) {
-        c.print_str(if c.options.minify { self.as_str_short() } else { self.as_str_long() });
+        c.print_str(if c.options.short_names() { self.as_str_short() } else { self.as_str_long() });
     }
 }
 
@@ -305,22 +482,12 @@ impl Print for VariableMember<'_> {
 
 impl Print for ParenthesizedExpression<'_> {
     fn print(&self, c: &mut Codegen) {
-        self.body.print(c);
-    }
-}
-
-impl Print for ParenthesizedBody<'_> {
-    fn print(&self, c: &mut Codegen) {
-        match self {
-            Self::Single(expression) => {
+        match &self.body {
+            ParenthesizedBody::Single(expression) => {
                 c.print_wrapped('(', ')', |c| expression.print(c));
             }
-            Self::Multiple(statements) => {
-                c.print_scope('(', ')', |c| {
-                    for stmt in statements {
-                        stmt.print(c);
-                    }
-                });
+            ParenthesizedBody::Multiple(statements) => {
+                c.print_scope('(', ')', |c| c.print_statements(statements, self.span.end));
             }
         }
     }
@@ -328,21 +495,48 @@ impl Print for ParenthesizedBody<'_> {
 
 impl Print for BlockExpression<'_> {
     fn print(&self, c: &mut Codegen) {
-        c.print_scope('{', '}', |c| {
-            for stmt in &self.statements {
-                stmt.print(c);
-            }
-        });
+        c.print_scope('{', '}', |c| c.print_statements(&self.statements, self.span.end));
     }
 }
 
 impl Print for BinaryExpression<'_> {
     fn print(&self, c: &mut Codegen) {
-        self.left.print(c);
+        c.print_fitting(|c| self.print_flat(c), |c| self.print_chain(c));
+    }
+}
+
+impl BinaryExpression<'_> {
+    fn print_flat(&self, c: &mut Codegen) {
+        let (lbp, rbp) = self.operator.binding_power();
+        c.print_operand(&self.left, right_binding_power(&self.left) <= lbp);
         c.print_space();
         self.operator.print(c);
         c.print_space();
-        self.right.print(c);
+        c.print_operand(&self.right, left_binding_power(&self.right) < rbp);
+    }
+
+    /// Prints a chain of operators with the same precedence, such as
+    /// `a && b && c`, with each operator on its own line.
+    fn print_chain(&self, c: &mut Codegen) {
+        let lbp = self.operator.binding_power().0;
+        let mut chain = vec![self];
+        while let Expression::Binary(left) = &chain.last().unwrap().left
+            && left.operator.binding_power().0 == lbp
+            && right_binding_power(&chain.last().unwrap().left) > lbp
+        {
+            chain.push(left);
+        }
+        let first = &chain.last().unwrap().left;
+        c.print_operand(first, right_binding_power(first) <= lbp);
+        c.indent();
+        for expr in chain.iter().rev() {
+            c.print_line_break();
+            expr.operator.print(c);
+            c.print_space();
+            let rbp = expr.operator.binding_power().1;
+            c.print_operand(&expr.right, left_binding_power(&expr.right) < rbp);
+        }
+        c.dedent();
     }
 }
 
@@ -355,12 +549,17 @@ impl Print for BinaryOperator {
 impl Print for UnaryExpression<'_> {
     fn print(&self, c: &mut Codegen) {
         self.operator.print(c);
-        self.argument.print(c);
+        let needs_parens = left_binding_power(&self.argument) < self.operator.binding_power();
+        c.print_operand(&self.argument, needs_parens);
     }
 }
 
 impl Print for UnaryOperator {
     fn print(&self, c: &mut Codegen) {
+        // `a - -b` must not be minified into `a--b`.
+        if *self == UnaryOperator::Negate && c.code.ends_with('-') {
+            c.print_char(' ');
+        }
         c.print_str(self.as_str());
     }
 }
@@ -380,11 +579,11 @@ impl Print for UpdateOperator {
 
 impl Print for TernaryExpression<'_> {
     fn print(&self, c: &mut Codegen) {
-        self.test.print(c);
+        c.print_operand(&self.test, right_binding_power(&self.test) <= QUESTION_BINDING_POWER);
         c.print_space();
         c.print_char('?');
         c.print_space();
-        self.consequent.print(c);
+        c.print_operand(&self.consequent, ends_with_conditional(&self.consequent));
         c.print_space();
         c.print_colon();
         c.print_space();
@@ -394,7 +593,7 @@ impl Print for TernaryExpression<'_> {
 
 impl Print for ConditionalExpression<'_> {
     fn print(&self, c: &mut Codegen) {
-        self.test.print(c);
+        c.print_operand(&self.test, right_binding_power(&self.test) <= QUESTION_BINDING_POWER);
         c.print_space();
         c.print_char('?');
         c.print_space();
@@ -423,7 +622,7 @@ impl Print for ArrayAccessExpression<'_> {
 
 impl Print for ArrowAccessExpression<'_> {
     fn print(&self, c: &mut Codegen) {
-        self.left.print(c);
+        c.print_operand(&self.left, right_binding_power(&self.left) != u8::MAX);
         c.print_str("->");
         self.right.print(c);
     }
@@ -434,15 +633,35 @@ impl Print for CallExpression<'_> {
         self.kind.print(c);
         c.print_dot();
         self.callee.print(c);
-        if let Some(args) = &self.arguments {
-            c.print_wrapped('(', ')', |c| c.print_list(args));
+        if let Some(args) = &self.arguments
+            && !args.is_empty()
+        {
+            c.print_fitting(
+                |c| c.print_wrapped('(', ')', |c| c.print_list(args)),
+                |c| {
+                    c.print_char('(');
+                    c.indent();
+                    for (index, arg) in args.iter().enumerate() {
+                        if index != 0 {
+                            c.print_comma();
+                        }
+                        c.print_line_break();
+                        arg.print(c);
+                    }
+                    c.dedent();
+                    c.print_line_break();
+                    c.print_char(')');
+                },
+            );
+        } else if self.arguments.is_some() {
+            c.print_str("()");
         }
     }
 }
 
 impl Print for CallKind {
     fn print(&self, c: &mut Codegen) {
-        c.print_str(if c.options.minify { self.as_str_short() } else { self.as_str_long() });
+        c.print_str(if c.options.short_names() { self.as_str_short() } else { self.as_str_long() });
     }
 }
 
@@ -451,3 +670,50 @@ impl Print for ThisExpression {
         c.print_str("this");
     }
 }
+
+/// The left binding power of `?`, see [`Kind::binding_power`][`crate::token::Kind::binding_power`].
+const QUESTION_BINDING_POWER: u8 = 3;
+
+/// The lowest binding power an operator before `expr` may have for `expr` to
+/// be read as its operand without parentheses.
+fn left_binding_power(expr: &Expression) -> u8 {
+    match expr {
+        Expression::Binary(expr) => expr.operator.binding_power().0,
+        Expression::Ternary(_) | Expression::Conditional(_) => QUESTION_BINDING_POWER,
+        _ => u8::MAX,
+    }
+}
+
+/// The parser keeps reading operators after `expr` as part of it if their
+/// left binding power is at least this.
+fn right_binding_power(expr: &Expression) -> u8 {
+    let (rbp, right) = match expr {
+        Expression::Binary(expr) => (expr.operator.binding_power().1, &expr.right),
+        Expression::Unary(expr) => (expr.operator.binding_power(), &expr.argument),
+        // The last operand of these is parsed greedily.
+        Expression::Ternary(_) | Expression::Conditional(_) | Expression::ArrowAccess(_) => {
+            return 0;
+        }
+        _ => return u8::MAX,
+    };
+    if left_binding_power(right) < rbp { rbp } else { rbp.min(right_binding_power(right)) }
+}
+
+/// Whether `expr` ends with a conditional expression that is printed without
+/// parentheses, which would take the `:` of an enclosing ternary.
+fn ends_with_conditional(expr: &Expression) -> bool {
+    match expr {
+        Expression::Conditional(_) => true,
+        Expression::Ternary(expr) => ends_with_conditional(&expr.alternate),
+        Expression::ArrowAccess(expr) => ends_with_conditional(&expr.right),
+        Expression::Binary(expr) => {
+            left_binding_power(&expr.right) >= expr.operator.binding_power().1
+                && ends_with_conditional(&expr.right)
+        }
+        Expression::Unary(expr) => {
+            left_binding_power(&expr.argument) >= expr.operator.binding_power()
+                && ends_with_conditional(&expr.argument)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,260 @@
+use std::mem;
+
+use crate::{
+    ast::*,
+    eval::{Value, binary_operation, math},
+    replace_with::ReplaceWith,
+    span::SPAN,
+    traverse::{Control, Traverse, traverse},
+};
+
+/// Folds constant subtrees and applies safe algebraic identities.
+///
+/// This is best run after [`MolangTransformer`][`crate::MolangTransformer`],
+/// which lowers `<<` and `>>` into chains such as `v.x * math.pow(2, 3)` that
+/// collapse into `v.x * 8`.
+///
+/// Identities such as `x * 1` and `x + 0` assume `x` is a number, like the
+/// rest of Molang's arithmetic does.
+#[derive(Default)]
+pub struct ConstantFolder {
+    /// Whether the next expression entered is the left side of `??`.
+    entering_coalesce_left: bool,
+    /// For every expression being traversed, whether it is the left side of
+    /// `??`. Folding these into a bare variable would change the meaning of
+    /// `??`, because only variables fall through to the right side.
+    coalesce_left: Vec<bool>,
+}
+
+impl ConstantFolder {
+    pub fn fold(&mut self, program: &mut Program<'_>) {
+        traverse(self, program);
+    }
+}
+
+impl<'src> Traverse<'src> for ConstantFolder {
+    fn exit_statements(&mut self, it: &mut Vec<Statement<'src>>) {
+        for stmt in it {
+            if let Statement::Expression(expr) = stmt
+                && constant(expr).is_some()
+            {
+                *stmt = EmptyStatement { span: expr.span() }.into();
+            }
+        }
+    }
+
+    fn enter_expression(&mut self, _: &mut Expression<'src>) -> Control {
+        let is_coalesce_left = mem::take(&mut self.entering_coalesce_left);
+        self.coalesce_left.push(is_coalesce_left);
+        Control::Continue
+    }
+
+    fn exit_expression(&mut self, it: &mut Expression<'src>) {
+        let keep_variables = self.coalesce_left.pop().unwrap();
+        it.replace_with(|expr| fold_expression(expr, keep_variables));
+    }
+
+    fn enter_binary_expression(&mut self, it: &mut BinaryExpression<'src>) -> Control {
+        self.entering_coalesce_left = it.operator == BinaryOperator::Coalesce;
+        Control::Continue
+    }
+}
+
+/// Folds an expression whose children have already been folded.
+fn fold_expression(expr: Expression<'_>, keep_variables: bool) -> Expression<'_> {
+    let select = |expr: &Expression| !(keep_variables && matches!(expr, Expression::Variable(_)));
+    match expr {
+        Expression::Parenthesized(paren_expr) => match paren_expr.body {
+            ParenthesizedBody::Single(expr) if is_primary(&expr) && select(&expr) => expr,
+            body => ParenthesizedExpression { span: paren_expr.span, body }.into(),
+        },
+        Expression::Unary(unary_expr) => fold_unary_expression(*unary_expr, select),
+        Expression::Binary(bin_expr) => fold_binary_expression(*bin_expr, select),
+        Expression::Ternary(ternary_expr) => match constant(&ternary_expr.test) {
+            Some(test) if test.is_truthy() && select(&ternary_expr.consequent) => {
+                ternary_expr.consequent
+            }
+            Some(test) if !test.is_truthy() && select(&ternary_expr.alternate) => {
+                ternary_expr.alternate
+            }
+            _ => Expression::Ternary(ternary_expr),
+        },
+        Expression::Conditional(cond_expr) => match constant(&cond_expr.test) {
+            Some(test) if !test.is_truthy() => number_expression(0.0),
+            Some(_) if select(&cond_expr.consequent) => cond_expr.consequent,
+            _ => Expression::Conditional(cond_expr),
+        },
+        Expression::Call(call_expr) => match fold_math_call(&call_expr) {
+            Some(value) => number_expression(value),
+            None => Expression::Call(call_expr),
+        },
+        expr => expr,
+    }
+}
+
+fn fold_unary_expression<'src>(
+    unary_expr: UnaryExpression<'src>,
+    select: impl Fn(&Expression<'src>) -> bool,
+) -> Expression<'src> {
+    let value = constant(&unary_expr.argument).and_then(|value| value.as_number());
+    match (unary_expr.operator, &unary_expr.argument, value) {
+        // `-1` is already as folded as it gets.
+        (UnaryOperator::Negate, Expression::NumericLiteral(_), _) => unary_expr.into(),
+        (UnaryOperator::Negate, _, Some(value)) => number_expression(-value),
+        (UnaryOperator::BitwiseNot, _, Some(value)) => number_expression(!(value as i32) as f32),
+        (UnaryOperator::Not, argument, _) => {
+            if let Some(value) = constant(argument) {
+                return number_expression(if value.is_truthy() { 0.0 } else { 1.0 });
+            }
+            match unary_expr.argument {
+                // `!!x` is `x` when `x` is already `0` or `1`.
+                Expression::Unary(inner)
+                    if inner.operator == UnaryOperator::Not
+                        && is_boolean(&inner.argument)
+                        && select(&inner.argument) =>
+                {
+                    inner.argument
+                }
+                argument => UnaryExpression { argument, ..unary_expr }.into(),
+            }
+        }
+        _ => unary_expr.into(),
+    }
+}
+
+fn fold_binary_expression<'src>(
+    bin_expr: BinaryExpression<'src>,
+    select: impl Fn(&Expression<'src>) -> bool,
+) -> Expression<'src> {
+    use BinaryOperator::*;
+
+    let left = constant(&bin_expr.left);
+    let right = constant(&bin_expr.right);
+    if let (Some(left), Some(right)) = (left.clone(), right.clone())
+        && let Ok(Value::Number(value)) = binary_operation(left, bin_expr.operator, right, SPAN)
+        && value.is_finite()
+    {
+        return number_expression(value);
+    }
+
+    let is = |value: &Option<Value>, expected: f32| {
+        value.as_ref().and_then(Value::as_number) == Some(expected)
+    };
+    let BinaryExpression { left: left_expr, operator, right: right_expr, .. } = &bin_expr;
+    match operator {
+        // Only unset variables fall through to the right side.
+        Coalesce if left.is_some() => bin_expr.left,
+        And | Or if left.is_some() => {
+            let short_circuits = left.unwrap().is_truthy() == (*operator == Or);
+            if short_circuits {
+                number_expression(if *operator == Or { 1.0 } else { 0.0 })
+            } else if is_boolean(right_expr) {
+                bin_expr.right
+            } else {
+                bin_expr.into()
+            }
+        }
+        Multiplication if is(&left, 1.0) && select(right_expr) => bin_expr.right,
+        Multiplication | Division if is(&right, 1.0) && select(left_expr) => bin_expr.left,
+        Addition if is(&left, 0.0) && select(right_expr) => bin_expr.right,
+        Addition | Subtraction if is(&right, 0.0) && select(left_expr) => bin_expr.left,
+        _ => bin_expr.into(),
+    }
+}
+
+/// Calls a deterministic `math.*` function whose arguments are all constant.
+fn fold_math_call(call_expr: &CallExpression) -> Option<f32> {
+    let arguments = call_expr.arguments.as_deref()?;
+    if call_expr.kind != CallKind::Math || arguments.is_empty() {
+        return None;
+    }
+    let function = math::resolve(&call_expr.callee.name, arguments.len()).ok()?;
+    if function.is_random() {
+        return None;
+    }
+    let arguments = arguments
+        .iter()
+        .map(|argument| constant(argument)?.as_number())
+        .collect::<Option<Vec<_>>>()?;
+    let value = function.call(&arguments, &mut math::Random::default());
+    value.is_finite().then_some(value)
+}
+
+/// Returns the value of a literal, including negative numbers and literals
+/// wrapped in parentheses.
+fn constant(expr: &Expression) -> Option<Value> {
+    match expr {
+        Expression::NumericLiteral(expr) => Some(expr.value.into()),
+        Expression::BooleanLiteral(expr) => Some(expr.value.into()),
+        Expression::StringLiteral(expr) => Some(expr.value.as_ref().into()),
+        Expression::Unary(expr)
+            if expr.operator == UnaryOperator::Negate
+                && matches!(expr.argument, Expression::NumericLiteral(_)) =>
+        {
+            constant(&expr.argument)
+                .and_then(|value| value.as_number())
+                .map(|value| (-value).into())
+        }
+        Expression::Parenthesized(expr) => match &expr.body {
+            ParenthesizedBody::Single(expr) => constant(expr),
+            ParenthesizedBody::Multiple(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether an expression always evaluates to `0` or `1`.
+fn is_boolean(expr: &Expression) -> bool {
+    use BinaryOperator::*;
+    match expr {
+        Expression::BooleanLiteral(_) => true,
+        Expression::Unary(expr) => expr.operator == UnaryOperator::Not,
+        Expression::Binary(expr) => matches!(
+            expr.operator,
+            Equality
+                | Inequality
+                | LessThan
+                | LessEqualThan
+                | GreaterThan
+                | GreaterEqualThan
+                | And
+                | Or
+        ),
+        Expression::Parenthesized(expr) => match &expr.body {
+            ParenthesizedBody::Single(expr) => is_boolean(expr),
+            ParenthesizedBody::Multiple(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether an expression never needs parentheses around it.
+fn is_primary(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::NumericLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::StringLiteral(_)
+            | Expression::Variable(_)
+            | Expression::Parenthesized(_)
+            | Expression::Resource(_)
+            | Expression::ArrayAccess(_)
+            | Expression::Call(_)
+            | Expression::This(_)
+    )
+}
+
+/// Builds a literal for a folded number. Negative numbers are represented
+/// like the parser does, as a negated literal.
+fn number_expression<'src>(value: f32) -> Expression<'src> {
+    // `-0` would print as `0` anyway.
+    let value = if value == 0.0 { 0.0 } else { value };
+    let literal =
+        NumericLiteral { span: SPAN, value: value.abs(), raw: value.abs().to_string().into() };
+    if value.is_sign_negative() {
+        UnaryExpression { span: SPAN, operator: UnaryOperator::Negate, argument: literal.into() }
+            .into()
+    } else {
+        literal.into()
+    }
+}
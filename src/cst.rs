@@ -0,0 +1,206 @@
+//! Lossless view of Molang source code.
+//!
+//! The [`Parser`][`crate::Parser`] skips whitespace and comments. A [`Cst`]
+//! keeps them as [trivia](Trivia) attached to the surrounding tokens, so the
+//! exact source can be reconstructed from it.
+//!
+//! Every AST node has a [`Span`] which maps it to the tokens it was parsed
+//! from, see [`Cst::tokens_in`], [`Cst::leading_trivia`] and
+//! [`Cst::trailing_trivia`].
+//!
+//! # Example
+//!
+//! ```
+//! # use nolana::{Parser, ast::ProgramBody, cst::Cst};
+//! let source = "// Grow\nv.size = v.size * 2; /* twice */";
+//! let program = Parser::new(source).parse().program;
+//! let cst = Cst::new(source);
+//! assert_eq!(cst.to_string(), source);
+//!
+//! let ProgramBody::Complex(stmts) = &program.body else { unreachable!() };
+//! let span = stmts[0].span();
+//! assert_eq!(cst.leading_trivia(span)[0].text, "// Grow");
+//! ```
+
+use std::{fmt, mem};
+
+use logos::Logos;
+
+use crate::{span::Span, token::Kind};
+
+/// A lossless sequence of tokens. The last token is always an empty end of
+/// file token, which holds the trivia at the end of the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cst<'src> {
+    source: &'src str,
+    tokens: Vec<SyntaxToken<'src>>,
+}
+
+/// A token along with the trivia surrounding it.
+///
+/// Trivia on the same line after a token is trailing trivia. Everything else,
+/// starting with the line break, is leading trivia of the next token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken<'src> {
+    pub span: Span,
+    pub text: &'src str,
+    pub leading_trivia: Vec<Trivia<'src>>,
+    pub trailing_trivia: Vec<Trivia<'src>>,
+}
+
+/// Source code that is not meaningful to the parser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia<'src> {
+    pub kind: TriviaKind,
+    pub span: Span,
+    pub text: &'src str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// `// foo`
+    LineComment,
+    /// `/* foo */`
+    BlockComment,
+}
+
+impl<'src> Cst<'src> {
+    /// Lexes the source code, keeping all trivia.
+    pub fn new(source: &'src str) -> Self {
+        let mut tokens: Vec<SyntaxToken> = Vec::new();
+        let mut lexer = Kind::lexer(source);
+        let mut prev_end = 0;
+        loop {
+            let is_eof = lexer.next().is_none();
+            let span = if is_eof { source.len()..source.len() } else { lexer.span() };
+            let mut leading_trivia = lex_trivia(source, prev_end, span.start);
+            if let Some(prev) = tokens.last_mut() {
+                prev.trailing_trivia = split_at_line_break(&mut leading_trivia);
+            }
+            tokens.push(SyntaxToken {
+                span: Span::new(span.start as u32, span.end as u32),
+                text: &source[span.clone()],
+                leading_trivia,
+                trailing_trivia: Vec::new(),
+            });
+            if is_eof {
+                break;
+            }
+            prev_end = span.end;
+        }
+        Self { source, tokens }
+    }
+
+    pub fn source(&self) -> &'src str {
+        self.source
+    }
+
+    pub fn tokens(&self) -> &[SyntaxToken<'src>] {
+        &self.tokens
+    }
+
+    /// Returns the tokens within `span`, such as the tokens an AST node was
+    /// parsed from.
+    pub fn tokens_in(&self, span: Span) -> &[SyntaxToken<'src>] {
+        let start = self.tokens.partition_point(|token| token.span.start < span.start);
+        // The end of file token is never part of a node.
+        let end = self.tokens.partition_point(|token| token.span.end <= span.end);
+        let end = end.min(self.tokens.len() - 1);
+        &self.tokens[start.min(end)..end]
+    }
+
+    /// Returns the trivia before the first token within `span`.
+    pub fn leading_trivia(&self, span: Span) -> &[Trivia<'src>] {
+        self.tokens_in(span).first().map_or(&[], |token| &token.leading_trivia)
+    }
+
+    /// Returns the trivia after the last token within `span`, up to the end
+    /// of its line.
+    pub fn trailing_trivia(&self, span: Span) -> &[Trivia<'src>] {
+        self.tokens_in(span).last().map_or(&[], |token| &token.trailing_trivia)
+    }
+
+    /// Returns every comment in the source code.
+    pub fn comments(&self) -> impl Iterator<Item = &Trivia<'src>> {
+        self.tokens
+            .iter()
+            .flat_map(|token| token.leading_trivia.iter().chain(&token.trailing_trivia))
+            .filter(|trivia| trivia.kind.is_comment())
+    }
+}
+
+/// Writes back the exact source code.
+impl fmt::Display for Cst<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.tokens {
+            for trivia in &token.leading_trivia {
+                f.write_str(trivia.text)?;
+            }
+            f.write_str(token.text)?;
+            for trivia in &token.trailing_trivia {
+                f.write_str(trivia.text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TriviaKind {
+    pub fn is_comment(self) -> bool {
+        matches!(self, Self::LineComment | Self::BlockComment)
+    }
+}
+
+impl From<TriviaKind> for Kind {
+    fn from(kind: TriviaKind) -> Self {
+        match kind {
+            TriviaKind::Whitespace => Self::Whitespace,
+            TriviaKind::LineComment => Self::LineComment,
+            TriviaKind::BlockComment => Self::BlockComment,
+        }
+    }
+}
+
+/// Splits the source between two tokens, which the lexer has skipped, into
+/// trivia.
+pub(crate) fn lex_trivia(source: &str, start: usize, end: usize) -> Vec<Trivia<'_>> {
+    let mut trivia = Vec::new();
+    let mut offset = start;
+    while offset < end {
+        let rest = &source[offset..end];
+        let (kind, len) = if rest.starts_with("//") {
+            (TriviaKind::LineComment, rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            // The `*/` is searched after the `/*`, so `/*/` does not close itself.
+            (TriviaKind::BlockComment, body.find("*/").map_or(rest.len(), |index| index + 4))
+        } else {
+            let len = rest.find(|ch: char| !ch.is_ascii_whitespace()).unwrap_or(rest.len());
+            // Anything else the lexer skipped is kept as whitespace, so that
+            // every trivia makes progress.
+            (TriviaKind::Whitespace, if len == 0 { rest.len() } else { len })
+        };
+        let span = Span::new(offset as u32, (offset + len) as u32);
+        trivia.push(Trivia { kind, span, text: &source[span] });
+        offset += len;
+    }
+    trivia
+}
+
+/// Removes and returns the trivia before the first line break.
+fn split_at_line_break<'src>(trivia: &mut Vec<Trivia<'src>>) -> Vec<Trivia<'src>> {
+    let Some(index) = trivia.iter().position(|trivia| trivia.text.contains('\n')) else {
+        return mem::take(trivia);
+    };
+    let mut rest = trivia.split_off(index);
+    let first = &mut rest[0];
+    if first.kind == TriviaKind::Whitespace
+        && let Some(line_break) = first.text.find('\n').filter(|&index| index != 0)
+    {
+        let (before, after) = first.text.split_at(line_break);
+        let middle = first.span.start + line_break as u32;
+        trivia.push(Trivia { text: before, span: Span::new(first.span.start, middle), ..*first });
+        *first = Trivia { text: after, span: Span::new(middle, first.span.end), ..*first };
+    }
+    mem::replace(trivia, rest)
+}
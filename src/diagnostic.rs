@@ -1,6 +1,19 @@
+mod code;
+mod fix;
+mod report;
+
 use std::{borrow::Cow, error, fmt, ops};
 
-use miette::{Diagnostic as MietteDiagnostic, LabeledSpan, Severity, SourceCode};
+use miette::{Diagnostic as MietteDiagnostic, LabeledSpan, Severity, SourceCode, SourceSpan};
+
+use crate::{
+    line_index::{Encoding, LineIndex, LineRange},
+    span::Span,
+};
+
+pub use code::Code;
+pub use fix::{Applicability, Edit, Fix, apply_fixes};
+pub use report::Report;
 
 pub type Error = miette::Error;
 
@@ -20,9 +33,17 @@ pub struct Diagnostic {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DiagnosticInner {
     pub message: Cow<'static, str>,
+    /// Identifies the kind of problem, such as
+    /// `nolana::parse::unexpected_token`.
+    pub code: Option<Code>,
+    /// The name of the lint rule that reported the problem, such as
+    /// `max_name_length`.
+    pub lint: Option<&'static str>,
     pub labels: Option<Vec<LabeledSpan>>,
     pub help: Option<Cow<'static, str>>,
     pub severity: Severity,
+    /// Suggested changes that resolve the problem.
+    pub fixes: Vec<Fix>,
 }
 
 impl Diagnostic {
@@ -31,9 +52,12 @@ impl Diagnostic {
         Self {
             inner: Box::new(DiagnosticInner {
                 message: message.into(),
+                code: None,
+                lint: None,
                 labels: None,
                 help: None,
                 severity: Severity::Error,
+                fixes: Vec::new(),
             }),
         }
     }
@@ -43,19 +67,46 @@ impl Diagnostic {
         Self {
             inner: Box::new(DiagnosticInner {
                 message: message.into(),
+                code: None,
+                lint: None,
                 labels: None,
                 help: None,
                 severity: Severity::Warning,
+                fixes: Vec::new(),
             }),
         }
     }
 
+    /// Sets the code identifying the kind of problem.
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.inner.code = Some(code);
+        self
+    }
+
+    /// Sets the name of the lint rule that reported the problem.
+    pub(crate) fn with_lint(mut self, name: &'static str) -> Self {
+        self.inner.lint = Some(name);
+        self
+    }
+
+    /// Sets whether this diagnostic is an error or a warning.
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.inner.severity = severity;
+        self
+    }
+
     /// Sets a possible suggestion for a problem to the user.
     pub fn with_help(mut self, help: impl Into<Cow<'static, str>>) -> Self {
         self.inner.help = Some(help.into());
         self
     }
 
+    /// Appends a suggested change that resolves the problem.
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.inner.fixes.push(fix);
+        self
+    }
+
     /// Sets a label covering the problematic portion of the source code.
     ///
     /// Existing labels will be removed. Use [`Diagnostic::add_label`] to append
@@ -73,6 +124,86 @@ impl Diagnostic {
         self
     }
 
+    /// Returns the identifier of this diagnostic as shown to users: its
+    /// [`Code`], or else the name of the lint rule that reported it.
+    pub fn code_id(&self) -> Option<&'static str> {
+        self.code.map(Code::as_str).or(self.lint)
+    }
+
+    /// Returns the span of the first label.
+    pub fn span(&self) -> Option<Span> {
+        let label = self.labels.as_ref()?.first()?;
+        Some(Span::new(label.offset() as u32, (label.offset() + label.len()) as u32))
+    }
+
+    /// Returns the lines and columns of the first label.
+    pub fn range(&self, index: &LineIndex, encoding: Encoding) -> Option<LineRange> {
+        Some(index.range(self.span()?, encoding))
+    }
+
+    /// Renders this diagnostic on a single line as
+    /// `path:line:column: severity: message`, which is the format CI tools
+    /// and editors pick up. Lines and columns start at 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use nolana::{Parser, line_index::LineIndex};
+    /// let source = "v.a = 1;\nv.b = ;";
+    /// let errors = Parser::new(source).parse().errors;
+    /// let index = LineIndex::new(source);
+    /// assert_eq!(errors[0].to_short_string("a.molang", &index), "a.molang:2:7: error: unexpected token");
+    /// ```
+    pub fn to_short_string(&self, path: &str, index: &LineIndex) -> String {
+        let severity = match self.severity {
+            Severity::Advice => "advice",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.range(index, Encoding::Utf8) {
+            Some(LineRange { start, .. }) => {
+                format!(
+                    "{path}:{}:{}: {severity}: {}",
+                    start.line + 1,
+                    start.column + 1,
+                    self.message
+                )
+            }
+            None => format!("{path}: {severity}: {}", self.message),
+        }
+    }
+
+    /// Moves every label by `delta` bytes.
+    pub(crate) fn shift(self, delta: i64) -> Self {
+        self.map_spans(|span| {
+            let start = (span.start as i64 + delta) as u32;
+            Span::new(start, start + (span.end - span.start))
+        })
+    }
+
+    /// Replaces the span of every label and fix with the result of `f`.
+    pub(crate) fn map_spans(mut self, f: impl Fn(Span) -> Span) -> Self {
+        for label in self.inner.labels.iter_mut().flatten() {
+            let span = Span::new(label.offset() as u32, (label.offset() + label.len()) as u32);
+            let span = SourceSpan::from(f(span));
+            let text = label.label().map(String::from);
+            *label = if label.primary() {
+                LabeledSpan::new_primary_with_span(text, span)
+            } else {
+                LabeledSpan::new_with_span(text, span)
+            };
+        }
+        for edit in self.inner.fixes.iter_mut().flat_map(|fix| &mut fix.edits) {
+            edit.span = f(edit.span);
+        }
+        self
+    }
+
+    /// Returns the edits of every fix.
+    pub(crate) fn edits_mut(&mut self) -> impl Iterator<Item = &mut Edit> {
+        self.inner.fixes.iter_mut().flat_map(|fix| &mut fix.edits)
+    }
+
     /// Adds a source to this diagnostic and converts it into an [`Error`].
     pub fn with_source_code(self, code: impl SourceCode + 'static) -> Error {
         Error::from(self).with_source_code(code)
@@ -96,6 +227,10 @@ impl ops::Deref for Diagnostic {
 }
 
 impl MietteDiagnostic for Diagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.code_id().map(|code| Box::new(code) as Box<dyn fmt::Display>)
+    }
+
     fn severity(&self) -> Option<Severity> {
         Some(self.severity)
     }
@@ -0,0 +1,159 @@
+use std::{fmt, str::FromStr};
+
+macro_rules! codes {
+    ($($name:ident = $code:literal => $explanation:literal,)*) => {
+        /// Identifies the kind of problem a [`Diagnostic`](super::Diagnostic)
+        /// reports.
+        ///
+        /// Codes are stable, so they can be used to suppress or look up
+        /// specific problems. Each code has a long-form [explanation].
+        ///
+        /// [explanation]: Code::explanation
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        pub enum Code {
+            $(
+                #[doc = $explanation]
+                $name,
+            )*
+        }
+
+        impl Code {
+            /// Every code, in the order they are declared.
+            pub const ALL: &[Code] = &[$(Code::$name),*];
+
+            /// Returns the code, such as `nolana::parse::unexpected_token`.
+            pub const fn as_str(self) -> &'static str {
+                match self {
+                    $(Code::$name => $code,)*
+                }
+            }
+
+            /// Explains the problem and how to fix it.
+            pub const fn explanation(self) -> &'static str {
+                match self {
+                    $(Code::$name => $explanation,)*
+                }
+            }
+        }
+    };
+}
+
+codes! {
+    InvalidNumber = "nolana::parse::invalid_number" =>
+        "A number literal could not be read, usually because it is too large or has more than \
+         one `.`.",
+    UnexpectedToken = "nolana::parse::unexpected_token" =>
+        "The parser found a token where it can't start or continue an expression, such as the \
+         `;` in `v.a = ;`.",
+    ExpectedToken = "nolana::parse::expected_token" =>
+        "A specific token is required here, such as the closing `)` of a call or parenthesized \
+         expression.",
+    SemiRequiredInComplex = "nolana::parse::semi_required_in_complex" =>
+        "Complex programs, which contain `=` or `;`, must end every statement with `;`. Simple \
+         programs are a single expression without a `;`: write `v.a = 1; v.b = 2;` instead of \
+         `v.a = 1; v.b = 2`.",
+    SemiRequiredInParenthesized = "nolana::parse::semi_required_in_parenthesized" =>
+        "When statements inside parentheses end with `;`, every one of them must: write \
+         `(v.a = 1; v.b = 2;)` instead of `(v.a = 1; v.b = 2)`.",
+    SemiRequiredInBlockExpression = "nolana::parse::semi_required_in_block_expression" =>
+        "Every statement inside `{ ... }` must end with `;`: write `{ v.a = 1; }` instead of \
+         `{ v.a = 1 }`.",
+    UnterminatedString = "nolana::parse::unterminated_string" =>
+        "A string was opened with `'` but never closed. Molang strings can't contain `'`.",
+    LoopInExpression = "nolana::parse::loop_in_expression" =>
+        "`loop` and `for_each` are statements, so they don't have a value and can't be used \
+         inside expressions. Move them to a statement of their own.",
+    IllegalUpdateOperation = "nolana::parse::illegal_update_operation" =>
+        "`++` and `--` change the value of a variable, so they can only be used on variables \
+         such as `v.a++`.",
+    InvalidForEachFirstArg = "nolana::parse::invalid_for_each_first_arg" =>
+        "The first argument of `for_each` is the variable each element is assigned to, so it \
+         must be a variable: `for_each(t.entity, q.get_nearby_entities(4), { ... });`.",
+    EmptyBlock = "nolana::semantic::empty_block" =>
+        "Block expressions must contain at least one statement. Remove the empty `{}` or add a \
+         statement to it.",
+    IllegalStringBinary = "nolana::semantic::illegal_string_binary" =>
+        "Strings can only be compared with `==` and `!=`. Other operators, such as `+` or `<`, \
+         are not supported on strings.",
+    BreakOutsideLoop = "nolana::semantic::break_outside_loop" =>
+        "`break` exits the innermost `loop` or `for_each`, so it can only be used inside one.",
+    ContinueOutsideLoop = "nolana::semantic::continue_outside_loop" =>
+        "`continue` skips to the next iteration of the innermost `loop` or `for_each`, so it can \
+         only be used inside one.",
+    ContextReadonly = "nolana::semantic::context_readonly" =>
+        "`context.*` variables are provided by the game and can't be assigned to. Use \
+         `variable.*` to keep a value on the entity, or `temp.*` for the current expression.",
+    ForEachWrongFirstArg = "nolana::semantic::for_each_wrong_first_arg" =>
+        "The first argument of `for_each` is assigned each element, so it can't be a read-only \
+         `context.*` variable. Use `variable.*` or `temp.*` instead.",
+    UnknownMathFunction = "nolana::math::unknown_function" =>
+        "The function is not part of the `math.*` library. Check its spelling against the list \
+         of math functions supported by Minecraft.",
+    MathArgumentCount = "nolana::math::argument_count" =>
+        "Math functions take a fixed number of arguments, such as 3 for \
+         `math.clamp(value, min, max)`.",
+    SyntaxError = "nolana::eval::syntax_error" =>
+        "The program contains syntax errors, which have been reported by the parser. Fix them \
+         before evaluating the program.",
+    ExpectedNumber = "nolana::eval::expected_number" =>
+        "The operation only works on numbers, but the value was a string, an entity or an array.",
+    ExpectedArray = "nolana::eval::expected_array" =>
+        "The second argument of `for_each` must evaluate to an array, such as the result of \
+         `q.get_nearby_entities`.",
+    ExpectedEntity = "nolana::eval::expected_entity" =>
+        "The left side of `->` must evaluate to an entity, such as `t.entity` in \
+         `t.entity->q.health`.",
+    UnknownArray = "nolana::eval::unknown_array" =>
+        "The `array.*` resource is not defined by the render controller being evaluated.",
+    UnknownQuery = "nolana::eval::unknown_query" =>
+        "The query is not answered by the query provider. Check its spelling, or add it to the \
+         provider.",
+    EntityVariable = "nolana::eval::entity_variable" =>
+        "`variable.*` on the right side of `->` belongs to the entity on the left side. It can \
+         only be read if the query provider implements `QueryProvider::entity_variable`, and it \
+         can't be assigned to.",
+    NoExponent = "nolana::lint::no_exponent" =>
+        "`**` and `**=` are Nolana extensions that Minecraft doesn't support. Use \
+         `math.pow(a, b)` instead, or rewrite them with the `MolangTransformer`.",
+    MaxNameLength = "nolana::lint::max_name_length" =>
+        "The variable name is longer than the project allows. Long names make expressions hard \
+         to read and increase the size of resource pack files.",
+    MaxLoopCount = "nolana::lint::max_loop_count" =>
+        "The `loop` count is greater than the project allows. Minecraft stops every loop after \
+         1024 iterations, so larger counts don't do what they say.",
+    InvalidLintConfig = "nolana::lint::invalid_config" =>
+        "The lint config could not be applied. Each line must be `rule = level`, where level is \
+         `allow`, `warn` or `deny`, or `rule.option = value`.",
+    InvalidJson = "nolana::json::invalid_json" =>
+        "The JSON file could not be read, so its Molang could not be checked. Comments are \
+         allowed, like in Minecraft.",
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Code {
+    type Err = ();
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        Code::ALL.iter().copied().find(|registered| registered.as_str() == code).ok_or(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_unique() {
+        for (index, code) in Code::ALL.iter().enumerate() {
+            assert_eq!(code.as_str().parse(), Ok(*code), "{index}");
+            assert!(code.as_str().starts_with("nolana::"));
+        }
+        assert_eq!("nolana::parse::nothing".parse::<Code>(), Err(()));
+    }
+}
@@ -0,0 +1,123 @@
+use std::borrow::Cow;
+
+use super::Diagnostic;
+use crate::span::Span;
+
+/// How confident a [`Fix`] is that it does what the user intended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Applicability {
+    /// The fix is definitely what the user intended, so it can be applied
+    /// without asking, like [`apply_fixes`] does.
+    MachineApplicable,
+    /// The fix resolves the problem but may change the meaning of the code,
+    /// so it should be reviewed before being applied.
+    MaybeIncorrect,
+}
+
+/// Replaces the text within `span` with `replacement`. An empty span inserts
+/// the replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: Cow<'static, str>,
+}
+
+/// A suggested change to the source code that resolves a [`Diagnostic`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    /// Describes the change, such as "insert `;`".
+    pub message: Cow<'static, str>,
+    /// Non-overlapping edits, which are applied together.
+    pub edits: Vec<Edit>,
+    pub applicability: Applicability,
+}
+
+impl Fix {
+    /// Creates a fix without any edits.
+    pub fn new(message: impl Into<Cow<'static, str>>, applicability: Applicability) -> Self {
+        Self { message: message.into(), edits: Vec::new(), applicability }
+    }
+
+    /// Adds an edit which replaces the text within `span`.
+    pub fn replace(mut self, span: Span, replacement: impl Into<Cow<'static, str>>) -> Self {
+        self.edits.push(Edit { span, replacement: replacement.into() });
+        self
+    }
+
+    /// Adds an edit which inserts text at `offset`.
+    pub fn insert(self, offset: u32, text: impl Into<Cow<'static, str>>) -> Self {
+        self.replace(Span::new(offset, offset), text)
+    }
+}
+
+/// Applies the [machine applicable] fixes of the diagnostics to the source
+/// code they were reported for.
+///
+/// Fixes that overlap with a fix applied before them are skipped, since their
+/// spans no longer match the source code. Running the checks again and
+/// applying the remaining fixes resolves them too.
+///
+/// # Example
+///
+/// ```
+/// # use nolana::{Parser, diagnostic::apply_fixes};
+/// let source = "v.a = 1; v.b = 2";
+/// let errors = Parser::new(source).parse().errors;
+/// assert_eq!(apply_fixes(source, &errors), "v.a = 1; v.b = 2;");
+/// ```
+///
+/// [machine applicable]: Applicability::MachineApplicable
+pub fn apply_fixes(source: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut edits: Vec<&Edit> = Vec::new();
+    let fixes = diagnostics.iter().flat_map(|diagnostic| &diagnostic.fixes);
+    for fix in fixes.filter(|fix| fix.applicability == Applicability::MachineApplicable) {
+        let overlaps = fix.edits.iter().any(|edit| edits.iter().any(|other| overlap(edit, other)));
+        if !overlaps {
+            edits.extend(&fix.edits);
+        }
+    }
+    edits.sort_by_key(|edit| edit.span.start);
+
+    let mut output = String::with_capacity(source.len());
+    let mut end = 0;
+    for edit in edits {
+        output.push_str(&source[end as usize..edit.span.start as usize]);
+        output.push_str(&edit.replacement);
+        end = edit.span.end;
+    }
+    output.push_str(&source[end as usize..]);
+    output
+}
+
+/// Returns whether applying both edits is ambiguous. Insertions at the same
+/// offset are, because their order is unknown.
+fn overlap(a: &Edit, b: &Edit) -> bool {
+    (a.span.start < b.span.end && b.span.start < a.span.end) || a.span.start == b.span.start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(applicability: Applicability, edits: &[(u32, u32, &'static str)]) -> Diagnostic {
+        let mut fix = Fix::new("fix", applicability);
+        for &(start, end, replacement) in edits {
+            fix = fix.replace(Span::new(start, end), replacement);
+        }
+        Diagnostic::error("error").with_fix(fix)
+    }
+
+    #[test]
+    fn test_apply_fixes() {
+        let source = "abcdef";
+        let diagnostics = [
+            fix(Applicability::MachineApplicable, &[(1, 2, "B"), (4, 4, "_")]),
+            // Overlaps with the first fix.
+            fix(Applicability::MachineApplicable, &[(0, 5, "")]),
+            fix(Applicability::MachineApplicable, &[(4, 4, "-")]),
+            fix(Applicability::MaybeIncorrect, &[(5, 6, "F")]),
+            fix(Applicability::MachineApplicable, &[(6, 6, "!")]),
+        ];
+        assert_eq!(apply_fixes(source, &diagnostics), "aBcd_ef!");
+    }
+}
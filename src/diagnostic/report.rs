@@ -0,0 +1,394 @@
+use std::fmt::{self, Write};
+
+use miette::Severity;
+
+use super::{Applicability, Diagnostic};
+use crate::{
+    line_index::{Encoding, LineColumn, LineIndex},
+    span::Span,
+};
+
+/// Collects the diagnostics of one or more files and serializes them as JSON
+/// or [SARIF], for CI tools to annotate pull requests with.
+///
+/// Both formats use lines and columns starting at 1. JSON columns count
+/// bytes, like [`Diagnostic::to_short_string`], while SARIF columns count
+/// UTF-16 code units, which is the default of the format.
+///
+/// # Example
+///
+/// ```
+/// # use nolana::{Parser, diagnostic::Report};
+/// let source = "v.a = ;";
+/// let errors = Parser::new(source).parse().errors;
+/// let mut report = Report::default();
+/// report.add_file("a.molang", source, errors);
+/// assert!(report.to_json().starts_with(r#"[{"path":"a.molang","code":"#));
+/// assert!(report.to_sarif().contains(r#""version":"2.1.0""#));
+/// ```
+///
+/// [SARIF]: <https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html>
+#[derive(Default)]
+pub struct Report {
+    files: Vec<ReportFile>,
+}
+
+struct ReportFile {
+    path: String,
+    index: LineIndex,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// The rule of diagnostics without a code.
+const DEFAULT_CODE: &str = "nolana";
+
+impl Report {
+    /// Adds the diagnostics of the file at `path`, whose contents are
+    /// `source`.
+    pub fn add_file(
+        &mut self,
+        path: impl Into<String>,
+        source: &str,
+        diagnostics: Vec<Diagnostic>,
+    ) -> &mut Self {
+        let index = LineIndex::new(source);
+        self.files.push(ReportFile { path: path.into(), index, diagnostics });
+        self
+    }
+
+    /// Returns whether no file has diagnostics.
+    pub fn is_empty(&self) -> bool {
+        self.files.iter().all(|file| file.diagnostics.is_empty())
+    }
+
+    fn diagnostics(&self) -> impl Iterator<Item = (&ReportFile, &Diagnostic)> {
+        self.files.iter().flat_map(|file| file.diagnostics.iter().map(move |d| (file, d)))
+    }
+
+    /// Serializes the diagnostics as a JSON array with an object for each
+    /// diagnostic:
+    ///
+    /// ```json
+    /// {
+    ///   "path": "a.molang",
+    ///   "code": "nolana::parse::unexpected_token",
+    ///   "severity": "error",
+    ///   "message": "unexpected token",
+    ///   "help": null,
+    ///   "labels": [{
+    ///     "label": null,
+    ///     "primary": false,
+    ///     "start": { "offset": 6, "line": 1, "column": 7 },
+    ///     "end": { "offset": 7, "line": 1, "column": 8 }
+    ///   }],
+    ///   "fixes": [{
+    ///     "message": "insert `;`",
+    ///     "applicability": "machine_applicable",
+    ///     "edits": [{ "start": ..., "end": ..., "replacement": ";" }]
+    ///   }]
+    /// }
+    /// ```
+    pub fn to_json(&self) -> String {
+        let diagnostics = self.diagnostics().map(|(file, diagnostic)| {
+            let position = |offset| {
+                let LineColumn { line, column } = file.index.line_column(offset, Encoding::Utf8);
+                Json::Object(vec![
+                    ("offset", Json::Number(offset)),
+                    ("line", Json::Number(line + 1)),
+                    ("column", Json::Number(column + 1)),
+                ])
+            };
+            let labels = diagnostic.labels.iter().flatten().map(|label| {
+                let span = label_span(label);
+                Json::Object(vec![
+                    ("label", label.label().into()),
+                    ("primary", Json::Bool(label.primary())),
+                    ("start", position(span.start)),
+                    ("end", position(span.end)),
+                ])
+            });
+            let fixes = diagnostic.fixes.iter().map(|fix| {
+                let edits = fix.edits.iter().map(|edit| {
+                    Json::Object(vec![
+                        ("start", position(edit.span.start)),
+                        ("end", position(edit.span.end)),
+                        ("replacement", edit.replacement.as_ref().into()),
+                    ])
+                });
+                Json::Object(vec![
+                    ("message", fix.message.as_ref().into()),
+                    ("applicability", applicability_name(fix.applicability).into()),
+                    ("edits", Json::Array(edits.collect())),
+                ])
+            });
+            Json::Object(vec![
+                ("path", file.path.as_str().into()),
+                ("code", diagnostic.code_id().into()),
+                ("severity", severity_name(diagnostic.severity).into()),
+                ("message", diagnostic.message.as_ref().into()),
+                ("help", diagnostic.help.as_deref().into()),
+                ("labels", Json::Array(labels.collect())),
+                ("fixes", Json::Array(fixes.collect())),
+            ])
+        });
+        Json::Array(diagnostics.collect()).to_string()
+    }
+
+    /// Serializes the diagnostics as a SARIF 2.1.0 log with a single run.
+    ///
+    /// The first primary label, or else the first label, is the location of
+    /// a result. Other labels are related locations. Fixes of every
+    /// applicability are included.
+    pub fn to_sarif(&self) -> String {
+        let mut rules: Vec<&str> = self
+            .diagnostics()
+            .map(|(_, diagnostic)| diagnostic.code_id().unwrap_or(DEFAULT_CODE))
+            .collect();
+        rules.sort_unstable();
+        rules.dedup();
+
+        let results = self.diagnostics().map(|(file, diagnostic)| {
+            let code = diagnostic.code_id().unwrap_or(DEFAULT_CODE);
+            let message = match &diagnostic.help {
+                Some(help) => format!("{}\nhelp: {help}", diagnostic.message),
+                None => diagnostic.message.to_string(),
+            };
+            let labels = diagnostic.labels.as_deref().unwrap_or_default();
+            let primary = labels.iter().position(|label| label.primary()).unwrap_or(0);
+            let artifact = || Json::Object(vec![("uri", file.path.as_str().into())]);
+            let region = |span: Span| {
+                let start = file.index.line_column(span.start, Encoding::Utf16);
+                let end = file.index.line_column(span.end, Encoding::Utf16);
+                Json::Object(vec![
+                    ("startLine", Json::Number(start.line + 1)),
+                    ("startColumn", Json::Number(start.column + 1)),
+                    ("endLine", Json::Number(end.line + 1)),
+                    ("endColumn", Json::Number(end.column + 1)),
+                ])
+            };
+            let location = |label: &miette::LabeledSpan| {
+                let mut location = vec![(
+                    "physicalLocation",
+                    Json::Object(vec![
+                        ("artifactLocation", artifact()),
+                        ("region", region(label_span(label))),
+                    ]),
+                )];
+                if let Some(text) = label.label() {
+                    location.push(("message", Json::Object(vec![("text", text.into())])));
+                }
+                Json::Object(location)
+            };
+            let related = labels
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| index != primary)
+                .map(|(_, label)| location(label));
+            let fixes = diagnostic.fixes.iter().map(|fix| {
+                let replacements = fix.edits.iter().map(|edit| {
+                    Json::Object(vec![
+                        ("deletedRegion", region(edit.span)),
+                        (
+                            "insertedContent",
+                            Json::Object(vec![("text", edit.replacement.as_ref().into())]),
+                        ),
+                    ])
+                });
+                let change = Json::Object(vec![
+                    ("artifactLocation", artifact()),
+                    ("replacements", Json::Array(replacements.collect())),
+                ]);
+                Json::Object(vec![
+                    ("description", Json::Object(vec![("text", fix.message.as_ref().into())])),
+                    ("artifactChanges", Json::Array(vec![change])),
+                ])
+            });
+            Json::Object(vec![
+                ("ruleId", code.into()),
+                ("ruleIndex", Json::Number(rules.binary_search(&code).unwrap() as u32)),
+                ("level", sarif_level(diagnostic.severity).into()),
+                ("message", Json::Object(vec![("text", message.into())])),
+                ("locations", Json::Array(labels.get(primary).map(location).into_iter().collect())),
+                ("relatedLocations", Json::Array(related.collect())),
+                ("fixes", Json::Array(fixes.collect())),
+            ])
+        });
+
+        let rules = rules.iter().map(|&code| Json::Object(vec![("id", code.into())]));
+        let driver = Json::Object(vec![
+            ("name", "nolana".into()),
+            ("version", env!("CARGO_PKG_VERSION").into()),
+            ("informationUri", env!("CARGO_PKG_REPOSITORY").into()),
+            ("rules", Json::Array(rules.collect())),
+        ]);
+        let run = Json::Object(vec![
+            ("tool", Json::Object(vec![("driver", driver)])),
+            ("columnKind", "utf16CodeUnits".into()),
+            ("results", Json::Array(results.collect())),
+        ]);
+        Json::Object(vec![
+            ("$schema", "https://json.schemastore.org/sarif-2.1.0.json".into()),
+            ("version", "2.1.0".into()),
+            ("runs", Json::Array(vec![run])),
+        ])
+        .to_string()
+    }
+}
+
+fn label_span(label: &miette::LabeledSpan) -> Span {
+    Span::new(label.offset() as u32, (label.offset() + label.len()) as u32)
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Advice => "advice",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+fn applicability_name(applicability: Applicability) -> &'static str {
+    match applicability {
+        Applicability::MachineApplicable => "machine_applicable",
+        Applicability::MaybeIncorrect => "maybe_incorrect",
+    }
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Advice => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+/// A JSON value, printed without whitespace.
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u32),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<Option<&str>> for Json {
+    fn from(value: Option<&str>) -> Self {
+        value.map_or(Json::Null, Json::from)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            Json::Number(value) => write!(f, "{value}"),
+            Json::String(value) => write_json_string(f, value),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_json_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for ch in value.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            ch if ch.is_control() => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => f.write_char(ch)?,
+        }
+    }
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::diagnostic::{Code, Fix};
+
+    #[test]
+    fn test_json() {
+        let source = "'ä' + ;";
+        let error = Diagnostic::error("unexpected token")
+            .with_code(Code::UnexpectedToken)
+            .with_help("say \"hi\"")
+            .with_label(Span::new(7, 8).label("here"))
+            .with_fix(
+                Fix::new("remove `;`", Applicability::MaybeIncorrect).replace(Span::new(7, 8), ""),
+            );
+        let mut report = Report::default();
+        report.add_file("a\\b.molang", source, vec![error]);
+        assert_eq!(
+            report.to_json(),
+            concat!(
+                r#"[{"path":"a\\b.molang","code":"nolana::parse::unexpected_token","#,
+                r#""severity":"error","message":"unexpected token","help":"say \"hi\"","#,
+                r#""labels":[{"label":"here","primary":false,"#,
+                r#""start":{"offset":7,"line":1,"column":8},"#,
+                r#""end":{"offset":8,"line":1,"column":9}}],"#,
+                r#""fixes":[{"message":"remove `;`","applicability":"maybe_incorrect","edits":[{"#,
+                r#""start":{"offset":7,"line":1,"column":8},"#,
+                r#""end":{"offset":8,"line":1,"column":9},"replacement":""}]}]}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_sarif() {
+        let source = "'ä' + ;";
+        let error = Diagnostic::error("unexpected token")
+            .with_label(Span::new(7, 8))
+            .add_label(Span::new(0, 4).label("string"));
+        let mut report = Report::default();
+        report.add_file("a.molang", source, vec![error]);
+        let sarif = report.to_sarif();
+        assert!(sarif.contains(r#""rules":[{"id":"nolana"}]"#));
+        assert!(sarif.contains(concat!(
+            r#""locations":[{"physicalLocation":{"artifactLocation":{"uri":"a.molang"},"#,
+            r#""region":{"startLine":1,"startColumn":7,"endLine":1,"endColumn":8}}}]"#
+        )));
+        assert!(sarif.contains(concat!(
+            r#""relatedLocations":[{"physicalLocation":{"artifactLocation":{"uri":"a.molang"},"#,
+            r#""region":{"startLine":1,"startColumn":1,"endLine":1,"endColumn":4}},"#,
+            r#""message":{"text":"string"}}]"#
+        )));
+    }
+}
@@ -0,0 +1,632 @@
+pub mod math;
+pub mod query;
+pub mod variables;
+
+use std::{collections::BTreeMap, collections::HashMap, fmt, sync::Arc};
+
+use crate::{
+    ast::*,
+    diagnostic::{Code, Diagnostic, Result},
+    span::Span,
+};
+use math::Random;
+use query::{NoQueries, QueryCall, QueryProvider};
+use variables::{Variables, lowercase, member_name};
+
+/// Maximum number of iterations a `loop` is allowed to run, as enforced by
+/// Bedrock.
+pub const MAX_LOOP_ITERATIONS: u32 = 1024;
+
+/// Members of a struct value, ordered by name.
+pub type Struct = BTreeMap<String, Value>;
+
+/// A runtime Molang value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Molang has a single number type. Booleans are represented as `1.0` and
+    /// `0.0`.
+    Number(f32),
+    String(Arc<str>),
+    /// `v.foo` in `v.foo.bar = 1;`
+    Struct(Struct),
+    /// Returned by queries such as `q.get_nearby_entities`.
+    Array(Vec<Value>),
+    /// A reference to an entity which can be accessed using `->`.
+    Entity(EntityRef),
+}
+
+impl Value {
+    /// Returns the number this value represents, or `None` if it is not a
+    /// number.
+    pub fn as_number(&self) -> Option<f32> {
+        match self {
+            Self::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the string this value represents, or `None` if it is not a
+    /// string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Numbers are truthy when they are non-zero. Every other value is truthy.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Self::Number(value) => *value != 0.0,
+            _ => true,
+        }
+    }
+
+    /// The name of this value's type as it appears in diagnostics.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+            Self::Struct(_) => "struct",
+            Self::Array(_) => "array",
+            Self::Entity(_) => "entity",
+        }
+    }
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Self::Number(0.0)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Number(if value { 1.0 } else { 0.0 })
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value.into())
+    }
+}
+
+impl From<EntityRef> for Value {
+    fn from(value: EntityRef) -> Self {
+        Self::Entity(value)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "{value}"),
+            Self::String(value) => write!(f, "'{value}'"),
+            Self::Struct(members) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in members.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+            Self::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Entity(entity) => write!(f, "entity#{}", entity.0),
+        }
+    }
+}
+
+/// An opaque handle to an entity owned by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityRef(pub u64);
+
+/// Tree-walking interpreter for Molang programs.
+///
+/// Queries are answered by a [`QueryProvider`] set with
+/// [`Interpreter::with_queries`].
+///
+/// # Example
+///
+/// ```
+/// # use nolana::{Parser, eval::{Interpreter, Value}};
+/// let program = Parser::new("t.a = 2; t.b = t.a * 3; return t.b + 1;").parse().program;
+/// let value = Interpreter::new().eval(&program).unwrap();
+/// assert_eq!(value, Value::Number(7.0));
+/// ```
+#[derive(Debug, Default)]
+pub struct Interpreter<Q = NoQueries> {
+    variables: Variables,
+    arrays: HashMap<String, Vec<Value>>,
+    this: Value,
+    random: Random,
+    queries: Q,
+    /// The entity on the left side of the `->` currently being evaluated.
+    target: Option<EntityRef>,
+}
+
+/// Unwinds the evaluation of a program up to the node that handles it.
+enum Interrupt {
+    Error(Diagnostic),
+    Return(Value),
+    Break(Span),
+    Continue(Span),
+}
+
+impl From<Diagnostic> for Interrupt {
+    fn from(error: Diagnostic) -> Self {
+        Self::Error(error)
+    }
+}
+
+type Eval<T> = std::result::Result<T, Interrupt>;
+
+impl Interpreter {
+    /// Creates a new [`Interpreter`] without a query provider.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<Q: QueryProvider> Interpreter<Q> {
+    /// Sets the provider used to answer `query.*` calls.
+    pub fn with_queries<T: QueryProvider>(self, queries: T) -> Interpreter<T> {
+        Interpreter {
+            variables: self.variables,
+            arrays: self.arrays,
+            this: self.this,
+            random: self.random,
+            queries,
+            target: self.target,
+        }
+    }
+
+    /// Returns the query provider, e.g. to update a mocked entity between
+    /// evaluations.
+    pub fn queries_mut(&mut self) -> &mut Q {
+        &mut self.queries
+    }
+
+    /// Sets the variables of the entity this interpreter evaluates for.
+    pub fn with_variables(mut self, variables: Variables) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    /// Returns the variables, which persist across evaluations except for
+    /// `temp.*`.
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    /// Returns the variables, e.g. to supply `context.*` values with
+    /// [`Variables::set_context`].
+    pub fn variables_mut(&mut self) -> &mut Variables {
+        &mut self.variables
+    }
+
+    /// Sets the value returned by `this`.
+    pub fn with_this(mut self, value: impl Into<Value>) -> Self {
+        self.this = value.into();
+        self
+    }
+
+    /// Seeds the generator used by `math.random` and friends.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.random = Random::new(seed);
+        self
+    }
+
+    /// Defines an array that can be accessed with `array.<name>[index]`.
+    pub fn with_array(mut self, name: impl Into<String>, values: Vec<Value>) -> Self {
+        self.arrays.insert(name.into().to_ascii_lowercase(), values);
+        self
+    }
+
+    /// Evaluates a program and returns its result. `temp.*` variables are
+    /// cleared beforehand.
+    ///
+    /// Simple programs evaluate to the value of their expression, while complex
+    /// programs evaluate to the argument of the `return` statement, or `0.0` if
+    /// there is none.
+    pub fn eval(&mut self, program: &Program) -> Result<Value> {
+        self.variables.clear_temp();
+        let result = match &program.body {
+            ProgramBody::Simple(expr) => self.eval_expression(expr),
+            ProgramBody::Complex(stmts) => self.eval_statements(stmts).map(|_| Value::default()),
+            ProgramBody::Empty => Ok(Value::default()),
+        };
+        match result {
+            Ok(value) | Err(Interrupt::Return(value)) => Ok(value),
+            Err(Interrupt::Error(error)) => Err(error),
+            Err(Interrupt::Break(span)) => Err(break_outside_loop(span)),
+            Err(Interrupt::Continue(span)) => Err(continue_outside_loop(span)),
+        }
+    }
+
+    fn eval_statements(&mut self, stmts: &[Statement]) -> Eval<()> {
+        for stmt in stmts {
+            self.eval_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn eval_statement(&mut self, stmt: &Statement) -> Eval<()> {
+        match stmt {
+            Statement::Expression(expr) => {
+                self.eval_expression(expr)?;
+            }
+            Statement::Assignment(stmt) => self.eval_assignment_statement(stmt)?,
+            Statement::Loop(stmt) => self.eval_loop_statement(stmt)?,
+            Statement::ForEach(stmt) => self.eval_for_each_statement(stmt)?,
+            Statement::Return(stmt) => {
+                return Err(Interrupt::Return(self.eval_expression(&stmt.argument)?));
+            }
+            Statement::Break(stmt) => return Err(Interrupt::Break(stmt.span)),
+            Statement::Continue(stmt) => return Err(Interrupt::Continue(stmt.span)),
+            Statement::Empty(_) => (),
+            Statement::Error(stmt) => return Err(syntax_error(stmt.span).into()),
+        }
+        Ok(())
+    }
+
+    fn eval_assignment_statement(&mut self, stmt: &AssignmentStatement) -> Eval<()> {
+        let right = match stmt.operator {
+            AssignmentOperator::Assign => self.eval_expression(&stmt.right)?,
+            AssignmentOperator::LogicalOr => {
+                if self.read_variable(&stmt.left)?.is_some_and(|value| value.is_truthy()) {
+                    return Ok(());
+                }
+                self.eval_expression(&stmt.right)?
+            }
+            AssignmentOperator::LogicalAnd => {
+                if !self.read_variable(&stmt.left)?.is_some_and(|value| value.is_truthy()) {
+                    return Ok(());
+                }
+                self.eval_expression(&stmt.right)?
+            }
+            operator => {
+                let left = self.read_variable(&stmt.left)?.unwrap_or_default();
+                let right = self.eval_expression(&stmt.right)?;
+                binary_operation(left, operator.into(), right, stmt.span)?
+            }
+        };
+        self.write_variable(&stmt.left, right)?;
+        Ok(())
+    }
+
+    fn eval_loop_statement(&mut self, stmt: &LoopStatement) -> Eval<()> {
+        let count = self.eval_number(&stmt.count)?;
+        for _ in 0..loop_iterations(count) {
+            match self.eval_statements(&stmt.block.statements) {
+                Ok(()) | Err(Interrupt::Continue(_)) => (),
+                Err(Interrupt::Break(_)) => break,
+                Err(interrupt) => return Err(interrupt),
+            }
+        }
+        Ok(())
+    }
+
+    fn eval_for_each_statement(&mut self, stmt: &ForEachStatement) -> Eval<()> {
+        let Value::Array(values) = self.eval_expression(&stmt.array)? else {
+            return Err(expected_array(stmt.array.span()).into());
+        };
+        for value in values {
+            self.write_variable(&stmt.variable, value)?;
+            match self.eval_statements(&stmt.block.statements) {
+                Ok(()) | Err(Interrupt::Continue(_)) => (),
+                Err(Interrupt::Break(_)) => break,
+                Err(interrupt) => return Err(interrupt),
+            }
+        }
+        Ok(())
+    }
+
+    fn eval_expression(&mut self, expr: &Expression) -> Eval<Value> {
+        Ok(match expr {
+            Expression::NumericLiteral(expr) => Value::Number(expr.value),
+            Expression::BooleanLiteral(expr) => expr.value.into(),
+            Expression::StringLiteral(expr) => expr.value.as_ref().into(),
+            Expression::Variable(expr) => self.read_variable(expr)?.unwrap_or_default(),
+            Expression::Parenthesized(expr) => match &expr.body {
+                ParenthesizedBody::Single(expr) => self.eval_expression(expr)?,
+                ParenthesizedBody::Multiple(stmts) => {
+                    self.eval_statements(stmts)?;
+                    Value::default()
+                }
+            },
+            Expression::Block(expr) => {
+                self.eval_statements(&expr.statements)?;
+                Value::default()
+            }
+            Expression::Binary(expr) => self.eval_binary_expression(expr)?,
+            Expression::Unary(expr) => self.eval_unary_expression(expr)?,
+            Expression::Update(expr) => self.eval_update_expression(expr)?,
+            Expression::Ternary(expr) => {
+                if self.eval_expression(&expr.test)?.is_truthy() {
+                    self.eval_expression(&expr.consequent)?
+                } else {
+                    self.eval_expression(&expr.alternate)?
+                }
+            }
+            Expression::Conditional(expr) => {
+                if self.eval_expression(&expr.test)?.is_truthy() {
+                    self.eval_expression(&expr.consequent)?
+                } else {
+                    Value::default()
+                }
+            }
+            Expression::Resource(expr) => {
+                format!("{}.{}", expr.section.as_str(), lowercase(&expr.name.name)).into()
+            }
+            Expression::ArrayAccess(expr) => self.eval_array_access_expression(expr)?,
+            Expression::ArrowAccess(expr) => {
+                let Value::Entity(entity) = self.eval_expression(&expr.left)? else {
+                    return Err(expected_entity(expr.left.span()).into());
+                };
+                let target = self.target.replace(entity);
+                let value = self.eval_expression(&expr.right);
+                self.target = target;
+                value?
+            }
+            Expression::Call(expr) => self.eval_call_expression(expr)?,
+            Expression::This(_) => self.this.clone(),
+            Expression::Error(expr) => return Err(syntax_error(expr.span).into()),
+        })
+    }
+
+    fn eval_number(&mut self, expr: &Expression) -> Eval<f32> {
+        match self.eval_expression(expr)? {
+            Value::Number(value) => Ok(value),
+            value => Err(expected_number(&value, expr.span()).into()),
+        }
+    }
+
+    fn eval_binary_expression(&mut self, expr: &BinaryExpression) -> Eval<Value> {
+        let left = match expr.operator {
+            BinaryOperator::And => {
+                let value = self.eval_expression(&expr.left)?.is_truthy()
+                    && self.eval_expression(&expr.right)?.is_truthy();
+                return Ok(value.into());
+            }
+            BinaryOperator::Or => {
+                let value = self.eval_expression(&expr.left)?.is_truthy()
+                    || self.eval_expression(&expr.right)?.is_truthy();
+                return Ok(value.into());
+            }
+            BinaryOperator::Coalesce => {
+                let left = match &expr.left {
+                    Expression::Variable(variable) => self.read_variable(variable)?,
+                    left => Some(self.eval_expression(left)?),
+                };
+                return match left {
+                    Some(value) => Ok(value),
+                    None => self.eval_expression(&expr.right),
+                };
+            }
+            _ => self.eval_expression(&expr.left)?,
+        };
+        let right = self.eval_expression(&expr.right)?;
+        Ok(binary_operation(left, expr.operator, right, expr.span)?)
+    }
+
+    fn eval_unary_expression(&mut self, expr: &UnaryExpression) -> Eval<Value> {
+        Ok(match expr.operator {
+            UnaryOperator::Not => (!self.eval_expression(&expr.argument)?.is_truthy()).into(),
+            UnaryOperator::Negate => Value::Number(-self.eval_number(&expr.argument)?),
+            UnaryOperator::BitwiseNot => {
+                Value::Number(!(self.eval_number(&expr.argument)? as i32) as f32)
+            }
+        })
+    }
+
+    /// Updates are applied before the variable is read, which mirrors how
+    /// [`MolangTransformer`][`crate::MolangTransformer`] lowers them.
+    fn eval_update_expression(&mut self, expr: &UpdateExpression) -> Eval<Value> {
+        let value = self.read_variable(&expr.variable)?.unwrap_or_default();
+        let value = binary_operation(value, expr.operator.into(), 1.0.into(), expr.span)?;
+        self.write_variable(&expr.variable, value.clone())?;
+        Ok(value)
+    }
+
+    fn eval_call_expression(&mut self, expr: &CallExpression) -> Eval<Value> {
+        match expr.kind {
+            CallKind::Math => {
+                let arguments = expr.arguments.as_deref().unwrap_or_default();
+                let function = math::resolve(&expr.callee.name, arguments.len())
+                    .map_err(|error| error.with_label(expr.span))?;
+                let mut args = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    args.push(self.eval_number(argument)?);
+                }
+                Ok(function.call(&args, &mut self.random).into())
+            }
+            CallKind::Query => {
+                let arguments = match &expr.arguments {
+                    Some(arguments) => Some(
+                        arguments
+                            .iter()
+                            .map(|argument| self.eval_expression(argument))
+                            .collect::<Eval<Vec<_>>>()?,
+                    ),
+                    None => None,
+                };
+                let name = lowercase(&expr.callee.name);
+                let call =
+                    QueryCall { name: &name, arguments: arguments.as_deref(), target: self.target };
+                let value = self.queries.query(call).map_err(|error| {
+                    if error.labels.is_none() { error.with_label(expr.span) } else { error }
+                })?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn eval_array_access_expression(&mut self, expr: &ArrayAccessExpression) -> Eval<Value> {
+        let index = self.eval_number(&expr.index)?;
+        let name = lowercase(&expr.name.name);
+        let Some(values) = self.arrays.get(name.as_ref()).filter(|values| !values.is_empty())
+        else {
+            return Err(unknown_array(&name, expr.span).into());
+        };
+        Ok(values[array_index(index, values.len())].clone())
+    }
+
+    /// `variable.*` on the right side of `->` belongs to the target entity, so
+    /// it is read through the query provider.
+    fn read_variable(&mut self, variable: &VariableExpression) -> Result<Option<Value>> {
+        match self.target {
+            Some(entity) if variable.lifetime == VariableLifetime::Variable => {
+                let name = member_name(&variable.member);
+                self.queries.entity_variable(entity, &name).map_err(|error| {
+                    if error.labels.is_none() { error.with_label(variable.span) } else { error }
+                })
+            }
+            _ => Ok(self.variables.get_member(variable.lifetime, &variable.member).cloned()),
+        }
+    }
+
+    fn write_variable(&mut self, variable: &VariableExpression, value: Value) -> Result<()> {
+        if self.target.is_some() && variable.lifetime == VariableLifetime::Variable {
+            return Err(entity_variable_assignment(&member_name(&variable.member), variable.span));
+        }
+        self.variables
+            .set_member(variable.lifetime, &variable.member, value)
+            .map_err(|error| error.with_label(variable.span))
+    }
+}
+
+/// Clamps the count of a `loop` to `[0, MAX_LOOP_ITERATIONS]`.
+pub(crate) fn loop_iterations(count: f32) -> u32 {
+    (count.max(0.0) as u32).min(MAX_LOOP_ITERATIONS)
+}
+
+/// Array indices are floored and wrapped around the size of the array.
+pub(crate) fn array_index(index: f32, len: usize) -> usize {
+    (index.max(0.0) as usize) % len
+}
+
+/// Applies a non short-circuiting binary operator to two values.
+pub(crate) fn binary_operation(
+    left: Value,
+    operator: BinaryOperator,
+    right: Value,
+    span: Span,
+) -> Result<Value> {
+    use BinaryOperator::*;
+    let (left, right) = match (left, right) {
+        (Value::Number(left), Value::Number(right)) => (left, right),
+        (left, right) => {
+            return match operator {
+                Equality => Ok((left == right).into()),
+                Inequality => Ok((left != right).into()),
+                _ if matches!(left, Value::String(_)) || matches!(right, Value::String(_)) => {
+                    Err(illegal_string_operation(span))
+                }
+                _ => Err(expected_number(
+                    if left.as_number().is_some() { &right } else { &left },
+                    span,
+                )),
+            };
+        }
+    };
+    Ok(match operator {
+        Equality => (left == right).into(),
+        Inequality => (left != right).into(),
+        LessThan => (left < right).into(),
+        LessEqualThan => (left <= right).into(),
+        GreaterThan => (left > right).into(),
+        GreaterEqualThan => (left >= right).into(),
+        Addition => (left + right).into(),
+        Subtraction => (left - right).into(),
+        Multiplication => (left * right).into(),
+        Division => (left / right).into(),
+        Exponential => left.powf(right).into(),
+        Remainder => (left % right).into(),
+        Or => (left != 0.0 || right != 0.0).into(),
+        And => (left != 0.0 && right != 0.0).into(),
+        Coalesce => left.into(),
+        ShiftLeft => (left * 2f32.powf(right)).into(),
+        ShiftRight => (left / 2f32.powf(right)).floor().into(),
+        BitwiseOr => ((left as i32 | right as i32) as f32).into(),
+        BitwiseAnd => ((left as i32 & right as i32) as f32).into(),
+        BitwiseXor => ((left as i32 ^ right as i32) as f32).into(),
+    })
+}
+
+pub(crate) fn break_outside_loop(span: Span) -> Diagnostic {
+    Diagnostic::error("`break` is only supported inside `loop` and `for_each` statements")
+        .with_code(Code::BreakOutsideLoop)
+        .with_label(span)
+}
+
+pub(crate) fn continue_outside_loop(span: Span) -> Diagnostic {
+    Diagnostic::error("`continue` is only supported inside `loop` and `for_each` statements")
+        .with_code(Code::ContinueOutsideLoop)
+        .with_label(span)
+}
+
+pub(crate) fn syntax_error(span: Span) -> Diagnostic {
+    Diagnostic::error("cannot evaluate code with syntax errors")
+        .with_code(Code::SyntaxError)
+        .with_label(span)
+}
+
+pub(crate) fn illegal_string_operation(span: Span) -> Diagnostic {
+    Diagnostic::error("strings only support `==` and `!=` operators")
+        .with_code(Code::IllegalStringBinary)
+        .with_label(span)
+}
+
+pub(crate) fn expected_number(found: &Value, span: Span) -> Diagnostic {
+    Diagnostic::error(format!("expected a number but found a {}", found.type_name()))
+        .with_code(Code::ExpectedNumber)
+        .with_label(span)
+}
+
+pub(crate) fn expected_array(span: Span) -> Diagnostic {
+    Diagnostic::error("`for_each` second argument must be an array")
+        .with_code(Code::ExpectedArray)
+        .with_label(span)
+}
+
+pub(crate) fn expected_entity(span: Span) -> Diagnostic {
+    Diagnostic::error("left side of `->` must be an entity")
+        .with_code(Code::ExpectedEntity)
+        .with_label(span)
+}
+
+pub(crate) fn entity_variable_assignment(name: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(format!("cannot assign to `variable.{name}` of another entity"))
+        .with_code(Code::EntityVariable)
+        .with_label(span)
+}
+
+pub(crate) fn unknown_array(name: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(format!("unknown array `array.{name}`"))
+        .with_code(Code::UnknownArray)
+        .with_label(span)
+}
@@ -0,0 +1,498 @@
+//! Implementation of the Molang `math.*` standard library.
+//!
+//! <https://bedrock.dev/docs/stable/Molang#Math%20Functions>
+
+use std::f32::consts::PI;
+
+use super::loop_iterations;
+use crate::diagnostic::{Code, Diagnostic, Result};
+
+/// A function that can be called with `math.<name>(...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathFunction {
+    /// `math.abs(value)`
+    Abs,
+    /// `math.acos(value)`, in degrees.
+    Acos,
+    /// `math.asin(value)`, in degrees.
+    Asin,
+    /// `math.atan(value)`, in degrees.
+    Atan,
+    /// `math.atan2(y, x)`, in degrees.
+    Atan2,
+    /// `math.ceil(value)`
+    Ceil,
+    /// `math.clamp(value, min, max)`
+    Clamp,
+    /// `math.copy_sign(a, b)`
+    CopySign,
+    /// `math.cos(value)`, where `value` is in degrees.
+    Cos,
+    /// `math.die_roll(num, low, high)`, where `num` is clamped like a `loop`
+    /// count.
+    DieRoll,
+    /// `math.die_roll_integer(num, low, high)`, where `num` is clamped like a
+    /// `loop` count.
+    DieRollInteger,
+    /// `math.exp(value)`
+    Exp,
+    /// `math.floor(value)`
+    Floor,
+    /// `math.hermite_blend(value)`
+    HermiteBlend,
+    /// `math.inverse_lerp(start, end, value)`
+    InverseLerp,
+    /// `math.lerp(start, end, 0_to_1)`
+    Lerp,
+    /// `math.lerprotate(start, end, 0_to_1)`
+    LerpRotate,
+    /// `math.ln(value)`
+    Ln,
+    /// `math.max(a, b)`
+    Max,
+    /// `math.min(a, b)`
+    Min,
+    /// `math.min_angle(value)`
+    MinAngle,
+    /// `math.mod(value, denominator)`
+    Mod,
+    /// `math.pi`
+    Pi,
+    /// `math.pow(base, exponent)`
+    Pow,
+    /// `math.random(low, high)`
+    Random,
+    /// `math.random_integer(low, high)`
+    RandomInteger,
+    /// `math.round(value)`
+    Round,
+    /// `math.sign(value)`
+    Sign,
+    /// `math.sin(value)`, where `value` is in degrees.
+    Sin,
+    /// `math.sqrt(value)`
+    Sqrt,
+    /// `math.trunc(value)`
+    Trunc,
+    /// `math.ease_<mode>_<curve>(start, end, 0_to_1)`
+    Ease(EasingMode, EasingCurve),
+}
+
+/// The `in`, `out` or `in_out` part of an easing function's name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EasingMode {
+    In,
+    Out,
+    InOut,
+}
+
+/// The curve part of an easing function's name, as described in
+/// <https://easings.net>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EasingCurve {
+    Quad,
+    Cubic,
+    Quart,
+    Quint,
+    Sine,
+    Expo,
+    Circ,
+    Back,
+    Elastic,
+    Bounce,
+}
+
+impl MathFunction {
+    /// Looks up a function by its lowercase name, without the `math.` prefix.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Self::Abs,
+            "acos" => Self::Acos,
+            "asin" => Self::Asin,
+            "atan" => Self::Atan,
+            "atan2" => Self::Atan2,
+            "ceil" => Self::Ceil,
+            "clamp" => Self::Clamp,
+            "copy_sign" => Self::CopySign,
+            "cos" => Self::Cos,
+            "die_roll" => Self::DieRoll,
+            "die_roll_integer" => Self::DieRollInteger,
+            "exp" => Self::Exp,
+            "floor" => Self::Floor,
+            "hermite_blend" => Self::HermiteBlend,
+            "inverse_lerp" => Self::InverseLerp,
+            "lerp" => Self::Lerp,
+            "lerprotate" => Self::LerpRotate,
+            "ln" => Self::Ln,
+            "max" => Self::Max,
+            "min" => Self::Min,
+            "min_angle" => Self::MinAngle,
+            "mod" => Self::Mod,
+            "pi" => Self::Pi,
+            "pow" => Self::Pow,
+            "random" => Self::Random,
+            "random_integer" => Self::RandomInteger,
+            "round" => Self::Round,
+            "sign" => Self::Sign,
+            "sin" => Self::Sin,
+            "sqrt" => Self::Sqrt,
+            "trunc" => Self::Trunc,
+            name => {
+                let name = name.strip_prefix("ease_")?;
+                let (mode, curve) = if let Some(curve) = name.strip_prefix("in_out_") {
+                    (EasingMode::InOut, curve)
+                } else if let Some(curve) = name.strip_prefix("in_") {
+                    (EasingMode::In, curve)
+                } else {
+                    (EasingMode::Out, name.strip_prefix("out_")?)
+                };
+                Self::Ease(mode, EasingCurve::from_name(curve)?)
+            }
+        })
+    }
+
+    /// The number of arguments this function must be called with.
+    pub fn arity(self) -> usize {
+        match self {
+            Self::Pi => 0,
+            Self::Abs
+            | Self::Acos
+            | Self::Asin
+            | Self::Atan
+            | Self::Ceil
+            | Self::Cos
+            | Self::Exp
+            | Self::Floor
+            | Self::HermiteBlend
+            | Self::Ln
+            | Self::MinAngle
+            | Self::Round
+            | Self::Sign
+            | Self::Sin
+            | Self::Sqrt
+            | Self::Trunc => 1,
+            Self::Atan2
+            | Self::CopySign
+            | Self::Max
+            | Self::Min
+            | Self::Mod
+            | Self::Pow
+            | Self::Random
+            | Self::RandomInteger => 2,
+            Self::Clamp
+            | Self::DieRoll
+            | Self::DieRollInteger
+            | Self::InverseLerp
+            | Self::Lerp
+            | Self::LerpRotate
+            | Self::Ease(..) => 3,
+        }
+    }
+
+    /// Whether calling this function may return a different result for the
+    /// same arguments.
+    pub fn is_random(self) -> bool {
+        matches!(self, Self::Random | Self::RandomInteger | Self::DieRoll | Self::DieRollInteger)
+    }
+
+    /// Calls this function.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of arguments does not match [`MathFunction::arity`].
+    pub fn call(self, args: &[f32], random: &mut Random) -> f32 {
+        assert_eq!(args.len(), self.arity(), "math function {self:?} called with wrong arity");
+        match self {
+            Self::Abs => args[0].abs(),
+            Self::Acos => args[0].acos().to_degrees(),
+            Self::Asin => args[0].asin().to_degrees(),
+            Self::Atan => args[0].atan().to_degrees(),
+            Self::Atan2 => args[0].atan2(args[1]).to_degrees(),
+            Self::Ceil => args[0].ceil(),
+            Self::Clamp => args[0].max(args[1]).min(args[2]),
+            Self::CopySign => args[0].copysign(args[1]),
+            Self::Cos => args[0].to_radians().cos(),
+            Self::DieRoll => {
+                let (low, high) = (args[1], args[2]);
+                (0..loop_iterations(args[0])).map(|_| random.range(low, high)).sum()
+            }
+            Self::DieRollInteger => {
+                let (low, high) = (args[1], args[2]);
+                (0..loop_iterations(args[0])).map(|_| random.range_integer(low, high)).sum()
+            }
+            Self::Exp => args[0].exp(),
+            Self::Floor => args[0].floor(),
+            Self::HermiteBlend => {
+                let t = args[0];
+                3.0 * t * t - 2.0 * t * t * t
+            }
+            Self::InverseLerp => {
+                let (start, end, value) = (args[0], args[1], args[2]);
+                if start == end { 0.0 } else { (value - start) / (end - start) }
+            }
+            Self::Lerp => lerp(args[0], args[1], args[2]),
+            Self::LerpRotate => {
+                let (start, end) = (min_angle(args[0]), min_angle(args[1]));
+                start + min_angle(end - start) * args[2]
+            }
+            Self::Ln => args[0].ln(),
+            Self::Max => args[0].max(args[1]),
+            Self::Min => args[0].min(args[1]),
+            Self::MinAngle => min_angle(args[0]),
+            Self::Mod => args[0] % args[1],
+            Self::Pi => PI,
+            Self::Pow => args[0].powf(args[1]),
+            Self::Random => random.range(args[0], args[1]),
+            Self::RandomInteger => random.range_integer(args[0], args[1]),
+            Self::Round => args[0].round(),
+            Self::Sign => args[0].signum(),
+            Self::Sin => args[0].to_radians().sin(),
+            Self::Sqrt => args[0].sqrt(),
+            Self::Trunc => args[0].trunc(),
+            Self::Ease(mode, curve) => lerp(args[0], args[1], ease(mode, curve, args[2])),
+        }
+    }
+}
+
+impl EasingCurve {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "quad" => Self::Quad,
+            "cubic" => Self::Cubic,
+            "quart" => Self::Quart,
+            "quint" => Self::Quint,
+            "sine" => Self::Sine,
+            "expo" => Self::Expo,
+            "circ" => Self::Circ,
+            "back" => Self::Back,
+            "elastic" => Self::Elastic,
+            "bounce" => Self::Bounce,
+            _ => return None,
+        })
+    }
+
+    /// The `in` variant of this curve, from which `out` and `in_out` are
+    /// derived.
+    fn ease_in(self, t: f32) -> f32 {
+        const BACK: f32 = 1.70158;
+        match self {
+            Self::Quad => t.powi(2),
+            Self::Cubic => t.powi(3),
+            Self::Quart => t.powi(4),
+            Self::Quint => t.powi(5),
+            Self::Sine => 1.0 - (t * PI / 2.0).cos(),
+            Self::Expo => {
+                if t == 0.0 {
+                    0.0
+                } else {
+                    2f32.powf(10.0 * t - 10.0)
+                }
+            }
+            Self::Circ => 1.0 - (1.0 - t * t).sqrt(),
+            Self::Back => (BACK + 1.0) * t * t * t - BACK * t * t,
+            Self::Elastic => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * (2.0 * PI / 3.0)).sin()
+                }
+            }
+            Self::Bounce => 1.0 - bounce_out(1.0 - t),
+        }
+    }
+}
+
+/// Validates a call to `math.<name>` with `argument_count` arguments and
+/// returns the function to call.
+///
+/// The returned [`Diagnostic`] has no label, it is up to the caller to point
+/// it at the call expression.
+pub fn resolve(name: &str, argument_count: usize) -> Result<MathFunction> {
+    let Some(function) = MathFunction::from_name(&name.to_ascii_lowercase()) else {
+        return Err(unknown_math_function(name));
+    };
+    if function.arity() != argument_count {
+        return Err(wrong_argument_count(name, function.arity(), argument_count));
+    }
+    Ok(function)
+}
+
+/// A small, deterministic pseudo-random number generator (xorshift64*) used
+/// by the random functions.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Creates a new generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        const MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+        // The state must never be zero, or xorshift only returns zero.
+        let state = seed ^ MIX;
+        Self { state: if state == 0 { MIX } else { state } }
+    }
+
+    /// Returns a number in the range `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+        bits as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a number in the range `[low, high)`.
+    pub fn range(&mut self, low: f32, high: f32) -> f32 {
+        lerp(low, high, self.next_f32())
+    }
+
+    /// Returns an integer in the range `[low, high]`.
+    pub fn range_integer(&mut self, low: f32, high: f32) -> f32 {
+        let (low, high) = (low.round(), high.round());
+        (low + (self.next_f32() * (high - low + 1.0)).floor()).min(high)
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+fn lerp(start: f32, end: f32, t: f32) -> f32 {
+    start + (end - start) * t
+}
+
+/// Wraps an angle in degrees into the range `[-180, 180)`.
+fn min_angle(value: f32) -> f32 {
+    (value + 180.0).rem_euclid(360.0) - 180.0
+}
+
+fn ease(mode: EasingMode, curve: EasingCurve, t: f32) -> f32 {
+    match mode {
+        EasingMode::In => curve.ease_in(t),
+        EasingMode::Out => 1.0 - curve.ease_in(1.0 - t),
+        EasingMode::InOut => {
+            if t < 0.5 {
+                curve.ease_in(t * 2.0) / 2.0
+            } else {
+                1.0 - curve.ease_in(2.0 - t * 2.0) / 2.0
+            }
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+fn unknown_math_function(name: &str) -> Diagnostic {
+    Diagnostic::error(format!("unknown math function `math.{name}`"))
+        .with_code(Code::UnknownMathFunction)
+}
+
+fn wrong_argument_count(name: &str, expected: usize, found: usize) -> Diagnostic {
+    let plural = if expected == 1 { "" } else { "s" };
+    let verb = if found == 1 { "was" } else { "were" };
+    Diagnostic::error(format!(
+        "`math.{name}` expects {expected} argument{plural} but {found} {verb} provided"
+    ))
+    .with_code(Code::MathArgumentCount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::MAX_LOOP_ITERATIONS;
+
+    fn call(name: &str, args: &[f32]) -> f32 {
+        resolve(name, args.len()).unwrap().call(args, &mut Random::default())
+    }
+
+    fn assert_close(left: f32, right: f32) {
+        assert!((left - right).abs() < 1e-4, "{left} != {right}");
+    }
+
+    #[test]
+    fn test_trigonometry_uses_degrees() {
+        assert_close(call("cos", &[180.0]), -1.0);
+        assert_close(call("sin", &[90.0]), 1.0);
+        assert_close(call("atan2", &[1.0, 1.0]), 45.0);
+        assert_close(call("acos", &[0.0]), 90.0);
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_close(call("lerp", &[10.0, 20.0, 0.25]), 12.5);
+        assert_close(call("inverse_lerp", &[10.0, 20.0, 12.5]), 0.25);
+        assert_close(call("lerprotate", &[170.0, -170.0, 0.5]), 180.0);
+        assert_close(call("lerprotate", &[10.0, 350.0, 0.5]), 0.0);
+        assert_close(call("hermite_blend", &[0.5]), 0.5);
+        assert_close(call("min_angle", &[270.0]), -90.0);
+    }
+
+    #[test]
+    fn test_mod_keeps_sign_of_dividend() {
+        assert_close(call("mod", &[-7.0, 3.0]), -1.0);
+        assert_close(call("mod", &[7.0, 3.0]), 1.0);
+    }
+
+    #[test]
+    fn test_easing_endpoints() {
+        for mode in ["in", "out", "in_out"] {
+            for curve in [
+                "quad", "cubic", "quart", "quint", "sine", "expo", "circ", "back", "elastic",
+                "bounce",
+            ] {
+                let name = format!("ease_{mode}_{curve}");
+                assert_close(call(&name, &[2.0, 4.0, 0.0]), 2.0);
+                assert_close(call(&name, &[2.0, 4.0, 1.0]), 4.0);
+            }
+        }
+        assert_close(call("ease_in_out_quad", &[0.0, 1.0, 0.5]), 0.5);
+    }
+
+    #[test]
+    fn test_random_ranges() {
+        let mut random = Random::new(42);
+        for _ in 0..1000 {
+            let value = random.range_integer(1.0, 6.0);
+            assert!((1.0..=6.0).contains(&value) && value.fract() == 0.0);
+            let value = random.range(-1.0, 1.0);
+            assert!((-1.0..1.0).contains(&value));
+        }
+        // A seed that cancels out the mixing constant still gives numbers.
+        let mut random = Random::new(0x9E37_79B9_7F4A_7C15);
+        assert!((0..10).any(|_| random.next_f32() != 0.0));
+    }
+
+    #[test]
+    fn test_die_roll_count_is_clamped() {
+        let sum = call("die_roll_integer", &[4_000_000_000.0, 1.0, 1.0]);
+        assert_eq!(sum, MAX_LOOP_ITERATIONS as f32);
+        assert_eq!(call("die_roll", &[-5.0, 1.0, 2.0]), 0.0);
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        assert_eq!(resolve("PoW", 2), Ok(MathFunction::Pow));
+        assert_eq!(resolve("pi", 0), Ok(MathFunction::Pi));
+        assert!(resolve("foo", 1).is_err());
+        assert!(resolve("cos", 2).is_err());
+        assert!(resolve("ease_sideways_quad", 3).is_err());
+    }
+}
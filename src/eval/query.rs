@@ -0,0 +1,93 @@
+//! Host integration for `query.*` calls.
+//!
+//! <https://bedrock.dev/docs/stable/Molang#Query%20Functions>
+
+use super::{EntityRef, Value};
+use crate::diagnostic::{Code, Diagnostic, Result};
+
+/// Answers `query.*` calls on behalf of the host.
+///
+/// Implemented for closures taking a [`QueryCall`], which is convenient for
+/// mocking entities in tests.
+///
+/// # Example
+///
+/// ```
+/// # use nolana::{Parser, eval::{Interpreter, Value, query::QueryCall}};
+/// let program = Parser::new("q.anim_time * 2").parse().program;
+/// let mut interpreter = Interpreter::new().with_queries(|call: QueryCall| match call.name {
+///     "anim_time" => Ok(Value::Number(1.5)),
+///     _ => Err(call.unknown()),
+/// });
+/// assert_eq!(interpreter.eval(&program).unwrap(), Value::Number(3.0));
+/// ```
+pub trait QueryProvider {
+    /// Returns the result of a query, or a [`Diagnostic`] if it cannot be
+    /// answered. Diagnostics without labels will point at the call expression.
+    fn query(&mut self, call: QueryCall<'_>) -> Result<Value>;
+
+    /// Returns `variable.<name>` of another entity, as read by
+    /// `t.entity->v.name`. The name is lowercase, with struct members
+    /// separated by `.`. Returns `None` if the variable is unset.
+    ///
+    /// Only the host knows the variables of other entities, so this fails
+    /// unless implemented.
+    fn entity_variable(&mut self, entity: EntityRef, name: &str) -> Result<Option<Value>> {
+        let _ = entity;
+        Err(unsupported_entity_variable(name))
+    }
+}
+
+/// A single `query.*` call.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryCall<'a> {
+    /// The lowercase name of the query, without the `query.` prefix.
+    pub name: &'a str,
+    /// The evaluated arguments. This is `None` for `q.foo` and `Some` for
+    /// `q.foo()` or `q.foo(1, 2)`.
+    pub arguments: Option<&'a [Value]>,
+    /// The entity on the left side of `->`, or `None` if the query targets
+    /// the current entity.
+    pub target: Option<EntityRef>,
+}
+
+impl QueryCall<'_> {
+    /// Returns the argument at `index`, if provided.
+    pub fn argument(&self, index: usize) -> Option<&Value> {
+        self.arguments.and_then(|arguments| arguments.get(index))
+    }
+
+    /// Creates a [`Diagnostic`] reporting this query as unknown.
+    pub fn unknown(&self) -> Diagnostic {
+        unknown_query(self.name)
+    }
+}
+
+/// Provider used when the host does not supply one. Every query is unknown.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoQueries;
+
+impl QueryProvider for NoQueries {
+    fn query(&mut self, call: QueryCall<'_>) -> Result<Value> {
+        Err(call.unknown())
+    }
+}
+
+impl<F> QueryProvider for F
+where
+    F: FnMut(QueryCall<'_>) -> Result<Value>,
+{
+    fn query(&mut self, call: QueryCall<'_>) -> Result<Value> {
+        self(call)
+    }
+}
+
+fn unknown_query(name: &str) -> Diagnostic {
+    Diagnostic::error(format!("unknown query `query.{name}`")).with_code(Code::UnknownQuery)
+}
+
+fn unsupported_entity_variable(name: &str) -> Diagnostic {
+    Diagnostic::error(format!("cannot read `variable.{name}` of another entity"))
+        .with_code(Code::EntityVariable)
+        .with_help("implement `QueryProvider::entity_variable` to provide it")
+}
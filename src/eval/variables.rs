@@ -0,0 +1,207 @@
+//! Runtime storage for `temp.*`, `variable.*` and `context.*`.
+//!
+//! <https://bedrock.dev/docs/stable/Molang#Variables>
+
+use std::borrow::Cow;
+
+use super::{Struct, Value};
+use crate::{
+    ast::{VariableLifetime, VariableMember},
+    diagnostic::{Code, Diagnostic, Result},
+};
+
+/// Stores the variables of a single entity, following Molang's scoping rules:
+///
+/// - `temp.*` only lives for a single expression, see [`Variables::clear_temp`].
+/// - `variable.*` persists for as long as this store does, which should be the
+///   lifetime of the entity.
+/// - `context.*` is read-only and supplied by the host with
+///   [`Variables::set_context`].
+///
+/// Struct members such as `v.foo.bar` are stored as nested [`Value::Struct`]s.
+/// Names are case-insensitive.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Variables {
+    temp: Struct,
+    variable: Struct,
+    context: Struct,
+}
+
+impl Variables {
+    /// Returns the value at `path`, e.g. `["foo", "bar"]` for `v.foo.bar`.
+    ///
+    /// Reading an unset variable returns `None`. Molang treats these as `0.0`,
+    /// except on the left side of `??`.
+    pub fn get(&self, lifetime: VariableLifetime, path: &[impl AsRef<str>]) -> Option<&Value> {
+        get(self.scope(lifetime), path.iter().map(|name| lowercase(name.as_ref())))
+    }
+
+    /// Sets the value at `path`, creating intermediate structs as needed.
+    ///
+    /// Fails for `context.*`, use [`Variables::set_context`] instead.
+    pub fn set(
+        &mut self,
+        lifetime: VariableLifetime,
+        path: &[impl AsRef<str>],
+        value: Value,
+    ) -> Result<()> {
+        if lifetime == VariableLifetime::Context {
+            return Err(context_readonly());
+        }
+        set(self.scope_mut(lifetime), path.iter().map(|name| lowercase(name.as_ref())), value);
+        Ok(())
+    }
+
+    /// Sets a `context.*` variable on behalf of the host.
+    pub fn set_context(&mut self, path: &[impl AsRef<str>], value: Value) {
+        set(&mut self.context, path.iter().map(|name| lowercase(name.as_ref())), value);
+    }
+
+    /// Removes every `temp.*` variable. This is done before each evaluation.
+    pub fn clear_temp(&mut self) {
+        self.temp.clear();
+    }
+
+    /// Removes every `context.*` variable.
+    pub fn clear_context(&mut self) {
+        self.context.clear();
+    }
+
+    /// Returns every variable of a lifetime.
+    pub fn scope(&self, lifetime: VariableLifetime) -> &Struct {
+        match lifetime {
+            VariableLifetime::Temporary => &self.temp,
+            VariableLifetime::Variable => &self.variable,
+            VariableLifetime::Context => &self.context,
+        }
+    }
+
+    fn scope_mut(&mut self, lifetime: VariableLifetime) -> &mut Struct {
+        match lifetime {
+            VariableLifetime::Temporary => &mut self.temp,
+            VariableLifetime::Variable => &mut self.variable,
+            VariableLifetime::Context => &mut self.context,
+        }
+    }
+
+    pub(crate) fn get_member(
+        &self,
+        lifetime: VariableLifetime,
+        member: &VariableMember,
+    ) -> Option<&Value> {
+        get(self.scope(lifetime), member_path(member).into_iter())
+    }
+
+    pub(crate) fn set_member(
+        &mut self,
+        lifetime: VariableLifetime,
+        member: &VariableMember,
+        value: Value,
+    ) -> Result<()> {
+        if lifetime == VariableLifetime::Context {
+            return Err(context_readonly());
+        }
+        set(self.scope_mut(lifetime), member_path(member).into_iter(), value);
+        Ok(())
+    }
+}
+
+fn get<'a, 'b>(
+    scope: &'a Struct,
+    mut path: impl Iterator<Item = Cow<'b, str>>,
+) -> Option<&'a Value> {
+    let mut value = scope.get(path.next()?.as_ref())?;
+    for name in path {
+        let Value::Struct(members) = value else { return None };
+        value = members.get(name.as_ref())?;
+    }
+    Some(value)
+}
+
+/// Non-struct values found along the path are replaced with structs.
+fn set<'a>(scope: &mut Struct, mut path: impl Iterator<Item = Cow<'a, str>>, value: Value) {
+    let Some(mut name) = path.next() else { return };
+    let mut members = scope;
+    for next in path {
+        let entry =
+            members.entry(name.into_owned()).or_insert_with(|| Value::Struct(Struct::new()));
+        if !matches!(entry, Value::Struct(_)) {
+            *entry = Value::Struct(Struct::new());
+        }
+        let Value::Struct(inner) = entry else { unreachable!() };
+        members = inner;
+        name = next;
+    }
+    members.insert(name.into_owned(), value);
+}
+
+/// `v.foo.bar` becomes `["foo", "bar"]`.
+fn member_path<'a>(member: &'a VariableMember) -> Vec<Cow<'a, str>> {
+    match member {
+        VariableMember::Object { object, property } => {
+            let mut path = member_path(object);
+            path.push(lowercase(&property.name));
+            path
+        }
+        VariableMember::Property { property } => vec![lowercase(&property.name)],
+    }
+}
+
+/// `v.foo.bar` becomes `"foo.bar"`, as passed to
+/// [`QueryProvider::entity_variable`](super::query::QueryProvider::entity_variable).
+pub(crate) fn member_name(member: &VariableMember) -> String {
+    member_path(member).join(".")
+}
+
+/// Molang identifiers are case-insensitive. Only allocates if `name` contains
+/// uppercase characters.
+pub(crate) fn lowercase(name: &str) -> Cow<'_, str> {
+    if name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+fn context_readonly() -> Diagnostic {
+    Diagnostic::error("`context.*` variables are read-only")
+        .with_code(Code::ContextReadonly)
+        .with_help("try using `variable.*` or `temp.*` instead")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, eval::Interpreter};
+
+    #[test]
+    fn test_nested_structs() {
+        let mut variables = Variables::default();
+        variables.set(VariableLifetime::Variable, &["Foo", "bar"], 1.0.into()).unwrap();
+        variables.set(VariableLifetime::Variable, &["foo", "baz"], 2.0.into()).unwrap();
+        assert_eq!(variables.get(VariableLifetime::Variable, &["foo", "BAR"]), Some(&1.0.into()));
+        let Some(Value::Struct(foo)) = variables.get(VariableLifetime::Variable, &["foo"]) else {
+            panic!("`v.foo` must be a struct");
+        };
+        assert_eq!(foo.len(), 2);
+        assert_eq!(variables.get(VariableLifetime::Variable, &["foo", "bar", "baz"]), None);
+    }
+
+    #[test]
+    fn test_context_is_readonly() {
+        let mut variables = Variables::default();
+        assert!(variables.set(VariableLifetime::Context, &["foo"], 1.0.into()).is_err());
+        variables.set_context(&["foo"], 1.0.into());
+        assert_eq!(variables.get(VariableLifetime::Context, &["foo"]), Some(&1.0.into()));
+    }
+
+    #[test]
+    fn test_lifetimes_across_evaluations() {
+        let mut interpreter = Interpreter::new();
+        let first = Parser::new("t.a = 1; v.b = 2; return t.a + v.b;").parse().program;
+        let second = Parser::new("return (t.a ?? 10) + v.b;").parse().program;
+        assert_eq!(interpreter.eval(&first).unwrap(), 3.0.into());
+        assert_eq!(interpreter.eval(&second).unwrap(), 12.0.into());
+        assert_eq!(interpreter.variables().get(VariableLifetime::Temporary, &["a"]), None);
+    }
+}
@@ -0,0 +1,530 @@
+//! Rewriting of owned ASTs.
+//!
+//! [`Fold`] takes each node by value and returns the node that replaces it,
+//! so rewrites can destructure nodes and move their children without
+//! [`ReplaceWith`](crate::replace_with::ReplaceWith). The default
+//! implementations rebuild nodes from their folded children using the
+//! `walk_*` functions, which overrides can call to fold the children before
+//! or after rewriting a node.
+//!
+//! # Example
+//!
+//! ```
+//! # use nolana::{Codegen, Parser, ast::*, fold::{Fold, fold, walk_expression}};
+//! /// Rewrites `a ** b` into `math.pow(a, b)`.
+//! struct ExponentFolder;
+//!
+//! impl<'src> Fold<'src> for ExponentFolder {
+//!     fn fold_expression(&mut self, it: Expression<'src>) -> Expression<'src> {
+//!         match walk_expression(self, it) {
+//!             Expression::Binary(binary) if binary.operator == BinaryOperator::Exponential => {
+//!                 let BinaryExpression { span, left, right, .. } = *binary;
+//!                 let callee = Identifier { span, name: "pow".into() };
+//!                 let kind = CallKind::Math;
+//!                 CallExpression { span, kind, callee, arguments: Some(vec![left, right]) }.into()
+//!             }
+//!             expr => expr,
+//!         }
+//!     }
+//! }
+//!
+//! let program = Parser::new("v.a ** (v.b ** 2)").parse().program;
+//! let program = fold(&mut ExponentFolder, program);
+//! assert_eq!(Codegen::default().build(&program), "math.pow(v.a,(math.pow(v.b,2)))");
+//! ```
+
+use crate::ast::*;
+
+/// Folds the program using an implementer of [`Fold`].
+pub fn fold<'src>(folder: &mut impl Fold<'src>, program: Program<'src>) -> Program<'src> {
+    folder.fold_program(program)
+}
+
+pub trait Fold<'src>: Sized {
+    #[inline]
+    fn fold_program(&mut self, it: Program<'src>) -> Program<'src> {
+        walk_program(self, it)
+    }
+
+    #[inline]
+    fn fold_statements(&mut self, it: Vec<Statement<'src>>) -> Vec<Statement<'src>> {
+        walk_statements(self, it)
+    }
+
+    #[inline]
+    fn fold_statement(&mut self, it: Statement<'src>) -> Statement<'src> {
+        walk_statement(self, it)
+    }
+
+    #[inline]
+    fn fold_assignment_statement(
+        &mut self,
+        it: AssignmentStatement<'src>,
+    ) -> AssignmentStatement<'src> {
+        walk_assignment_statement(self, it)
+    }
+
+    #[inline]
+    fn fold_loop_statement(&mut self, it: LoopStatement<'src>) -> LoopStatement<'src> {
+        walk_loop_statement(self, it)
+    }
+
+    #[inline]
+    fn fold_for_each_statement(&mut self, it: ForEachStatement<'src>) -> ForEachStatement<'src> {
+        walk_for_each_statement(self, it)
+    }
+
+    #[inline]
+    fn fold_return_statement(&mut self, it: ReturnStatement<'src>) -> ReturnStatement<'src> {
+        walk_return_statement(self, it)
+    }
+
+    #[inline]
+    fn fold_break_statement(&mut self, it: BreakStatement) -> BreakStatement {
+        it
+    }
+
+    #[inline]
+    fn fold_continue_statement(&mut self, it: ContinueStatement) -> ContinueStatement {
+        it
+    }
+
+    #[inline]
+    fn fold_empty_statement(&mut self, it: EmptyStatement) -> EmptyStatement {
+        it
+    }
+
+    #[inline]
+    fn fold_error_statement(&mut self, it: ErrorStatement) -> ErrorStatement {
+        it
+    }
+
+    #[inline]
+    fn fold_expression(&mut self, it: Expression<'src>) -> Expression<'src> {
+        walk_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_identifier_reference(&mut self, it: Identifier<'src>) -> Identifier<'src> {
+        it
+    }
+
+    #[inline]
+    fn fold_numeric_literal(&mut self, it: NumericLiteral<'src>) -> NumericLiteral<'src> {
+        it
+    }
+
+    #[inline]
+    fn fold_boolean_literal(&mut self, it: BooleanLiteral) -> BooleanLiteral {
+        it
+    }
+
+    #[inline]
+    fn fold_string_literal(&mut self, it: StringLiteral<'src>) -> StringLiteral<'src> {
+        it
+    }
+
+    #[inline]
+    fn fold_variable_expression(
+        &mut self,
+        it: VariableExpression<'src>,
+    ) -> VariableExpression<'src> {
+        walk_variable_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_variable_member(&mut self, it: VariableMember<'src>) -> VariableMember<'src> {
+        walk_variable_member(self, it)
+    }
+
+    #[inline]
+    fn fold_parenthesized_expression(
+        &mut self,
+        it: ParenthesizedExpression<'src>,
+    ) -> ParenthesizedExpression<'src> {
+        walk_parenthesized_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_block_expression(&mut self, it: BlockExpression<'src>) -> BlockExpression<'src> {
+        walk_block_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_binary_expression(&mut self, it: BinaryExpression<'src>) -> BinaryExpression<'src> {
+        walk_binary_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_unary_expression(&mut self, it: UnaryExpression<'src>) -> UnaryExpression<'src> {
+        walk_unary_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_update_expression(&mut self, it: UpdateExpression<'src>) -> UpdateExpression<'src> {
+        walk_update_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_ternary_expression(&mut self, it: TernaryExpression<'src>) -> TernaryExpression<'src> {
+        walk_ternary_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_conditional_expression(
+        &mut self,
+        it: ConditionalExpression<'src>,
+    ) -> ConditionalExpression<'src> {
+        walk_conditional_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_resource_expression(
+        &mut self,
+        it: ResourceExpression<'src>,
+    ) -> ResourceExpression<'src> {
+        walk_resource_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_array_access_expression(
+        &mut self,
+        it: ArrayAccessExpression<'src>,
+    ) -> ArrayAccessExpression<'src> {
+        walk_array_access_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_arrow_access_expression(
+        &mut self,
+        it: ArrowAccessExpression<'src>,
+    ) -> ArrowAccessExpression<'src> {
+        walk_arrow_access_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_call_expression(&mut self, it: CallExpression<'src>) -> CallExpression<'src> {
+        walk_call_expression(self, it)
+    }
+
+    #[inline]
+    fn fold_this_expression(&mut self, it: ThisExpression) -> ThisExpression {
+        it
+    }
+
+    #[inline]
+    fn fold_error_expression(&mut self, it: ErrorExpression) -> ErrorExpression {
+        it
+    }
+}
+
+/// Folds the node inside a box, reusing its allocation.
+#[inline]
+fn fold_boxed<T>(mut it: Box<T>, fold: impl FnOnce(T) -> T) -> Box<T> {
+    *it = fold(*it);
+    it
+}
+
+pub fn walk_program<'src>(folder: &mut impl Fold<'src>, it: Program<'src>) -> Program<'src> {
+    let body = match it.body {
+        ProgramBody::Simple(expr) => ProgramBody::Simple(folder.fold_expression(expr)),
+        ProgramBody::Complex(stmts) => ProgramBody::Complex(folder.fold_statements(stmts)),
+        ProgramBody::Empty => ProgramBody::Empty,
+    };
+    Program { body, ..it }
+}
+
+pub fn walk_statements<'src>(
+    folder: &mut impl Fold<'src>,
+    it: Vec<Statement<'src>>,
+) -> Vec<Statement<'src>> {
+    it.into_iter().map(|stmt| folder.fold_statement(stmt)).collect()
+}
+
+pub fn walk_statement<'src>(folder: &mut impl Fold<'src>, it: Statement<'src>) -> Statement<'src> {
+    match it {
+        Statement::Expression(it) => {
+            Statement::Expression(fold_boxed(it, |it| folder.fold_expression(it)))
+        }
+        Statement::Assignment(it) => {
+            Statement::Assignment(fold_boxed(it, |it| folder.fold_assignment_statement(it)))
+        }
+        Statement::Loop(it) => Statement::Loop(fold_boxed(it, |it| folder.fold_loop_statement(it))),
+        Statement::ForEach(it) => {
+            Statement::ForEach(fold_boxed(it, |it| folder.fold_for_each_statement(it)))
+        }
+        Statement::Return(it) => {
+            Statement::Return(fold_boxed(it, |it| folder.fold_return_statement(it)))
+        }
+        Statement::Break(it) => {
+            Statement::Break(fold_boxed(it, |it| folder.fold_break_statement(it)))
+        }
+        Statement::Continue(it) => {
+            Statement::Continue(fold_boxed(it, |it| folder.fold_continue_statement(it)))
+        }
+        Statement::Empty(it) => {
+            Statement::Empty(fold_boxed(it, |it| folder.fold_empty_statement(it)))
+        }
+        Statement::Error(it) => {
+            Statement::Error(fold_boxed(it, |it| folder.fold_error_statement(it)))
+        }
+    }
+}
+
+pub fn walk_assignment_statement<'src>(
+    folder: &mut impl Fold<'src>,
+    it: AssignmentStatement<'src>,
+) -> AssignmentStatement<'src> {
+    let left = folder.fold_variable_expression(it.left);
+    let right = folder.fold_expression(it.right);
+    AssignmentStatement { left, right, ..it }
+}
+
+pub fn walk_loop_statement<'src>(
+    folder: &mut impl Fold<'src>,
+    it: LoopStatement<'src>,
+) -> LoopStatement<'src> {
+    let count = folder.fold_expression(it.count);
+    let block = folder.fold_block_expression(it.block);
+    LoopStatement { count, block, ..it }
+}
+
+pub fn walk_for_each_statement<'src>(
+    folder: &mut impl Fold<'src>,
+    it: ForEachStatement<'src>,
+) -> ForEachStatement<'src> {
+    let variable = folder.fold_variable_expression(it.variable);
+    let array = folder.fold_expression(it.array);
+    let block = folder.fold_block_expression(it.block);
+    ForEachStatement { variable, array, block, ..it }
+}
+
+pub fn walk_return_statement<'src>(
+    folder: &mut impl Fold<'src>,
+    it: ReturnStatement<'src>,
+) -> ReturnStatement<'src> {
+    let argument = folder.fold_expression(it.argument);
+    ReturnStatement { argument, ..it }
+}
+
+pub fn walk_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: Expression<'src>,
+) -> Expression<'src> {
+    match it {
+        Expression::NumericLiteral(it) => {
+            Expression::NumericLiteral(fold_boxed(it, |it| folder.fold_numeric_literal(it)))
+        }
+        Expression::BooleanLiteral(it) => {
+            Expression::BooleanLiteral(fold_boxed(it, |it| folder.fold_boolean_literal(it)))
+        }
+        Expression::StringLiteral(it) => {
+            Expression::StringLiteral(fold_boxed(it, |it| folder.fold_string_literal(it)))
+        }
+        Expression::Variable(it) => {
+            Expression::Variable(fold_boxed(it, |it| folder.fold_variable_expression(it)))
+        }
+        Expression::Parenthesized(it) => {
+            Expression::Parenthesized(fold_boxed(it, |it| folder.fold_parenthesized_expression(it)))
+        }
+        Expression::Block(it) => {
+            Expression::Block(fold_boxed(it, |it| folder.fold_block_expression(it)))
+        }
+        Expression::Binary(it) => {
+            Expression::Binary(fold_boxed(it, |it| folder.fold_binary_expression(it)))
+        }
+        Expression::Unary(it) => {
+            Expression::Unary(fold_boxed(it, |it| folder.fold_unary_expression(it)))
+        }
+        Expression::Update(it) => {
+            Expression::Update(fold_boxed(it, |it| folder.fold_update_expression(it)))
+        }
+        Expression::Ternary(it) => {
+            Expression::Ternary(fold_boxed(it, |it| folder.fold_ternary_expression(it)))
+        }
+        Expression::Conditional(it) => {
+            Expression::Conditional(fold_boxed(it, |it| folder.fold_conditional_expression(it)))
+        }
+        Expression::Resource(it) => {
+            Expression::Resource(fold_boxed(it, |it| folder.fold_resource_expression(it)))
+        }
+        Expression::ArrayAccess(it) => {
+            Expression::ArrayAccess(fold_boxed(it, |it| folder.fold_array_access_expression(it)))
+        }
+        Expression::ArrowAccess(it) => {
+            Expression::ArrowAccess(fold_boxed(it, |it| folder.fold_arrow_access_expression(it)))
+        }
+        Expression::Call(it) => {
+            Expression::Call(fold_boxed(it, |it| folder.fold_call_expression(it)))
+        }
+        Expression::This(it) => {
+            Expression::This(fold_boxed(it, |it| folder.fold_this_expression(it)))
+        }
+        Expression::Error(it) => {
+            Expression::Error(fold_boxed(it, |it| folder.fold_error_expression(it)))
+        }
+    }
+}
+
+pub fn walk_variable_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: VariableExpression<'src>,
+) -> VariableExpression<'src> {
+    let member = folder.fold_variable_member(it.member);
+    VariableExpression { member, ..it }
+}
+
+pub fn walk_variable_member<'src>(
+    folder: &mut impl Fold<'src>,
+    it: VariableMember<'src>,
+) -> VariableMember<'src> {
+    match it {
+        VariableMember::Object { object, property } => {
+            let object = fold_boxed(object, |object| folder.fold_variable_member(object));
+            let property = folder.fold_identifier_reference(property);
+            VariableMember::Object { object, property }
+        }
+        VariableMember::Property { property } => {
+            VariableMember::Property { property: folder.fold_identifier_reference(property) }
+        }
+    }
+}
+
+pub fn walk_parenthesized_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: ParenthesizedExpression<'src>,
+) -> ParenthesizedExpression<'src> {
+    let body = match it.body {
+        ParenthesizedBody::Single(expr) => ParenthesizedBody::Single(folder.fold_expression(expr)),
+        ParenthesizedBody::Multiple(stmts) => {
+            ParenthesizedBody::Multiple(folder.fold_statements(stmts))
+        }
+    };
+    ParenthesizedExpression { body, ..it }
+}
+
+pub fn walk_block_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: BlockExpression<'src>,
+) -> BlockExpression<'src> {
+    let statements = folder.fold_statements(it.statements);
+    BlockExpression { statements, ..it }
+}
+
+pub fn walk_binary_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: BinaryExpression<'src>,
+) -> BinaryExpression<'src> {
+    let left = folder.fold_expression(it.left);
+    let right = folder.fold_expression(it.right);
+    BinaryExpression { left, right, ..it }
+}
+
+pub fn walk_unary_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: UnaryExpression<'src>,
+) -> UnaryExpression<'src> {
+    let argument = folder.fold_expression(it.argument);
+    UnaryExpression { argument, ..it }
+}
+
+pub fn walk_update_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: UpdateExpression<'src>,
+) -> UpdateExpression<'src> {
+    let variable = folder.fold_variable_expression(it.variable);
+    UpdateExpression { variable, ..it }
+}
+
+pub fn walk_ternary_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: TernaryExpression<'src>,
+) -> TernaryExpression<'src> {
+    let test = folder.fold_expression(it.test);
+    let consequent = folder.fold_expression(it.consequent);
+    let alternate = folder.fold_expression(it.alternate);
+    TernaryExpression { test, consequent, alternate, ..it }
+}
+
+pub fn walk_conditional_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: ConditionalExpression<'src>,
+) -> ConditionalExpression<'src> {
+    let test = folder.fold_expression(it.test);
+    let consequent = folder.fold_expression(it.consequent);
+    ConditionalExpression { test, consequent, ..it }
+}
+
+pub fn walk_resource_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: ResourceExpression<'src>,
+) -> ResourceExpression<'src> {
+    let name = folder.fold_identifier_reference(it.name);
+    ResourceExpression { name, ..it }
+}
+
+pub fn walk_array_access_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: ArrayAccessExpression<'src>,
+) -> ArrayAccessExpression<'src> {
+    let name = folder.fold_identifier_reference(it.name);
+    let index = folder.fold_expression(it.index);
+    ArrayAccessExpression { name, index, ..it }
+}
+
+pub fn walk_arrow_access_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: ArrowAccessExpression<'src>,
+) -> ArrowAccessExpression<'src> {
+    let left = folder.fold_expression(it.left);
+    let right = folder.fold_expression(it.right);
+    ArrowAccessExpression { left, right, ..it }
+}
+
+pub fn walk_call_expression<'src>(
+    folder: &mut impl Fold<'src>,
+    it: CallExpression<'src>,
+) -> CallExpression<'src> {
+    let callee = folder.fold_identifier_reference(it.callee);
+    let arguments =
+        it.arguments.map(|args| args.into_iter().map(|arg| folder.fold_expression(arg)).collect());
+    CallExpression { callee, arguments, ..it }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    /// Only overrides a leaf, so every other node goes through `walk_*`.
+    #[derive(Default)]
+    struct NumberCounter {
+        count: usize,
+    }
+
+    impl<'src> Fold<'src> for NumberCounter {
+        fn fold_numeric_literal(&mut self, it: NumericLiteral<'src>) -> NumericLiteral<'src> {
+            self.count += 1;
+            it
+        }
+    }
+
+    #[test]
+    fn test_walk_rebuilds_identical_tree() {
+        let sources = [
+            "math.sin(v.a * 2) + (v.b ?? 3)",
+            "v.a.b = -4; t.c += 5 ** 6; loop(7, { break; continue; }); \
+             for_each(t.e, q.get_nearby_entities(8), { t.e->q.health; }); \
+             v.s = 'a' == 'b' ? true : false; v.x ? 9; v.y++; this; geometry.default; \
+             array.a[10]; v.z = (v.w = 11; t.u;); return 12;",
+            "v.a = ; v.b = (13 +);",
+        ];
+        let counts = [2, 9, 0];
+        for (source, count) in sources.into_iter().zip(counts) {
+            let program = Parser::new(source).parse().program;
+            let mut folder = NumberCounter::default();
+            assert_eq!(fold(&mut folder, program.clone()), program, "{source}");
+            assert_eq!(folder.count, count, "{source}");
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{Codegen, CodegenOptions, ParseResult, Parser, cst::Cst, diagnostic::Diagnostic};
+
+pub struct FormatterOptions {
+    /// The number of spaces per indentation level.
+    pub indent_width: usize,
+    /// Long call argument lists and binary chains are broken up into multiple
+    /// lines to fit within this width. `0` disables wrapping.
+    pub max_line_width: usize,
+    /// Prints `v`, `t`, `c` and `q` instead of `variable`, `temp`, `context`
+    /// and `query`.
+    pub short_names: bool,
+}
+
+impl Default for FormatterOptions {
+    fn default() -> Self {
+        Self { indent_width: 4, max_line_width: 100, short_names: false }
+    }
+}
+
+/// Formats Molang source code while keeping its comments.
+///
+/// # Example
+///
+/// ```
+/// # use nolana::Formatter;
+/// let source = "v.x=1;// Start at one\nv.y=q.foo(v.x,'bar');";
+/// let out = Formatter::default().format(source).unwrap();
+/// assert_eq!(out, "variable.x = 1; // Start at one\nvariable.y = query.foo(variable.x, 'bar');\n");
+/// ```
+#[derive(Default)]
+pub struct Formatter {
+    options: FormatterOptions,
+}
+
+impl Formatter {
+    pub fn with_options(mut self, options: FormatterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Formats the source code, or returns its syntax errors. Source code with
+    /// syntax errors is never formatted since the AST may be missing parts of
+    /// it.
+    pub fn format(self, source: &str) -> Result<String, Vec<Diagnostic>> {
+        let ParseResult { program, errors } = Parser::new(source).parse();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let FormatterOptions { indent_width, max_line_width, short_names } = self.options;
+        let options = CodegenOptions::default()
+            .with_minify(false)
+            .with_short_names(short_names)
+            .with_indent_width(indent_width)
+            .with_max_line_width(max_line_width);
+        let codegen = Codegen::default().with_options(options).with_comments(&Cst::new(source));
+        Ok(codegen.build(&program))
+    }
+}
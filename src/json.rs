@@ -0,0 +1,489 @@
+//! Molang embedded in resource pack JSON files.
+//!
+//! Entity, attachable, animation, animation controller, render controller and
+//! particle files keep their Molang in JSON strings. [`extract`] finds those
+//! strings and [`EmbeddedMolang`] maps the spans of the Molang inside them
+//! back to the JSON file, so diagnostics point at the right place in the pack.
+//!
+//! # Example
+//!
+//! ```
+//! # use nolana::{json, line_index::LineIndex};
+//! let source = r#"{
+//!     "animation_controllers": {
+//!         "controller.animation.walk": {
+//!             "states": {
+//!                 "default": {
+//!                     "transitions": [{ "walking": "q.modified_move_speed > 0.1 &&" }]
+//!                 }
+//!             }
+//!         }
+//!     }
+//! }"#;
+//! let diagnostics = json::check(source);
+//! let index = LineIndex::new(source);
+//! assert_eq!(
+//!     diagnostics[0].to_short_string("walk.json", &index),
+//!     "walk.json:6:81: error: unexpected token"
+//! );
+//! ```
+
+use std::borrow::Cow;
+
+use crate::{
+    ParseResult, Parser,
+    diagnostic::{Code, Diagnostic},
+    semantic::SemanticChecker,
+    span::Span,
+};
+
+/// A Molang string found in a JSON document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedMolang<'json> {
+    /// [JSON Pointer] to the string, such as
+    /// `/animations/animation.walk/anim_time_update`.
+    ///
+    /// [JSON Pointer]: <https://www.rfc-editor.org/rfc/rfc6901>
+    pub pointer: String,
+    /// The Molang source code, with JSON escapes resolved.
+    pub source: Cow<'json, str>,
+    /// The contents of the string in the JSON document, without the quotes.
+    pub span: Span,
+    /// Offsets in [`source`] and in the JSON document right after each escape
+    /// sequence.
+    ///
+    /// [`source`]: EmbeddedMolang::source
+    escapes: Vec<(u32, u32)>,
+}
+
+impl<'json> EmbeddedMolang<'json> {
+    /// Parses the Molang. Spans in the result are relative to [`source`];
+    /// use [`to_json_span`] and [`to_json_diagnostic`] to map them back to
+    /// the JSON document.
+    ///
+    /// [`source`]: EmbeddedMolang::source
+    /// [`to_json_span`]: EmbeddedMolang::to_json_span
+    /// [`to_json_diagnostic`]: EmbeddedMolang::to_json_diagnostic
+    pub fn parse(&self) -> ParseResult<'_> {
+        Parser::new(&self.source).parse()
+    }
+
+    /// Converts an offset in [`source`] to an offset in the JSON document.
+    ///
+    /// [`source`]: EmbeddedMolang::source
+    pub fn to_json_offset(&self, offset: u32) -> u32 {
+        let index = self.escapes.partition_point(|&(molang, _)| molang <= offset);
+        let (molang, json) = match index {
+            0 => (0, self.span.start),
+            _ => self.escapes[index - 1],
+        };
+        json + (offset - molang)
+    }
+
+    /// Converts a span in [`source`] to a span in the JSON document.
+    ///
+    /// [`source`]: EmbeddedMolang::source
+    pub fn to_json_span(&self, span: Span) -> Span {
+        Span::new(self.to_json_offset(span.start), self.to_json_offset(span.end))
+    }
+
+    /// Moves the labels and fixes of a diagnostic about [`source`] to the
+    /// JSON document. The text inserted by fixes is escaped.
+    ///
+    /// [`source`]: EmbeddedMolang::source
+    pub fn to_json_diagnostic(&self, diagnostic: Diagnostic) -> Diagnostic {
+        let mut diagnostic = diagnostic.map_spans(|span| self.to_json_span(span));
+        for edit in diagnostic.edits_mut() {
+            edit.replacement = escape(&edit.replacement).into();
+        }
+        diagnostic
+    }
+}
+
+/// Finds every Molang string in a JSON document.
+///
+/// Which strings are Molang is decided by their location in the document, so
+/// only the file types listed in the [module docs](self) are recognized.
+/// Comments are allowed, like in Minecraft.
+pub fn extract(json: &str) -> Result<Vec<EmbeddedMolang<'_>>, Diagnostic> {
+    let mut extractor = Extractor { json, pos: 0, path: Vec::new(), found: Vec::new() };
+    extractor.skip_trivia();
+    extractor.parse_value()?;
+    extractor.skip_trivia();
+    if extractor.pos < json.len() {
+        return Err(extractor.expected("end of file"));
+    }
+    Ok(extractor.found)
+}
+
+/// Parses and semantically checks every Molang string in a JSON document.
+///
+/// The spans of the diagnostics are offsets in the JSON document.
+pub fn check(json: &str) -> Vec<Diagnostic> {
+    let embedded = match extract(json) {
+        Ok(embedded) => embedded,
+        Err(error) => return vec![error],
+    };
+    let mut diagnostics = Vec::new();
+    for molang in &embedded {
+        let ParseResult { program, mut errors } = molang.parse();
+        errors.extend(SemanticChecker::default().check(&program));
+        diagnostics.extend(errors.into_iter().map(|error| molang.to_json_diagnostic(error)));
+    }
+    diagnostics
+}
+
+/// A step in the path from the root of the document to a value.
+#[derive(Debug, Clone, Copy)]
+enum Segment<'json> {
+    /// An object key, as written in the document.
+    Key(&'json str),
+    Index(usize),
+}
+
+use Segment::{Index, Key};
+
+/// Returns whether the string at `path` is Molang.
+fn is_molang(path: &[Segment]) -> bool {
+    match path {
+        [
+            Key("minecraft:client_entity" | "minecraft:entity" | "minecraft:attachable"),
+            Key("description"),
+            rest @ ..,
+        ] => {
+            matches!(
+                rest,
+                [Key("scripts"), Key("initialize" | "pre_animation"), Index(_)]
+                    | [
+                        Key("scripts"),
+                        Key("scale"
+                            | "scaleX"
+                            | "scaleY"
+                            | "scaleZ"
+                            | "should_update_bones_and_effects_offscreen"
+                            | "should_update_effects_offscreen")
+                    ]
+                    | [Key("scripts"), Key("animate"), Index(_), Key(_)]
+                    | [Key("render_controllers"), Index(_), Key(_)]
+            )
+        }
+        [Key("animations"), _, rest @ ..] => match rest {
+            [Key("anim_time_update" | "blend_weight" | "start_delay" | "loop_delay")] => true,
+            [Key("bones"), _, Key("rotation" | "position" | "scale"), rest @ ..] => {
+                !matches!(rest.last(), Some(Key("lerp_mode")))
+            }
+            [Key("timeline"), _] | [Key("timeline"), _, Index(_)] => true,
+            [Key("particle_effects"), .., Key("pre_effect_script")] => true,
+            _ => false,
+        },
+        [Key("animation_controllers"), _, Key("states"), _, rest @ ..] => matches!(
+            rest,
+            [Key("transitions"), Index(_), Key(_)]
+                | [Key("on_entry" | "on_exit"), Index(_)]
+                | [Key("animations"), Index(_), Key(_)]
+                | [Key("variables"), _, Key("input")]
+                | [Key("particle_effects"), Index(_), Key("pre_effect_script")]
+        ),
+        [Key("render_controllers"), _, rest @ ..] => matches!(
+            rest,
+            [Key("geometry" | "light_color_multiplier")]
+                | [Key("textures"), Index(_)]
+                | [Key("materials" | "part_visibility"), Index(_), Key(_)]
+                | [Key("color" | "overlay_color" | "on_fire_color" | "is_hurt_color"), Key(_)]
+                | [Key("uv_anim"), Key("offset" | "scale"), Index(_)]
+        ),
+        [Key("particle_effect"), Key("components"), Key(component), rest @ ..] => {
+            // Almost every string in a component is Molang. These are the
+            // exceptions, which hold names, modes or colors.
+            let names = matches!(
+                *component,
+                "minecraft:emitter_lifetime_events"
+                    | "minecraft:particle_lifetime_events"
+                    | "minecraft:particle_expire_if_in_blocks"
+                    | "minecraft:particle_expire_if_not_in_blocks"
+            );
+            let keys = rest.iter().any(|segment| {
+                matches!(segment, Key("gradient" | "event" | "events" | "expiration_event"))
+            });
+            let last = matches!(
+                rest.last(),
+                Some(Key("texture"
+                    | "material"
+                    | "facing_camera_mode"
+                    | "mode"
+                    | "direction"
+                    | "plane_normal"))
+            );
+            !names && !keys && !last
+        }
+        _ => false,
+    }
+}
+
+struct Extractor<'json> {
+    json: &'json str,
+    pos: usize,
+    path: Vec<Segment<'json>>,
+    found: Vec<EmbeddedMolang<'json>>,
+}
+
+impl<'json> Extractor<'json> {
+    fn peek(&self) -> Option<u8> {
+        self.json.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let eaten = self.peek() == Some(byte);
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), Diagnostic> {
+        if !self.eat(byte) {
+            return Err(self.expected(&format!("`{}`", byte as char)));
+        }
+        Ok(())
+    }
+
+    fn expected(&self, expected: &str) -> Diagnostic {
+        let end = self.json[self.pos..].chars().next().map_or(0, char::len_utf8);
+        let span = Span::new(self.pos as u32, (self.pos + end) as u32);
+        Diagnostic::error(format!("invalid JSON, expected {expected}"))
+            .with_code(Code::InvalidJson)
+            .with_label(span)
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) {
+        loop {
+            let rest = &self.json[self.pos..];
+            let trimmed = rest.trim_start_matches([' ', '\t', '\n', '\r']);
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                self.pos += comment.find("*/").map_or(trimmed.len(), |end| end + 4);
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<(), Diagnostic> {
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => {
+                let raw = self.parse_string()?;
+                if is_molang(&self.path) {
+                    self.found.push(self.embedded_molang(raw)?);
+                }
+                Ok(())
+            }
+            _ => {
+                // Numbers, booleans and `null`.
+                let rest = &self.json[self.pos..];
+                let len = rest
+                    .find(|ch: char| !ch.is_ascii_alphanumeric() && !"+-.".contains(ch))
+                    .unwrap_or(rest.len());
+                if len == 0 {
+                    return Err(self.expected("a value"));
+                }
+                self.pos += len;
+                Ok(())
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<(), Diagnostic> {
+        self.expect(b'{')?;
+        loop {
+            self.skip_trivia();
+            if self.eat(b'}') {
+                return Ok(());
+            }
+            if self.peek() != Some(b'"') {
+                return Err(self.expected("a key"));
+            }
+            let key = self.parse_string()?;
+            self.skip_trivia();
+            self.expect(b':')?;
+            self.skip_trivia();
+            self.path.push(Key(&self.json[key]));
+            self.parse_value()?;
+            self.path.pop();
+            self.skip_trivia();
+            if !self.eat(b',') {
+                return self.expect(b'}');
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<(), Diagnostic> {
+        self.expect(b'[')?;
+        for index in 0.. {
+            self.skip_trivia();
+            if self.eat(b']') {
+                break;
+            }
+            self.path.push(Index(index));
+            self.parse_value()?;
+            self.path.pop();
+            self.skip_trivia();
+            if !self.eat(b',') {
+                return self.expect(b']');
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the span of the contents of the string.
+    fn parse_string(&mut self) -> Result<Span, Diagnostic> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(b'"') => break,
+                Some(b'\\') => self.pos += 2,
+                Some(_) => self.pos += 1,
+                None => {
+                    let span = Span::new(start as u32 - 1, self.json.len() as u32);
+                    return Err(Diagnostic::error("invalid JSON, unterminated string")
+                        .with_code(Code::InvalidJson)
+                        .with_label(span));
+                }
+            }
+        }
+        let span = Span::new(start as u32, self.pos as u32);
+        self.pos += 1;
+        Ok(span)
+    }
+
+    fn embedded_molang(&self, span: Span) -> Result<EmbeddedMolang<'json>, Diagnostic> {
+        let (source, escapes) = unescape(self.json, span)?;
+        Ok(EmbeddedMolang { pointer: self.pointer(), source, span, escapes })
+    }
+
+    fn pointer(&self) -> String {
+        let mut pointer = String::new();
+        for segment in &self.path {
+            pointer.push('/');
+            match segment {
+                Key(key) => {
+                    let key = match unescape(key, Span::new(0, key.len() as u32)) {
+                        Ok((key, _)) => key,
+                        Err(_) => Cow::Borrowed(*key),
+                    };
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                }
+                Index(index) => pointer.push_str(&index.to_string()),
+            }
+        }
+        pointer
+    }
+}
+
+/// Escapes text to be inserted into a JSON string.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ch if ch.is_control() => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// A string with its escape sequences resolved, and the offsets right after
+/// each of them.
+type Unescaped<'json> = (Cow<'json, str>, Vec<(u32, u32)>);
+
+/// Resolves the escape sequences of the string at `span`.
+fn unescape(json: &str, span: Span) -> Result<Unescaped<'_>, Diagnostic> {
+    let raw = &json[span];
+    if !raw.contains('\\') {
+        return Ok((Cow::Borrowed(raw), Vec::new()));
+    }
+    let invalid = |start: usize, len: usize| {
+        let start = span.start + start as u32;
+        let end = (start + len as u32).min(span.end);
+        Diagnostic::error("invalid JSON, invalid escape sequence")
+            .with_code(Code::InvalidJson)
+            .with_label(Span::new(start, end))
+    };
+    let mut source = String::with_capacity(raw.len());
+    let mut escapes = Vec::new();
+    let mut pos = 0;
+    while let Some(backslash) = raw[pos..].find('\\') {
+        let start = pos + backslash;
+        source.push_str(&raw[pos..start]);
+        let (ch, len) = match raw.as_bytes().get(start + 1) {
+            Some(b'"') => ('"', 2),
+            Some(b'\\') => ('\\', 2),
+            Some(b'/') => ('/', 2),
+            Some(b'b') => ('\u{8}', 2),
+            Some(b'f') => ('\u{c}', 2),
+            Some(b'n') => ('\n', 2),
+            Some(b'r') => ('\r', 2),
+            Some(b't') => ('\t', 2),
+            Some(b'u') => {
+                let code_unit = |at: usize| {
+                    let hex = raw.get(at + 2..at + 6)?;
+                    u16::from_str_radix(hex, 16).ok()
+                };
+                let high = code_unit(start).ok_or_else(|| invalid(start, 6))?;
+                let (code_point, len) = match high {
+                    0xD800..=0xDBFF if raw[start + 6..].starts_with("\\u") => {
+                        let low = code_unit(start + 6)
+                            .filter(|low| (0xDC00..=0xDFFF).contains(low))
+                            .ok_or_else(|| invalid(start, 12))?;
+                        let code_point =
+                            0x10000 + ((high as u32 - 0xD800) << 10) + (low as u32 - 0xDC00);
+                        (code_point, 12)
+                    }
+                    _ => (high as u32, 6),
+                };
+                (char::from_u32(code_point).ok_or_else(|| invalid(start, len))?, len)
+            }
+            _ => return Err(invalid(start, 2)),
+        };
+        source.push(ch);
+        pos = start + len;
+        escapes.push((source.len() as u32, span.start + pos as u32));
+    }
+    source.push_str(&raw[pos..]);
+    Ok((Cow::Owned(source), escapes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escapes() {
+        let json = r#"{ "animations": { "a": { "blend_weight": "q.is_name_any('é\n') + v.x" } } }"#;
+        let embedded = extract(json).unwrap();
+        assert_eq!(embedded[0].pointer, "/animations/a/blend_weight");
+        assert_eq!(embedded[0].source, "q.is_name_any('é\n') + v.x");
+        let start = json.find("v.x").unwrap() as u32;
+        let source_start = embedded[0].source.find("v.x").unwrap() as u32;
+        let span = Span::new(source_start, source_start + 3);
+        assert_eq!(embedded[0].to_json_span(span), Span::new(start, start + 3));
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        assert_eq!(extract(r#"{ "a": 1, }"#).map(|found| found.len()), Ok(0));
+        assert_eq!(extract(r#"{ "a" 1 }"#).unwrap_err().span(), Some(Span::new(6, 7)));
+        assert_eq!(extract(r#"{ "a": "b"#).unwrap_err().span(), Some(Span::new(7, 9)));
+        assert_eq!(extract(r#"[1] 2"#).unwrap_err().span(), Some(Span::new(4, 5)));
+    }
+}
@@ -1,18 +1,34 @@
 #![doc = include_str!("../README.md")]
 
 mod parser;
-pub use parser::{ParseResult, Parser};
+pub use parser::{ParseResult, Parser, TextEdit};
 
 mod codegen;
 pub use codegen::{Codegen, CodegenOptions};
 
+mod formatter;
+pub use formatter::{Formatter, FormatterOptions};
+
 mod transformer;
 pub use transformer::MolangTransformer;
 
+mod constant_folder;
+pub use constant_folder::ConstantFolder;
+
+#[cfg(feature = "arena")]
+pub mod arena;
 pub mod ast;
+pub mod cst;
 pub mod diagnostic;
+pub mod eval;
+pub mod fold;
+pub mod json;
+pub mod line_index;
+pub mod lint;
 pub mod replace_with;
 pub mod semantic;
 pub mod span;
-mod token;
+pub mod token;
 pub mod traverse;
+pub mod visit;
+pub mod vm;
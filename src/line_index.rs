@@ -0,0 +1,206 @@
+//! Conversion between byte offsets and line/column positions.
+//!
+//! [`Span`]s are byte offsets into the source code. Editors and CI tools
+//! expect lines and columns instead, which [`LineIndex`] converts to and from.
+//!
+//! # Example
+//!
+//! ```
+//! # use nolana::{line_index::{Encoding, LineColumn, LineIndex}, span::Span};
+//! let index = LineIndex::new("v.x = 1;\nv.name = 'ä' + v.y;");
+//! let span = Span::new(25, 28); // `v.y`
+//! let range = index.range(span, Encoding::Utf16);
+//! assert_eq!(range.start, LineColumn { line: 1, column: 15 });
+//! assert_eq!(index.span(range, Encoding::Utf16), Some(span));
+//! ```
+
+use crate::span::Span;
+
+/// How columns are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Bytes, like Rust strings.
+    Utf8,
+    /// UTF-16 code units, like JavaScript strings and the Language Server
+    /// Protocol.
+    Utf16,
+}
+
+/// A zero-based line and column.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineColumn {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// The start and end of a [`Span`] as lines and columns.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LineRange {
+    pub start: LineColumn,
+    pub end: LineColumn,
+}
+
+/// Index of the lines of some source code.
+///
+/// The index doesn't borrow the source code, so it can be kept next to an
+/// owned copy of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// The offset of the first character of every line.
+    line_starts: Vec<u32>,
+    /// Every non-ASCII character, which takes more bytes in UTF-8 than code
+    /// units in UTF-16.
+    wide_chars: Vec<WideChar>,
+    len: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WideChar {
+    offset: u32,
+    len_utf8: u8,
+    len_utf16: u8,
+}
+
+impl WideChar {
+    fn end(self) -> u32 {
+        self.offset + self.len_utf8 as u32
+    }
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        let mut wide_chars = Vec::new();
+        for (offset, ch) in source.char_indices() {
+            if ch == '\n' {
+                line_starts.push(offset as u32 + 1);
+            } else if !ch.is_ascii() {
+                wide_chars.push(WideChar {
+                    offset: offset as u32,
+                    len_utf8: ch.len_utf8() as u8,
+                    len_utf16: ch.len_utf16() as u8,
+                });
+            }
+        }
+        Self { line_starts, wide_chars, len: source.len() as u32 }
+    }
+
+    /// The number of lines, which is one more than the number of line breaks.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Converts a byte offset into a line and column. Offsets past the end of
+    /// the source code are treated as the end.
+    pub fn line_column(&self, offset: u32, encoding: Encoding) -> LineColumn {
+        let offset = offset.min(self.len);
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let mut column = offset - line_start;
+        if encoding == Encoding::Utf16 {
+            column -= self
+                .wide_chars_between(line_start, offset)
+                .iter()
+                .map(|ch| (ch.len_utf8 - ch.len_utf16) as u32)
+                .sum::<u32>();
+        }
+        LineColumn { line: line as u32, column }
+    }
+
+    /// Converts a line and column back into a byte offset. Returns `None` if
+    /// the position is past the end of its line or in the middle of a
+    /// character.
+    pub fn offset(&self, position: LineColumn, encoding: Encoding) -> Option<u32> {
+        let line = position.line as usize;
+        let line_start = *self.line_starts.get(line)?;
+        // The line break is the last position on a line.
+        let line_end = self.line_starts.get(line + 1).map_or(self.len, |start| start - 1);
+        let mut offset = line_start;
+        let mut column = position.column;
+        if encoding == Encoding::Utf16 {
+            for ch in self.wide_chars_between(line_start, line_end) {
+                let ascii_len = ch.offset - offset;
+                if column <= ascii_len {
+                    break;
+                }
+                column -= ascii_len;
+                if column < ch.len_utf16 as u32 {
+                    return None;
+                }
+                column -= ch.len_utf16 as u32;
+                offset = ch.end();
+            }
+        }
+        let offset = offset.checked_add(column).filter(|&offset| offset <= line_end)?;
+        // UTF-8 columns may point in the middle of a character as well.
+        let splits_char =
+            self.wide_chars_between(line_start, offset).last().is_some_and(|ch| ch.end() > offset);
+        (!splits_char).then_some(offset)
+    }
+
+    pub fn range(&self, span: Span, encoding: Encoding) -> LineRange {
+        LineRange {
+            start: self.line_column(span.start, encoding),
+            end: self.line_column(span.end, encoding),
+        }
+    }
+
+    pub fn span(&self, range: LineRange, encoding: Encoding) -> Option<Span> {
+        Some(Span::new(self.offset(range.start, encoding)?, self.offset(range.end, encoding)?))
+    }
+
+    /// Returns the wide characters starting within `start..end`.
+    fn wide_chars_between(&self, start: u32, end: u32) -> &[WideChar] {
+        let from = self.wide_chars.partition_point(|ch| ch.offset < start);
+        let to = self.wide_chars.partition_point(|ch| ch.offset < end);
+        &self.wide_chars[from..to]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_column(line: u32, column: u32) -> LineColumn {
+        LineColumn { line, column }
+    }
+
+    #[test]
+    fn test_lines() {
+        let index = LineIndex::new("a\nbc\r\n\nd");
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_column(0, Encoding::Utf8), line_column(0, 0));
+        assert_eq!(index.line_column(1, Encoding::Utf8), line_column(0, 1));
+        assert_eq!(index.line_column(2, Encoding::Utf8), line_column(1, 0));
+        assert_eq!(index.line_column(6, Encoding::Utf8), line_column(2, 0));
+        assert_eq!(index.line_column(7, Encoding::Utf8), line_column(3, 0));
+        assert_eq!(index.line_column(100, Encoding::Utf8), line_column(3, 1));
+        for offset in 0..=8 {
+            let position = index.line_column(offset, Encoding::Utf8);
+            assert_eq!(index.offset(position, Encoding::Utf8), Some(offset));
+        }
+        assert_eq!(index.offset(line_column(0, 2), Encoding::Utf8), None);
+        assert_eq!(index.offset(line_column(4, 0), Encoding::Utf8), None);
+    }
+
+    #[test]
+    fn test_utf16_columns() {
+        // `ä` is 2 bytes and 1 code unit, `😀` is 4 bytes and 2 code units.
+        let source = "'ä😀x'\n'😀'";
+        let index = LineIndex::new(source);
+        assert_eq!(index.line_column(3, Encoding::Utf16), line_column(0, 2));
+        assert_eq!(index.line_column(7, Encoding::Utf16), line_column(0, 4));
+        assert_eq!(index.line_column(7, Encoding::Utf8), line_column(0, 7));
+        assert_eq!(index.line_column(15, Encoding::Utf16), line_column(1, 3));
+        for (offset, _) in source.char_indices() {
+            let offset = offset as u32;
+            for encoding in [Encoding::Utf8, Encoding::Utf16] {
+                let position = index.line_column(offset, encoding);
+                assert_eq!(index.offset(position, encoding), Some(offset));
+            }
+        }
+        // In the middle of `😀`.
+        assert_eq!(index.offset(line_column(0, 3), Encoding::Utf16), None);
+        assert_eq!(index.offset(line_column(0, 4), Encoding::Utf8), None);
+    }
+}
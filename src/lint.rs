@@ -0,0 +1,388 @@
+//! Configurable checks for code that is valid Molang but goes against the
+//! conventions of a project.
+//!
+//! Unlike the [`SemanticChecker`](crate::semantic::SemanticChecker), which
+//! reports code that Minecraft rejects, every lint [`Rule`] can be turned
+//! off or made an error with a [`Level`]. Rules are usually implemented with
+//! [`Visit`](crate::visit::Visit), like the [built-in rules].
+//!
+//! Levels and rule options can be set from code with [`Linter::set_level`]
+//! and the constructors of the rules, or from a config file with
+//! [`Linter::configure`]:
+//!
+//! ```text
+//! # Comments start with `#`.
+//! no_exponent = deny
+//! max_name_length = warn
+//! max_name_length.max = 24
+//! ```
+//!
+//! A lint can be suppressed with a `nolana-ignore` comment, which applies to
+//! the line it is on, or to the next line if it is on a line of its own.
+//! `nolana-ignore-file` applies to the whole source code. Both take the rules
+//! to suppress, or suppress every rule if none are given:
+//!
+//! ```text
+//! // nolana-ignore-file max_name_length
+//! v.a = v.b ** 2; // nolana-ignore no_exponent
+//! ```
+//!
+//! [built-in rules]: rules
+
+pub mod rules;
+
+use std::{borrow::Cow, fmt, str::FromStr};
+
+use miette::Severity;
+
+use crate::{
+    ast::Program,
+    cst::{Cst, Trivia},
+    diagnostic::{Code, Diagnostic},
+    line_index::{Encoding, LineIndex},
+    span::Span,
+};
+
+/// How a lint [`Rule`] is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Level {
+    /// The rule is not checked.
+    Allow,
+    /// Problems are reported as warnings.
+    Warn,
+    /// Problems are reported as errors.
+    Deny,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Warn => "warn",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = ();
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level {
+            "allow" => Ok(Self::Allow),
+            "warn" => Ok(Self::Warn),
+            "deny" => Ok(Self::Deny),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A lint that checks a program for a single kind of problem.
+pub trait Rule {
+    /// The name the rule is configured and suppressed with, such as
+    /// `no_exponent`.
+    fn name(&self) -> &'static str;
+
+    /// The level the rule has unless it is configured.
+    fn default_level(&self) -> Level {
+        Level::Warn
+    }
+
+    /// Sets an option from a config file, such as `max` in
+    /// `max_name_length.max = 24`. Returns a message if the option is unknown
+    /// or its value is invalid.
+    fn set_option(&mut self, option: &str, value: &str) -> Result<(), String> {
+        let _ = value;
+        Err(format!("unknown option `{option}`"))
+    }
+
+    /// Returns the problems in the program. The [`Linter`] decides their
+    /// severity, so it doesn't matter which one they are created with.
+    fn check(&mut self, program: &Program) -> Vec<Diagnostic>;
+}
+
+struct RuleEntry {
+    rule: Box<dyn Rule>,
+    level: Level,
+}
+
+/// Runs lint [`Rule`]s over programs.
+///
+/// The default linter has every [built-in rule](rules) with its default
+/// level.
+pub struct Linter {
+    rules: Vec<RuleEntry>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self { rules: Vec::new() }
+            .with_rule(rules::NoExponent::default())
+            .with_rule(rules::MaxNameLength::default())
+            .with_rule(rules::MaxLoopCount::default())
+    }
+}
+
+impl Linter {
+    /// Adds a rule with its default level. A rule with the same name is
+    /// replaced.
+    pub fn with_rule(mut self, rule: impl Rule + 'static) -> Self {
+        let entry = RuleEntry { level: rule.default_level(), rule: Box::new(rule) };
+        match self.rules.iter_mut().find(|other| other.rule.name() == entry.rule.name()) {
+            Some(other) => *other = entry,
+            None => self.rules.push(entry),
+        }
+        self
+    }
+
+    /// Returns the names and levels of the rules.
+    pub fn rules(&self) -> impl Iterator<Item = (&'static str, Level)> {
+        self.rules.iter().map(|entry| (entry.rule.name(), entry.level))
+    }
+
+    /// Sets the level of the rule called `name`.
+    pub fn set_level(&mut self, name: &str, level: Level) -> Result<(), Diagnostic> {
+        let Some(entry) = self.rules.iter_mut().find(|entry| entry.rule.name() == name) else {
+            return Err(invalid_config(format!("unknown lint rule `{name}`")));
+        };
+        entry.level = level;
+        Ok(())
+    }
+
+    /// Applies a config file, where each line sets the level of a rule with
+    /// `rule = level`, or one of its options with `rule.option = value`.
+    /// Errors are labeled with spans into the config file.
+    pub fn configure(&mut self, config: &str) -> Result<(), Diagnostic> {
+        let mut line_start = 0;
+        for line in config.split_inclusive('\n') {
+            let offset = line_start;
+            line_start += line.len();
+            let span = |text: &str| {
+                let start = offset + (text.as_ptr() as usize - line.as_ptr() as usize);
+                Span::new(start as u32, (start + text.len()) as u32)
+            };
+            let content = line.split('#').next().unwrap_or_default().trim();
+            if content.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = content.split_once('=') else {
+                return Err(invalid_config("expected `rule = level` or `rule.option = value`")
+                    .with_label(span(content)));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let (name, option) = match key.split_once('.') {
+                Some((name, option)) => (name, Some(option)),
+                None => (key, None),
+            };
+            let Some(entry) = self.rules.iter_mut().find(|entry| entry.rule.name() == name) else {
+                return Err(
+                    invalid_config(format!("unknown lint rule `{name}`")).with_label(span(name))
+                );
+            };
+            match option {
+                Some(option) => entry
+                    .rule
+                    .set_option(option, value)
+                    .map_err(|message| invalid_config(message).with_label(span(content)))?,
+                None => {
+                    entry.level = value.parse().map_err(|()| {
+                        invalid_config(format!("unknown lint level `{value}`"))
+                            .with_help("expected `allow`, `warn` or `deny`")
+                            .with_label(span(value))
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs every rule that is not allowed and returns the problems that are
+    /// not suppressed by comments.
+    pub fn lint(&mut self, program: &Program) -> Vec<Diagnostic> {
+        let suppressions = Suppressions::new(&program.source);
+        let mut diagnostics = Vec::new();
+        for entry in &mut self.rules {
+            let severity = match entry.level {
+                Level::Allow => continue,
+                Level::Warn => Severity::Warning,
+                Level::Deny => Severity::Error,
+            };
+            let name = entry.rule.name();
+            for diagnostic in entry.rule.check(program) {
+                if diagnostic.span().is_some_and(|span| suppressions.contains(name, span)) {
+                    continue;
+                }
+                diagnostics.push(diagnostic.with_lint(name).with_severity(severity));
+            }
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span().map(|span| span.start));
+        diagnostics
+    }
+}
+
+/// The `nolana-ignore` comments of a source code.
+struct Suppressions<'src> {
+    index: LineIndex,
+    /// The line each comment applies to, or `None` for the whole source code,
+    /// and the rules it suppresses. No rules suppress every rule.
+    comments: Vec<(Option<u32>, Vec<&'src str>)>,
+}
+
+impl<'src> Suppressions<'src> {
+    fn new(source: &'src str) -> Self {
+        let index = LineIndex::new(source);
+        let mut comments = Vec::new();
+        // Most programs have no suppressions, so they skip building the CST.
+        if !source.contains("nolana-ignore") {
+            return Self { index, comments };
+        }
+        let cst = Cst::new(source);
+        for token in cst.tokens() {
+            // Leading comments apply to the line of the token after them.
+            let line = index.line_column(token.span.start, Encoding::Utf8).line;
+            let trivia = token.leading_trivia.iter().chain(&token.trailing_trivia);
+            for comment in trivia.filter(|trivia| trivia.kind.is_comment()) {
+                if let Some((file, rules)) = parse_suppression(comment) {
+                    comments.push(((!file).then_some(line), rules));
+                }
+            }
+        }
+        Self { index, comments }
+    }
+
+    fn contains(&self, rule: &str, span: Span) -> bool {
+        if self.comments.is_empty() {
+            return false;
+        }
+        let line = self.index.line_column(span.start, Encoding::Utf8).line;
+        self.comments.iter().any(|(comment_line, rules)| {
+            comment_line.is_none_or(|comment_line| comment_line == line)
+                && (rules.is_empty() || rules.contains(&rule))
+        })
+    }
+}
+
+/// Parses `nolana-ignore rule, ...` and `nolana-ignore-file rule, ...`
+/// comments into whether they apply to the whole file and their rules.
+fn parse_suppression<'src>(comment: &Trivia<'src>) -> Option<(bool, Vec<&'src str>)> {
+    let text = comment.text.strip_prefix("//").or_else(|| comment.text.strip_prefix("/*"))?;
+    let text = text.strip_suffix("*/").unwrap_or(text).trim();
+    let text = text.strip_prefix("nolana-ignore")?;
+    let (file, rules) = match text.strip_prefix("-file") {
+        Some(rules) => (true, rules),
+        None => (false, text),
+    };
+    // Rejects comments like `nolana-ignored`.
+    if rules.starts_with(|ch: char| !ch.is_whitespace()) {
+        return None;
+    }
+    let rules = rules.split([',', ' ', '\t']).filter(|rule| !rule.is_empty()).collect();
+    Some((file, rules))
+}
+
+fn invalid_config(message: impl Into<Cow<'static, str>>) -> Diagnostic {
+    Diagnostic::error(message).with_code(Code::InvalidLintConfig)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn lint(linter: &mut Linter, source: &str) -> Vec<String> {
+        let result = Parser::new(source).parse();
+        assert!(result.errors.is_empty());
+        let index = LineIndex::new(source);
+        let diagnostics = linter.lint(&result.program);
+        diagnostics.iter().map(|diagnostic| diagnostic.to_short_string("a", &index)).collect()
+    }
+
+    #[test]
+    fn test_configure() {
+        let mut linter = Linter::default();
+        let config = "# House rules\nno_exponent = deny\n\nmax_name_length.max = 3 # short\n";
+        linter.configure(config).unwrap();
+        assert_eq!(
+            linter.rules().collect::<Vec<_>>(),
+            [
+                ("no_exponent", Level::Deny),
+                ("max_name_length", Level::Warn),
+                ("max_loop_count", Level::Warn)
+            ]
+        );
+        assert_eq!(
+            lint(&mut linter, "v.abcd = 2 ** 2;"),
+            [
+                "a:1:3: warning: variable name `abcd` is longer than 3 characters",
+                "a:1:10: error: `**` is not supported by Minecraft",
+            ]
+        );
+
+        let mut error = |config: &str| linter.configure(config).unwrap_err().span();
+        assert_eq!(error("\nfoo = deny"), Some(Span::new(1, 4)));
+        assert_eq!(error("no_exponent = never"), Some(Span::new(14, 19)));
+        assert_eq!(error("no_exponent.max = 1"), Some(Span::new(0, 19)));
+        assert_eq!(error("max_name_length.max = -1"), Some(Span::new(0, 24)));
+        assert_eq!(error("  max_loop_count\n"), Some(Span::new(2, 16)));
+        assert!(linter.set_level("foo", Level::Allow).is_err());
+    }
+
+    #[test]
+    fn test_suppressions() {
+        let mut linter = Linter::default();
+        linter.set_level("no_exponent", Level::Warn).unwrap();
+        let source = "\
+v.a = 1 ** 2; // nolana-ignore
+// nolana-ignore max_name_length, no_exponent
+v.a = 1 ** 2;
+v.a = 1 ** 2; /* nolana-ignore max_name_length */
+v.a = 1 ** 2; // nolana-ignored
+";
+        assert_eq!(
+            lint(&mut linter, source),
+            [
+                "a:4:7: warning: `**` is not supported by Minecraft",
+                "a:5:7: warning: `**` is not supported by Minecraft"
+            ]
+        );
+        let source = "v.a = 1 ** 2;\n// nolana-ignore-file no_exponent\nv.a = 1 ** 2;";
+        assert!(lint(&mut linter, source).is_empty());
+    }
+
+    #[test]
+    fn test_codes() {
+        struct NoThis;
+
+        impl Rule for NoThis {
+            fn name(&self) -> &'static str {
+                "no_this"
+            }
+
+            fn check(&mut self, _: &Program) -> Vec<Diagnostic> {
+                vec![Diagnostic::warning("`this` is not allowed")]
+            }
+        }
+
+        let mut linter = Linter::default().with_rule(NoThis);
+        linter.set_level("no_exponent", Level::Warn).unwrap();
+        let program = Parser::new("1 ** 2").parse().program;
+        let codes: Vec<_> = linter
+            .lint(&program)
+            .iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.lint, diagnostic.code_id()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (None, Some("no_this"), Some("no_this")),
+                (Some(Code::NoExponent), Some("no_exponent"), Some("nolana::lint::no_exponent")),
+            ]
+        );
+    }
+}
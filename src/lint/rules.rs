@@ -0,0 +1,184 @@
+//! Lint rules that come with Nolana.
+
+use std::mem;
+
+use crate::{
+    ast::*,
+    diagnostic::{Applicability, Code, Diagnostic, Fix},
+    lint::{Level, Rule},
+    span::Span,
+    traverse::Control,
+    visit::{Visit, visit},
+};
+
+/// Reports `**` and `**=`, which Minecraft doesn't support. Allowed by
+/// default, since the [`MolangTransformer`](crate::MolangTransformer) rewrites
+/// them.
+#[derive(Debug, Default)]
+pub struct NoExponent {
+    /// Source code of the program being checked, for the fixes.
+    source: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Rule for NoExponent {
+    fn name(&self) -> &'static str {
+        "no_exponent"
+    }
+
+    fn default_level(&self) -> Level {
+        Level::Allow
+    }
+
+    fn check(&mut self, program: &Program) -> Vec<Diagnostic> {
+        program.source.as_ref().clone_into(&mut self.source);
+        visit(self, program);
+        mem::take(&mut self.diagnostics)
+    }
+}
+
+impl<'a> Visit<'a> for NoExponent {
+    fn enter_binary_expression(&mut self, it: &BinaryExpression<'a>) -> Control {
+        if it.operator == BinaryOperator::Exponential {
+            let source = self.source.as_str();
+            let (left, right) = (&source[it.left.span()], &source[it.right.span()]);
+            let fix = Fix::new("use `math.pow`", Applicability::MachineApplicable)
+                .replace(it.span, format!("math.pow({left}, {right})"));
+            self.diagnostics.push(no_exponent(it.span).with_fix(fix));
+        }
+        Control::Continue
+    }
+
+    fn enter_assignment_statement(&mut self, it: &AssignmentStatement<'a>) -> Control {
+        if it.operator == AssignmentOperator::Exponential {
+            let source = self.source.as_str();
+            let (left, right) = (&source[it.left.span], &source[it.right.span()]);
+            let fix = Fix::new("use `math.pow`", Applicability::MachineApplicable)
+                .replace(it.span, format!("{left} = math.pow({left}, {right})"));
+            self.diagnostics.push(no_exponent(it.span).with_fix(fix));
+        }
+        Control::Continue
+    }
+}
+
+/// Reports variable names longer than `max` characters, 32 by default.
+#[derive(Debug)]
+pub struct MaxNameLength {
+    pub max: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl MaxNameLength {
+    pub fn new(max: usize) -> Self {
+        Self { max, diagnostics: Vec::new() }
+    }
+}
+
+impl Default for MaxNameLength {
+    fn default() -> Self {
+        Self::new(32)
+    }
+}
+
+impl Rule for MaxNameLength {
+    fn name(&self) -> &'static str {
+        "max_name_length"
+    }
+
+    fn set_option(&mut self, option: &str, value: &str) -> Result<(), String> {
+        match option {
+            "max" => self.max = parse_option(option, value)?,
+            _ => return Err(format!("unknown option `{option}`")),
+        }
+        Ok(())
+    }
+
+    fn check(&mut self, program: &Program) -> Vec<Diagnostic> {
+        visit(self, program);
+        mem::take(&mut self.diagnostics)
+    }
+}
+
+impl<'a> Visit<'a> for MaxNameLength {
+    fn enter_variable_member(&mut self, it: &VariableMember<'a>) -> Control {
+        let (VariableMember::Object { property, .. } | VariableMember::Property { property }) = it;
+        if property.name.chars().count() > self.max {
+            self.diagnostics.push(name_too_long(property, self.max));
+        }
+        Control::Continue
+    }
+}
+
+/// Reports `loop` counts greater than `max`, which is 1024 by default because
+/// Minecraft stops loops after 1024 iterations. Only number literals are
+/// checked.
+#[derive(Debug)]
+pub struct MaxLoopCount {
+    pub max: u32,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl MaxLoopCount {
+    pub fn new(max: u32) -> Self {
+        Self { max, diagnostics: Vec::new() }
+    }
+}
+
+impl Default for MaxLoopCount {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl Rule for MaxLoopCount {
+    fn name(&self) -> &'static str {
+        "max_loop_count"
+    }
+
+    fn set_option(&mut self, option: &str, value: &str) -> Result<(), String> {
+        match option {
+            "max" => self.max = parse_option(option, value)?,
+            _ => return Err(format!("unknown option `{option}`")),
+        }
+        Ok(())
+    }
+
+    fn check(&mut self, program: &Program) -> Vec<Diagnostic> {
+        visit(self, program);
+        mem::take(&mut self.diagnostics)
+    }
+}
+
+impl<'a> Visit<'a> for MaxLoopCount {
+    fn enter_loop_statement(&mut self, it: &LoopStatement<'a>) -> Control {
+        if let Expression::NumericLiteral(count) = &it.count
+            && count.value > self.max as f32
+        {
+            self.diagnostics.push(loop_count_too_large(count, self.max));
+        }
+        Control::Continue
+    }
+}
+
+fn parse_option<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("`{option}` must be a whole number, found `{value}`"))
+}
+
+fn no_exponent(span: Span) -> Diagnostic {
+    Diagnostic::warning("`**` is not supported by Minecraft")
+        .with_code(Code::NoExponent)
+        .with_help("use `math.pow` instead")
+        .with_label(span)
+}
+
+fn name_too_long(name: &Identifier, max: usize) -> Diagnostic {
+    Diagnostic::warning(format!("variable name `{}` is longer than {max} characters", name.name))
+        .with_code(Code::MaxNameLength)
+        .with_label(name.span)
+}
+
+fn loop_count_too_large(count: &NumericLiteral, max: u32) -> Diagnostic {
+    Diagnostic::warning(format!("`loop` runs {} times, which is more than {max}", count.raw))
+        .with_code(Code::MaxLoopCount)
+        .with_label(count.span)
+}
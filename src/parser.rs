@@ -1,12 +1,18 @@
+#[cfg(feature = "arena")]
+pub(crate) mod arena;
+mod incremental;
+
 use logos::{Lexer, Logos};
 
 use crate::{
     ast::*,
-    diagnostic::{Diagnostic, Result},
+    diagnostic::{Applicability, Code, Diagnostic, Fix, Result},
     span::Span,
     token::{Kind, Token},
 };
 
+pub use incremental::TextEdit;
+
 /// Return value of [`Parser::parse`] which contains the AST and errors.
 ///
 /// ## AST
@@ -21,8 +27,10 @@ use crate::{
 ///
 /// Nolana is able to recover from most syntax errors and continue parsing
 /// anyway. When this happens:
-/// 1. [`program`] will contain an AST
-/// 2. [`errors`] will be non-empty
+/// 1. [`program`] will contain an AST, where the code that could not be
+///    parsed is replaced by [`Statement::Error`] and [`Expression::Error`]
+///    nodes
+/// 2. [`errors`] will contain every syntax error
 ///
 /// [`program`]: ParseResult::program
 /// [`errors`]: ParseResult::errors
@@ -38,6 +46,8 @@ pub struct Parser<'src> {
     source_code: &'src str,
     token: Token,
     prev_token_end: u32,
+    /// The number of `(` and `{` that have been bumped but not closed yet.
+    depth: u32,
     is_complex: bool,
     errors: Vec<Diagnostic>,
 }
@@ -50,6 +60,7 @@ impl<'src> Parser<'src> {
             source_code,
             token: Token::default(),
             prev_token_end: 0,
+            depth: 0,
             is_complex: false,
             errors: Vec::new(),
         }
@@ -60,27 +71,20 @@ impl<'src> Parser<'src> {
     /// See [`ParseResult`] for more info.
     pub fn parse(mut self) -> ParseResult<'src> {
         self.bump(); // First token.
-        let program = match self.parse_program() {
-            Ok(program) => program,
-            Err(error) => {
-                self.error(error);
-                Program {
-                    span: Span::default(),
-                    source: self.source_code,
-                    body: ProgramBody::Empty,
-                }
-            }
-        };
+        let program = self.parse_program();
         ParseResult { program, errors: self.errors }
     }
 
-    fn parse_program(&mut self) -> Result<Program<'src>> {
+    fn parse_program(&mut self) -> Program<'src> {
         let span = self.start_span();
         let mut body = ProgramBody::Empty;
         while !self.at(Kind::Eof) {
-            let stmt = self.parse_statement()?;
-            if !self.parse_semi(&stmt) && self.is_complex {
-                self.error(semi_required_in_complex(self.current_token().span()));
+            let stmt = self.parse_statement_or_recover();
+            if !self.parse_semi(&stmt) && self.is_complex && !is_error(&stmt) {
+                self.error(semi_required_in_complex(
+                    self.current_token().span(),
+                    self.prev_token_end,
+                ));
             }
             match &mut body {
                 ProgramBody::Complex(stmts) => stmts.push(stmt),
@@ -97,7 +101,53 @@ impl<'src> Parser<'src> {
                 ProgramBody::Simple(_) => unreachable!(),
             }
         }
-        Ok(Program { span: self.end_span(span), source: self.source_code, body })
+        // Programs without any tokens have nothing to span.
+        let span = if self.prev_token_end == 0 { Span::default() } else { self.end_span(span) };
+        Program { span, source: self.source_code.into(), body }
+    }
+
+    /// Parses a statement in a list of statements. On a syntax error, the
+    /// rest of the statement is skipped and an [`ErrorStatement`] takes its
+    /// place, so the statements after it are still parsed.
+    fn parse_statement_or_recover(&mut self) -> Statement<'src> {
+        let span = self.start_span();
+        let depth = self.depth;
+        match self.parse_statement() {
+            Ok(stmt) => stmt,
+            Err(error) => {
+                self.error(error);
+                // A stray `}` or `)` is skipped on its own, since it does not
+                // close anything.
+                if matches!(self.current_kind(), Kind::RightBrace | Kind::RightParen)
+                    && self.current_token().start == span.start
+                {
+                    self.bump();
+                    self.depth = depth;
+                } else {
+                    self.synchronize(depth, Kind::Semi);
+                }
+                ErrorStatement { span: self.end_span(span) }.into()
+            }
+        }
+    }
+
+    /// Skips tokens up to the next `delimiter` or `;`, or up to the `}` or
+    /// `)` that closes the code the syntax error is in. `depth` is the
+    /// [depth](Self::depth) the erroneous code started at, so brackets it
+    /// opened itself are skipped as a whole.
+    fn synchronize(&mut self, depth: u32, delimiter: Kind) {
+        loop {
+            match self.current_kind() {
+                Kind::Eof => break,
+                kind if self.depth <= depth
+                    && (kind == delimiter
+                        || matches!(kind, Kind::Semi | Kind::RightBrace | Kind::RightParen)) =>
+                {
+                    break;
+                }
+                _ => self.bump(),
+            }
+        }
     }
 
     fn parse_statement(&mut self) -> Result<Statement<'src>> {
@@ -214,7 +264,7 @@ impl<'src> Parser<'src> {
             }
             Kind::This => self.parse_this_expression()?,
             Kind::UnterminatedString => {
-                return Err(unterminated_string(self.end_span(span)));
+                return Err(unterminated_string(self.current_token().span()));
             }
             _ => return Err(unexpected_token(self.current_token().span())),
         };
@@ -266,7 +316,7 @@ impl<'src> Parser<'src> {
         let raw = self.current_src();
         self.expect(Kind::Number)?;
         let value = raw.parse::<f32>().map_err(|_| invalid_number(self.end_span(span)))?;
-        Ok(NumericLiteral { span: self.end_span(span), value, raw }.into())
+        Ok(NumericLiteral { span: self.end_span(span), value, raw: raw.into() }.into())
     }
 
     fn parse_literal_boolean(&mut self) -> Result<Expression<'src>> {
@@ -285,7 +335,7 @@ impl<'src> Parser<'src> {
         let value = self.current_src();
         let value = &value[1..value.len() - 1];
         self.expect(Kind::String)?;
-        Ok(StringLiteral { span: self.end_span(span), value }.into())
+        Ok(StringLiteral { span: self.end_span(span), value: value.into() }.into())
     }
 
     #[inline(always)] // Hot path
@@ -331,12 +381,15 @@ impl<'src> Parser<'src> {
     ) -> Result<Expression<'src>> {
         let mut statements = vec![first_statement];
         loop {
-            if self.at(Kind::RightParen) {
+            if self.at(Kind::RightParen) || self.at(Kind::Eof) {
                 break;
             }
-            let stmt = self.parse_statement()?;
-            if !self.parse_semi(&stmt) {
-                self.error(semi_required_in_parenthesized(self.current_token().span()));
+            let stmt = self.parse_statement_or_recover();
+            if !self.parse_semi(&stmt) && !is_error(&stmt) {
+                self.error(semi_required_in_parenthesized(
+                    self.current_token().span(),
+                    self.prev_token_end,
+                ));
             }
             statements.push(stmt);
         }
@@ -358,10 +411,13 @@ impl<'src> Parser<'src> {
         let span = self.start_span();
         self.expect(Kind::LeftBrace)?;
         let mut statements = Vec::new();
-        while !self.at(Kind::RightBrace) {
-            let stmt = self.parse_statement()?;
-            if !self.parse_semi(&stmt) && self.is_complex {
-                self.error(semi_required_in_block_expression(self.current_token().span()));
+        while !self.at(Kind::RightBrace) && !self.at(Kind::Eof) {
+            let stmt = self.parse_statement_or_recover();
+            if !self.parse_semi(&stmt) && self.is_complex && !is_error(&stmt) {
+                self.error(semi_required_in_block_expression(
+                    self.current_token().span(),
+                    self.prev_token_end,
+                ));
             }
             statements.push(stmt)
         }
@@ -383,10 +439,10 @@ impl<'src> Parser<'src> {
 
     fn parse_unary_expression(&mut self) -> Result<Expression<'src>> {
         let span = self.start_span();
-        let operator = self.current_kind().into();
+        let kind = self.current_kind();
         self.bump();
-        let argument = self.parse_expression(0)?;
-        Ok(UnaryExpression { span: self.end_span(span), operator, argument }.into())
+        let argument = self.parse_expression(kind.prefix_binding_power())?;
+        Ok(UnaryExpression { span: self.end_span(span), operator: kind.into(), argument }.into())
     }
 
     fn parse_ternary_or_conditional_expression(
@@ -482,7 +538,7 @@ impl<'src> Parser<'src> {
                         break;
                     }
                 }
-                arguments.push(self.parse_expression(0)?);
+                arguments.push(self.parse_argument_or_recover());
             }
             self.expect(Kind::RightParen)?;
             Some(arguments)
@@ -492,6 +548,20 @@ impl<'src> Parser<'src> {
         Ok(CallExpression { span: self.end_span(span), kind, callee, arguments }.into())
     }
 
+    /// Parses a call argument. On a syntax error, the rest of the argument is
+    /// skipped and an [`ErrorExpression`] takes its place.
+    fn parse_argument_or_recover(&mut self) -> Expression<'src> {
+        let span = self.start_span();
+        let depth = self.depth;
+        self.parse_expression(0).unwrap_or_else(|error| {
+            self.error(error);
+            self.synchronize(depth, Kind::Comma);
+            // Nothing is skipped if the argument is missing, as in `q.foo(1,;`.
+            ErrorExpression { span: Span::new(span.start, self.prev_token_end.max(span.start)) }
+                .into()
+        })
+    }
+
     fn parse_this_expression(&mut self) -> Result<Expression<'src>> {
         let span = self.start_span();
         self.expect(Kind::This)?;
@@ -539,7 +609,12 @@ impl<'src> Parser<'src> {
     #[inline(always)] // Hot path
     fn bump(&mut self) {
         self.prev_token_end = self.token.end;
-        let kind = self.lexer.next().unwrap_or(Ok(Kind::Eof)).unwrap_or(Kind::UnterminatedString);
+        match self.token.kind {
+            Kind::LeftParen | Kind::LeftBrace => self.depth += 1,
+            Kind::RightParen | Kind::RightBrace => self.depth = self.depth.saturating_sub(1),
+            _ => (),
+        }
+        let kind = self.lexer.next().unwrap_or(Ok(Kind::Eof)).unwrap_or(Kind::Unknown);
         let span = self.lexer.span();
         self.token = Token { kind, start: span.start as u32, end: span.end as u32 };
     }
@@ -567,60 +642,83 @@ impl<'src> Parser<'src> {
     }
 }
 
+/// Missing `;` is not reported after a statement with a syntax error, which
+/// has been reported already.
+fn is_error(stmt: &Statement) -> bool {
+    matches!(stmt, Statement::Error(_))
+}
+
 #[cold]
 fn invalid_number(span: Span) -> Diagnostic {
-    Diagnostic::error("invalid number").with_label(span)
+    Diagnostic::error("invalid number").with_code(Code::InvalidNumber).with_label(span)
 }
 
 #[cold]
 fn unexpected_token(span: Span) -> Diagnostic {
-    Diagnostic::error("unexpected token").with_label(span)
+    Diagnostic::error("unexpected token").with_code(Code::UnexpectedToken).with_label(span)
 }
 
 #[cold]
 fn expected_token(expected: &str, found: &str, span: Span) -> Diagnostic {
-    Diagnostic::error(format!("expected `{expected}` but found `{found}`")).with_label(span)
+    Diagnostic::error(format!("expected `{expected}` but found `{found}`"))
+        .with_code(Code::ExpectedToken)
+        .with_label(span)
 }
 
 #[cold]
-fn semi_required_in_complex(span: Span) -> Diagnostic {
+fn semi_required_in_complex(span: Span, stmt_end: u32) -> Diagnostic {
     Diagnostic::error("semicolons are required for complex programs (containing `=` or `;`)")
+        .with_code(Code::SemiRequiredInComplex)
         .with_help("try inserting a semicolon here")
         .with_label(span)
+        .with_fix(insert_semi(stmt_end))
 }
 
 #[cold]
-fn semi_required_in_parenthesized(span: Span) -> Diagnostic {
+fn semi_required_in_parenthesized(span: Span, stmt_end: u32) -> Diagnostic {
     Diagnostic::error("statements inside parenthesized expressions must be delimited by `;` if the other statements also end with `;`")
+            .with_code(Code::SemiRequiredInParenthesized)
             .with_help("try inserting a semicolon here")
             .with_label(span)
+            .with_fix(insert_semi(stmt_end))
 }
 
 #[cold]
-fn semi_required_in_block_expression(span: Span) -> Diagnostic {
+fn semi_required_in_block_expression(span: Span, stmt_end: u32) -> Diagnostic {
     Diagnostic::error("statements inside block expressions must be delimited by `;`")
+        .with_code(Code::SemiRequiredInBlockExpression)
         .with_help("try inserting a semicolon here")
         .with_label(span)
+        .with_fix(insert_semi(stmt_end))
+}
+
+fn insert_semi(stmt_end: u32) -> Fix {
+    Fix::new("insert `;`", Applicability::MachineApplicable).insert(stmt_end, ";")
 }
 
 #[cold]
 fn unterminated_string(span: Span) -> Diagnostic {
-    Diagnostic::error("unterminated string").with_label(span)
+    Diagnostic::error("unterminated string").with_code(Code::UnterminatedString).with_label(span)
 }
 
 #[cold]
 fn loop_in_expression(span: Span) -> Diagnostic {
     Diagnostic::error("`loop` statement cannot be used inside expressions")
+        .with_code(Code::LoopInExpression)
         .with_help("try defining it in a statement")
         .with_label(span)
 }
 
 #[cold]
 fn illegal_update_operation(span: Span) -> Diagnostic {
-    Diagnostic::error("`++` and `--` can only be used on variables").with_label(span)
+    Diagnostic::error("`++` and `--` can only be used on variables")
+        .with_code(Code::IllegalUpdateOperation)
+        .with_label(span)
 }
 
 #[cold]
 fn invalid_for_each_first_arg(span: Span) -> Diagnostic {
-    Diagnostic::error("`for_each` statement first argument must be a variable").with_label(span)
+    Diagnostic::error("`for_each` statement first argument must be a variable")
+        .with_code(Code::InvalidForEachFirstArg)
+        .with_label(span)
 }
@@ -0,0 +1,582 @@
+use bumpalo::{Bump, vec};
+
+use super::{
+    expected_token, illegal_update_operation, invalid_for_each_first_arg, invalid_number,
+    loop_in_expression, semi_required_in_block_expression, semi_required_in_complex,
+    semi_required_in_parenthesized, unexpected_token, unterminated_string,
+};
+use crate::{
+    arena::*,
+    diagnostic::{Diagnostic, Result},
+    span::Span,
+    token::Kind,
+};
+
+/// Return value of [`Parser::parse`] which contains the AST and errors.
+///
+/// See [`crate::ParseResult`] for more info.
+#[derive(Debug)]
+pub struct ParseResult<'arena, 'src> {
+    pub program: Program<'arena, 'src>,
+    pub errors: std::vec::Vec<Diagnostic>,
+}
+
+/// Recursive Descent Parser that allocates the [arena AST](crate::arena) in
+/// `arena`.
+///
+/// It parses exactly like [`crate::Parser`], including error recovery.
+pub struct Parser<'arena, 'src> {
+    arena: &'arena Bump,
+    /// Holds the lexer, the current token and the errors.
+    inner: super::Parser<'src>,
+}
+
+impl<'arena, 'src> Parser<'arena, 'src> {
+    /// Creates a new [`Parser`] that allocates the AST in `arena`.
+    pub fn new(arena: &'arena Bump, source_code: &'src str) -> Self {
+        Self { arena, inner: super::Parser::new(source_code) }
+    }
+
+    /// Main entry point.
+    ///
+    /// See [`ParseResult`] for more info.
+    pub fn parse(mut self) -> ParseResult<'arena, 'src> {
+        self.inner.bump(); // First token.
+        let program = self.parse_program();
+        ParseResult { program, errors: self.inner.errors }
+    }
+
+    fn parse_program(&mut self) -> Program<'arena, 'src> {
+        let span = self.inner.start_span();
+        let mut body = ProgramBody::Empty;
+        while !self.inner.at(Kind::Eof) {
+            let stmt = self.parse_statement_or_recover();
+            if !self.parse_semi(&stmt) && self.inner.is_complex && !is_error(&stmt) {
+                self.inner.error(semi_required_in_complex(
+                    self.inner.current_token().span(),
+                    self.inner.prev_token_end,
+                ));
+            }
+            match &mut body {
+                ProgramBody::Complex(stmts) => stmts.push(stmt),
+                ProgramBody::Empty => {
+                    body = match stmt {
+                        Statement::Expression(expr)
+                            if !self.inner.is_complex && self.inner.at(Kind::Eof) =>
+                        {
+                            ProgramBody::Simple(*expr)
+                        }
+                        stmt => ProgramBody::Complex(vec![in self.arena; stmt]),
+                    };
+                }
+                // Simple is only set when it's the end of the program, so this
+                // is not possible to reach.
+                ProgramBody::Simple(_) => unreachable!(),
+            }
+        }
+        // Programs without any tokens have nothing to span.
+        let span = if self.inner.prev_token_end == 0 {
+            Span::default()
+        } else {
+            self.inner.end_span(span)
+        };
+        Program { span, source: self.inner.source_code, body }
+    }
+
+    /// See [`crate::Parser::parse_statement_or_recover`].
+    fn parse_statement_or_recover(&mut self) -> Statement<'arena, 'src> {
+        let span = self.inner.start_span();
+        let depth = self.inner.depth;
+        match self.parse_statement() {
+            Ok(stmt) => stmt,
+            Err(error) => {
+                self.inner.error(error);
+                if matches!(self.inner.current_kind(), Kind::RightBrace | Kind::RightParen)
+                    && self.inner.current_token().start == span.start
+                {
+                    self.inner.bump();
+                    self.inner.depth = depth;
+                } else {
+                    self.inner.synchronize(depth, Kind::Semi);
+                }
+                Statement::Error(self.alloc(ErrorStatement { span: self.inner.end_span(span) }))
+            }
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement<'arena, 'src>> {
+        let stmt = match self.inner.current_kind() {
+            Kind::Semi => self.parse_empty_statement()?,
+            v if v.is_variable() => self.parse_assignment_statement_or_expression()?,
+            Kind::Loop => self.parse_loop_statement()?,
+            Kind::ForEach => self.parse_for_each_statement()?,
+            Kind::Return => self.parse_return_statement()?,
+            Kind::Break => self.parse_break_statement()?,
+            Kind::Continue => self.parse_continue_statement()?,
+            _ => {
+                let expr = self.parse_expression(0)?;
+                Statement::Expression(self.alloc(expr))
+            }
+        };
+        Ok(stmt)
+    }
+
+    fn parse_semi(&mut self, stmt: &Statement<'arena, 'src>) -> bool {
+        if !stmt.is_empty() && self.inner.eat(Kind::Semi) {
+            self.inner.is_complex = true;
+            return true;
+        }
+        false
+    }
+
+    fn parse_assignment_statement_or_expression(&mut self) -> Result<Statement<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let left = self.parse_variable_expression()?;
+        let kind = self.inner.current_kind();
+        Ok(if kind.is_assignment_operator() {
+            let operator = kind.into();
+            self.inner.bump();
+
+            if !self.inner.is_complex {
+                self.inner.is_complex = true;
+            }
+
+            let right = self.parse_expression(0)?;
+            Statement::Assignment(self.alloc(AssignmentStatement {
+                span: self.inner.end_span(span),
+                left,
+                operator,
+                right,
+            }))
+        } else {
+            let left = Expression::Variable(self.alloc(left));
+            let expr = self.parse_expression_rest(0, left, span)?;
+            Statement::Expression(self.alloc(expr))
+        })
+    }
+
+    fn parse_loop_statement(&mut self) -> Result<Statement<'arena, 'src>> {
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::Loop)?;
+        self.inner.expect(Kind::LeftParen)?;
+        let count = self.parse_expression(0)?;
+        self.inner.expect(Kind::Comma)?;
+        let block = self.parse_block_expression()?;
+        self.inner.expect(Kind::RightParen)?;
+        Ok(Statement::Loop(self.alloc(LoopStatement {
+            span: self.inner.end_span(span),
+            count,
+            block,
+        })))
+    }
+
+    fn parse_for_each_statement(&mut self) -> Result<Statement<'arena, 'src>> {
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::ForEach)?;
+        self.inner.expect(Kind::LeftParen)?;
+        if !self.inner.current_kind().is_variable() {
+            return Err(invalid_for_each_first_arg(self.inner.current_token().span()));
+        }
+        let variable = self.parse_variable_expression()?;
+        self.inner.expect(Kind::Comma)?;
+        let array = self.parse_expression(0)?;
+        self.inner.expect(Kind::Comma)?;
+        let block = self.parse_block_expression()?;
+        self.inner.expect(Kind::RightParen)?;
+        Ok(Statement::ForEach(self.alloc(ForEachStatement {
+            span: self.inner.end_span(span),
+            variable,
+            array,
+            block,
+        })))
+    }
+
+    fn parse_return_statement(&mut self) -> Result<Statement<'arena, 'src>> {
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::Return)?;
+        let argument = self.parse_expression(0)?;
+        Ok(Statement::Return(
+            self.alloc(ReturnStatement { span: self.inner.end_span(span), argument }),
+        ))
+    }
+
+    fn parse_break_statement(&mut self) -> Result<Statement<'arena, 'src>> {
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::Break)?;
+        Ok(Statement::Break(self.alloc(BreakStatement { span: self.inner.end_span(span) })))
+    }
+
+    fn parse_continue_statement(&mut self) -> Result<Statement<'arena, 'src>> {
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::Continue)?;
+        Ok(Statement::Continue(self.alloc(ContinueStatement { span: self.inner.end_span(span) })))
+    }
+
+    fn parse_empty_statement(&mut self) -> Result<Statement<'arena, 'src>> {
+        self.inner.expect(Kind::Semi)?;
+        let span = self.inner.end_span_single(self.inner.current_token().span());
+        Ok(Statement::Empty(self.alloc(EmptyStatement { span })))
+    }
+
+    fn parse_expression(&mut self, min_bp: u8) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let left = match self.inner.current_kind() {
+            Kind::True | Kind::False => self.parse_literal_boolean()?,
+            Kind::Number => self.parse_literal_number()?,
+            Kind::String => self.parse_literal_string()?,
+            v if v.is_variable() => {
+                let variable = self.parse_variable_expression()?;
+                Expression::Variable(self.alloc(variable))
+            }
+            Kind::LeftParen => self.parse_parenthesized_expression()?,
+            Kind::LeftBrace => {
+                let block = self.parse_block_expression()?;
+                Expression::Block(self.alloc(block))
+            }
+            v if v.is_unary_operator() => self.parse_unary_expression()?,
+            Kind::Query | Kind::Math => self.parse_call_expression()?,
+            v if v.is_resource() => self.parse_resource_expression()?,
+            Kind::Array => self.parse_array_access_expression()?,
+            Kind::Loop | Kind::ForEach => {
+                return Err(loop_in_expression(self.inner.end_span_single(span)));
+            }
+            Kind::This => self.parse_this_expression()?,
+            Kind::UnterminatedString => {
+                return Err(unterminated_string(self.inner.current_token().span()));
+            }
+            _ => return Err(unexpected_token(self.inner.current_token().span())),
+        };
+        self.parse_expression_rest(min_bp, left, span)
+    }
+
+    fn parse_expression_rest(
+        &mut self,
+        min_bp: u8,
+        mut left: Expression<'arena, 'src>,
+        span: Span,
+    ) -> Result<Expression<'arena, 'src>> {
+        loop {
+            let kind = self.inner.current_kind();
+
+            if kind == Kind::Arrow {
+                left = self.parse_arrow_access_expression(span, left)?;
+                break;
+            }
+
+            let Some((lbp, rbp)) = kind.binding_power() else {
+                break;
+            };
+            if lbp < min_bp {
+                break;
+            }
+
+            match self.inner.current_kind() {
+                kind if kind.is_binary_operator() => {
+                    left = self.parse_binary_expression(span, left, rbp)?;
+                }
+                kind if kind.is_update_operator() => match left {
+                    Expression::Variable(variable) => {
+                        left = self.parse_update_expression(span, variable.clone())?;
+                    }
+                    _ => return Err(illegal_update_operation(self.inner.end_span(span))),
+                },
+                Kind::Question => {
+                    left = self.parse_ternary_or_conditional_expression(span, left)?;
+                }
+                _ => break,
+            }
+        }
+        Ok(left)
+    }
+
+    fn parse_literal_number(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let raw = self.inner.current_src();
+        self.inner.expect(Kind::Number)?;
+        let value = raw.parse::<f32>().map_err(|_| invalid_number(self.inner.end_span(span)))?;
+        let span = self.inner.end_span(span);
+        Ok(Expression::NumericLiteral(self.alloc(NumericLiteral { span, value, raw: raw.into() })))
+    }
+
+    fn parse_literal_boolean(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let value = match self.inner.current_kind() {
+            Kind::True => true,
+            Kind::False => false,
+            kind => unreachable!("Boolean Literal: {kind:?}"),
+        };
+        self.inner.bump();
+        Ok(Expression::BooleanLiteral(
+            self.alloc(BooleanLiteral { span: self.inner.end_span(span), value }),
+        ))
+    }
+
+    fn parse_literal_string(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let value = self.inner.current_src();
+        let value = &value[1..value.len() - 1];
+        self.inner.expect(Kind::String)?;
+        Ok(Expression::StringLiteral(
+            self.alloc(StringLiteral { span: self.inner.end_span(span), value: value.into() }),
+        ))
+    }
+
+    fn parse_parenthesized_expression(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::LeftParen)?;
+        let first_stmt = self.parse_statement()?;
+        if self.parse_semi(&first_stmt) {
+            self.parse_parenthesized_expression_rest(first_stmt, span)
+        } else if let Statement::Expression(expr) = first_stmt
+            && self.inner.eat(Kind::RightParen)
+        {
+            Ok(Expression::Parenthesized(self.alloc(ParenthesizedExpression {
+                span: self.inner.end_span(span),
+                body: ParenthesizedBody::Single(*expr),
+            })))
+        } else if self.inner.eat(Kind::Eof) {
+            Err(expected_token(
+                Kind::RightParen.as_str(),
+                self.inner.current_kind().as_str(),
+                Span::new(self.inner.prev_token_end, self.inner.current_token().start),
+            ))
+        } else {
+            Err(unexpected_token(self.inner.current_token().span()))
+        }
+    }
+
+    fn parse_parenthesized_expression_rest(
+        &mut self,
+        first_statement: Statement<'arena, 'src>,
+        span: Span,
+    ) -> Result<Expression<'arena, 'src>> {
+        let mut statements = vec![in self.arena; first_statement];
+        loop {
+            if self.inner.at(Kind::RightParen) || self.inner.at(Kind::Eof) {
+                break;
+            }
+            let stmt = self.parse_statement_or_recover();
+            if !self.parse_semi(&stmt) && !is_error(&stmt) {
+                self.inner.error(semi_required_in_parenthesized(
+                    self.inner.current_token().span(),
+                    self.inner.prev_token_end,
+                ));
+            }
+            statements.push(stmt);
+        }
+        self.inner.expect(Kind::RightParen)?;
+        Ok(Expression::Parenthesized(self.alloc(ParenthesizedExpression {
+            span: self.inner.end_span(span),
+            body: ParenthesizedBody::Multiple(statements),
+        })))
+    }
+
+    fn parse_block_expression(&mut self) -> Result<BlockExpression<'arena, 'src>> {
+        // See `crate::Parser::parse_block_expression`.
+        if !self.inner.is_complex {
+            self.inner.is_complex = true;
+        }
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::LeftBrace)?;
+        let mut statements = Vec::new_in(self.arena);
+        while !self.inner.at(Kind::RightBrace) && !self.inner.at(Kind::Eof) {
+            let stmt = self.parse_statement_or_recover();
+            if !self.parse_semi(&stmt) && self.inner.is_complex && !is_error(&stmt) {
+                self.inner.error(semi_required_in_block_expression(
+                    self.inner.current_token().span(),
+                    self.inner.prev_token_end,
+                ));
+            }
+            statements.push(stmt)
+        }
+        self.inner.expect(Kind::RightBrace)?;
+        Ok(BlockExpression { span: self.inner.end_span(span), statements })
+    }
+
+    fn parse_binary_expression(
+        &mut self,
+        left_span: Span,
+        left: Expression<'arena, 'src>,
+        rbp: u8,
+    ) -> Result<Expression<'arena, 'src>> {
+        let operator = self.inner.current_kind().into();
+        self.inner.bump();
+        let right = self.parse_expression(rbp)?;
+        Ok(Expression::Binary(self.alloc(BinaryExpression {
+            span: self.inner.end_span(left_span),
+            left,
+            operator,
+            right,
+        })))
+    }
+
+    fn parse_unary_expression(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let kind = self.inner.current_kind();
+        self.inner.bump();
+        let argument = self.parse_expression(kind.prefix_binding_power())?;
+        Ok(Expression::Unary(self.alloc(UnaryExpression {
+            span: self.inner.end_span(span),
+            operator: kind.into(),
+            argument,
+        })))
+    }
+
+    fn parse_ternary_or_conditional_expression(
+        &mut self,
+        test_span: Span,
+        test: Expression<'arena, 'src>,
+    ) -> Result<Expression<'arena, 'src>> {
+        self.inner.expect(Kind::Question)?;
+        let consequent = self.parse_expression(0)?;
+        if self.inner.eat(Kind::Colon) {
+            let alternate = self.parse_expression(0)?;
+            Ok(Expression::Ternary(self.alloc(TernaryExpression {
+                span: self.inner.end_span(test_span),
+                test,
+                consequent,
+                alternate,
+            })))
+        } else {
+            Ok(Expression::Conditional(self.alloc(ConditionalExpression {
+                span: self.inner.end_span(test_span),
+                test,
+                consequent,
+            })))
+        }
+    }
+
+    fn parse_variable_expression(&mut self) -> Result<VariableExpression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let lifetime: VariableLifetime = self.inner.current_kind().into();
+        self.inner.bump();
+        self.inner.expect(Kind::Dot)?;
+        let property = self.inner.parse_identifier()?;
+        let mut member = VariableMember::Property { property };
+        while self.inner.eat(Kind::Dot) {
+            let property = self.inner.parse_identifier()?;
+            member = VariableMember::Object { object: self.alloc(member), property };
+        }
+        Ok(VariableExpression { span: self.inner.end_span(span), lifetime, member })
+    }
+
+    fn parse_update_expression(
+        &mut self,
+        span: Span,
+        variable: VariableExpression<'arena, 'src>,
+    ) -> Result<Expression<'arena, 'src>> {
+        let operator = self.inner.current_kind().into();
+        self.inner.bump();
+        Ok(Expression::Update(self.alloc(UpdateExpression {
+            span: self.inner.end_span(span),
+            variable,
+            operator,
+        })))
+    }
+
+    fn parse_resource_expression(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let section: ResourceSection = self.inner.current_kind().into();
+        self.inner.bump();
+        self.inner.expect(Kind::Dot)?;
+        let name = self.inner.parse_identifier()?;
+        Ok(Expression::Resource(self.alloc(ResourceExpression {
+            span: self.inner.end_span(span),
+            section,
+            name,
+        })))
+    }
+
+    fn parse_array_access_expression(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::Array)?;
+        self.inner.expect(Kind::Dot)?;
+        let name = self.inner.parse_identifier()?;
+        self.inner.expect(Kind::LeftBracket)?;
+        let index = self.parse_expression(0)?;
+        self.inner.expect(Kind::RightBracket)?;
+        Ok(Expression::ArrayAccess(self.alloc(ArrayAccessExpression {
+            span: self.inner.end_span(span),
+            name,
+            index,
+        })))
+    }
+
+    fn parse_arrow_access_expression(
+        &mut self,
+        left_span: Span,
+        left: Expression<'arena, 'src>,
+    ) -> Result<Expression<'arena, 'src>> {
+        self.inner.expect(Kind::Arrow)?;
+        let right = self.parse_expression(0)?;
+        Ok(Expression::ArrowAccess(self.alloc(ArrowAccessExpression {
+            span: self.inner.end_span(left_span),
+            left,
+            right,
+        })))
+    }
+
+    fn parse_call_expression(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        let kind: CallKind = self.inner.current_kind().into();
+        self.inner.bump();
+        self.inner.expect(Kind::Dot)?;
+        let callee = self.inner.parse_identifier()?;
+        let arguments = if self.inner.eat(Kind::LeftParen) {
+            let mut arguments = Vec::new_in(self.arena);
+            let mut first = true;
+            loop {
+                if self.inner.at(Kind::RightParen) || self.inner.at(Kind::Eof) {
+                    break;
+                }
+                if first {
+                    first = false;
+                } else {
+                    self.inner.expect(Kind::Comma)?;
+                    if self.inner.at(Kind::RightParen) {
+                        break;
+                    }
+                }
+                arguments.push(self.parse_argument_or_recover());
+            }
+            self.inner.expect(Kind::RightParen)?;
+            Some(arguments)
+        } else {
+            None
+        };
+        Ok(Expression::Call(self.alloc(CallExpression {
+            span: self.inner.end_span(span),
+            kind,
+            callee,
+            arguments,
+        })))
+    }
+
+    /// See [`crate::Parser::parse_argument_or_recover`].
+    fn parse_argument_or_recover(&mut self) -> Expression<'arena, 'src> {
+        let span = self.inner.start_span();
+        let depth = self.inner.depth;
+        self.parse_expression(0).unwrap_or_else(|error| {
+            self.inner.error(error);
+            self.inner.synchronize(depth, Kind::Comma);
+            let end = self.inner.prev_token_end.max(span.start);
+            Expression::Error(self.alloc(ErrorExpression { span: Span::new(span.start, end) }))
+        })
+    }
+
+    fn parse_this_expression(&mut self) -> Result<Expression<'arena, 'src>> {
+        let span = self.inner.start_span();
+        self.inner.expect(Kind::This)?;
+        Ok(Expression::This(self.alloc(ThisExpression { span: self.inner.end_span(span) })))
+    }
+
+    #[inline]
+    fn alloc<T>(&self, value: T) -> &'arena T {
+        self.arena.alloc(value)
+    }
+}
+
+/// See [`crate::parser::is_error`].
+fn is_error(stmt: &Statement) -> bool {
+    matches!(stmt, Statement::Error(_))
+}
@@ -0,0 +1,593 @@
+mod rebind;
+
+use std::mem;
+
+use rebind::Rebind;
+
+use super::{
+    ParseResult, Parser, is_error, semi_required_in_block_expression, semi_required_in_complex,
+};
+use crate::{
+    ast::*,
+    diagnostic::Diagnostic,
+    span::{SPAN, Span},
+    token::{Kind, Lexer},
+    traverse::{Control, Traverse, traverse},
+};
+
+/// A change to source code: the text within `span` was replaced with
+/// `new_len` bytes of new text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The replaced text in the old source code.
+    pub span: Span,
+    pub new_len: u32,
+}
+
+impl TextEdit {
+    /// How much longer the new source code is.
+    fn delta(self) -> i64 {
+        self.new_len as i64 - (self.span.end - self.span.start) as i64
+    }
+
+    /// The end of the new text in the new source code.
+    fn new_end(self) -> u32 {
+        self.span.start + self.new_len
+    }
+
+    /// Moves an offset after the edit from the old to the new source code.
+    fn shift(self, offset: u32) -> u32 {
+        (offset as i64 + self.delta()) as u32
+    }
+}
+
+/// The statements of a list that have been reparsed.
+struct Region<'src> {
+    /// The offset parsing resumed at, which is before the edit and therefore
+    /// the same in the old and new source code.
+    resume: u32,
+    /// The old statements `start..end` are replaced by `statements`.
+    start: usize,
+    end: usize,
+    statements: Vec<Statement<'src>>,
+    /// The offset in the old source code from which on everything is reused,
+    /// or `None` if the rest of the source code was reparsed.
+    reused_from: Option<u32>,
+    /// The start of the first token that was reparsed.
+    first_token_start: u32,
+    /// The end of the last token that was reparsed.
+    last_token_end: u32,
+    /// The start of the token parsing stopped at.
+    stopped_at: u32,
+    errors: Vec<Diagnostic>,
+}
+
+impl ParseResult<'_> {
+    /// Converts the result into one that owns every name and string instead
+    /// of borrowing them from the source code.
+    ///
+    /// This allows to keep the result together with its source code, for
+    /// example to [reparse](Parser::reparse) it after the next edit.
+    pub fn into_owned(self) -> ParseResult<'static> {
+        let ParseResult { program, errors } = self;
+        // Nothing borrows from an empty source code, so everything is owned.
+        let rebind =
+            Rebind { old_source: "", source: "", edit: TextEdit { span: SPAN, new_len: 0 } };
+        let body = rebind.program_body(program.body);
+        let source = program.source.into_owned().into();
+        ParseResult { program: Program { span: program.span, source, body }, errors }
+    }
+}
+
+impl<'src> Parser<'src> {
+    /// Parses the source code after `edit` was applied to the source code
+    /// `previous` was parsed from.
+    ///
+    /// Statements before and after the edit are reused with their spans
+    /// shifted instead of being parsed again. If the edit is within a block,
+    /// only statements of that block are reparsed. The result is the same as
+    /// parsing the new source code from scratch, which is what happens when
+    /// nothing can be reused.
+    ///
+    /// `previous` doesn't have to outlive the new source code. Reused names
+    /// and strings borrow from the new source code instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use nolana::{Parser, TextEdit, span::Span};
+    /// let old = "v.a = 1; loop(10, { v.b = 2; v.c = 3; }); v.d = 4;";
+    /// let new = "v.a = 1; loop(10, { v.b = 20; v.c = 3; }); v.d = 4;";
+    /// let previous = Parser::new(old).parse();
+    /// let edit = TextEdit { span: Span::new(26, 27), new_len: 2 };
+    /// let result = Parser::new(new).reparse(previous, edit);
+    /// assert_eq!(result.program, Parser::new(new).parse().program);
+    /// ```
+    pub fn reparse(self, previous: ParseResult<'_>, edit: TextEdit) -> ParseResult<'src> {
+        let ParseResult { program, errors } = previous;
+        if !program.body.is_complex() {
+            return self.parse();
+        }
+        let (source, old_source) = (self.source_code, program.source);
+        let rebind = Rebind { old_source: &old_source, source, edit };
+        let body = rebind.program_body(program.body);
+        let mut program = Program { span: program.span, source: source.into(), body };
+        let ProgramBody::Complex(stmts) = &mut program.body else { unreachable!() };
+        let region = match reparse_block(source, &old_source, stmts, edit, 0) {
+            Some(region) => {
+                program.span.end = edit.shift(program.span.end);
+                region
+            }
+            None => {
+                let Some(mut region) = Parser::new(source).reparse_statements(
+                    &old_source,
+                    stmts,
+                    edit,
+                    0,
+                    Kind::Eof,
+                    semi_required_in_complex,
+                ) else {
+                    return self.parse();
+                };
+                // Nothing is reused, so let the parser decide whether this is
+                // still a complex program.
+                if region.start == 0 && region.reused_from.is_none() {
+                    return self.parse();
+                }
+                let start =
+                    if region.start == 0 { region.first_token_start } else { program.span.start };
+                let end = match region.reused_from {
+                    Some(_) => edit.shift(program.span.end),
+                    None => region.last_token_end,
+                };
+                program.span = Span::new(start, end);
+                region.apply(stmts, edit);
+                region
+            }
+        };
+
+        // Errors are kept in the order a full parse reports them.
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        for error in errors {
+            match (error.span(), region.reused_from) {
+                (Some(span), _) if span.start < region.resume => before.push(error),
+                (Some(span), Some(reused_from)) if span.start >= reused_from => {
+                    after.push(error.shift(edit.delta()));
+                }
+                (Some(_), _) => (),
+                (None, _) => before.push(error),
+            }
+        }
+        before.extend(region.errors);
+        before.extend(after);
+        ParseResult { program, errors: before }
+    }
+
+    /// Reparses a list of statements, starting at the first statement before
+    /// the edit that can't be reused, up to the first old statement after the
+    /// edit the parser lines up with again.
+    fn reparse_statements(
+        mut self,
+        old_source: &str,
+        old: &[Statement<'src>],
+        edit: TextEdit,
+        list_start: u32,
+        end: Kind,
+        missing_semi: fn(Span, u32) -> Diagnostic,
+    ) -> Option<Region<'src>> {
+        // Statements before the edit are reused if they are followed by a
+        // `;` which is before the edit too.
+        let mut start = 0;
+        while start + 1 < old.len()
+            && old[start + 1].span().start <= edit.span.start
+            && is_delimited(old_source, &old[start], &old[start + 1])
+        {
+            start += 1;
+        }
+        let resume = if start == 0 { list_start } else { old[start].span().start };
+        if start > 0 {
+            // The `;` after the reused statements made the program complex.
+            self.is_complex = true;
+        }
+        self.lexer.bump(resume as usize);
+        self.bump();
+        if start > 0 {
+            // The reused `;` is the token before the reparsed statements.
+            self.prev_token_end = delimiter_end(old_source, &old[start - 1], &old[start])?;
+        }
+        let first_token_start = self.current_token().start;
+
+        let mut statements: Vec<Statement<'src>> = Vec::new();
+        let mut reused = None;
+        while !self.at(end) && !self.at(Kind::Eof) {
+            if let Some(last) = statements.last()
+                && let Some(index) = self.reusable_statement(old_source, old, last, edit)
+            {
+                reused = Some(index);
+                break;
+            }
+            let stmt = self.parse_statement_or_recover();
+            if !self.parse_semi(&stmt) && self.is_complex && !is_error(&stmt) {
+                self.error(missing_semi(self.current_token().span(), self.prev_token_end));
+            }
+            statements.push(stmt);
+        }
+
+        // A block is reused from its `}` on if it is still the same `}`.
+        if reused.is_none() && end == Kind::RightBrace && !self.at(end) {
+            return None;
+        }
+        Some(Region {
+            resume,
+            start,
+            end: reused.unwrap_or(old.len()),
+            statements,
+            reused_from: reused.map(|index| old[index].span().start),
+            first_token_start,
+            last_token_end: self.prev_token_end,
+            stopped_at: self.current_token().start,
+            errors: self.errors,
+        })
+    }
+
+    /// Returns the index of the old statement the parser is at, if it starts
+    /// after the edit, and both the new statement and the old statement
+    /// before it are followed by `;`.
+    fn reusable_statement(
+        &self,
+        old_source: &str,
+        old: &[Statement],
+        last: &Statement,
+        edit: TextEdit,
+    ) -> Option<usize> {
+        // Only `parse_semi` bumps a `;` that doesn't start an empty statement.
+        let after_semi = self.prev_token_end > edit.new_end()
+            && self.source_code.as_bytes()[self.prev_token_end as usize - 1] == b';'
+            && !last.is_empty();
+        let old_start = u32::try_from(self.current_token().start as i64 - edit.delta()).ok()?;
+        if !after_semi || old_start < edit.span.end {
+            return None;
+        }
+        let index = old.binary_search_by_key(&old_start, |stmt| stmt.span().start).ok()?;
+        (index > 0 && is_delimited(old_source, &old[index - 1], &old[index])).then_some(index)
+    }
+}
+
+impl<'src> Region<'src> {
+    /// Replaces the reparsed old statements with the new ones, and shifts the
+    /// reused statements after them.
+    fn apply(&mut self, stmts: &mut Vec<Statement<'src>>, edit: TextEdit) {
+        let mut rest = stmts.split_off(self.end);
+        shift_spans(&mut rest, edit);
+        stmts.truncate(self.start);
+        stmts.append(&mut self.statements);
+        stmts.append(&mut rest);
+    }
+}
+
+/// Reparses the statements of the innermost block in `stmts` that contains
+/// the edit, and shifts the spans after it.
+fn reparse_block<'src>(
+    source: &'src str,
+    old_source: &str,
+    stmts: &mut Vec<Statement<'src>>,
+    edit: TextEdit,
+    depth: u32,
+) -> Option<Region<'src>> {
+    let index = stmts.partition_point(|stmt| stmt.span().end <= edit.span.start);
+    let (block, container_span, depth) = match stmts.get_mut(index)? {
+        Statement::Expression(expr) => match &mut **expr {
+            Expression::Block(block) => (&mut **block, None, depth + 1),
+            _ => return None,
+        },
+        Statement::Loop(stmt) => (&mut stmt.block, Some(&mut stmt.span), depth + 2),
+        Statement::ForEach(stmt) => (&mut stmt.block, Some(&mut stmt.span), depth + 2),
+        _ => return None,
+    };
+    // The braces must stay the same.
+    if edit.span.start <= block.span.start || edit.span.end >= block.span.end {
+        return None;
+    }
+    let region = match reparse_block(source, old_source, &mut block.statements, edit, depth) {
+        Some(region) => region,
+        None => {
+            let mut parser = Parser::new(source);
+            parser.is_complex = true;
+            parser.depth = depth;
+            let mut region = parser.reparse_statements(
+                old_source,
+                &block.statements,
+                edit,
+                block.span.start + 1,
+                Kind::RightBrace,
+                semi_required_in_block_expression,
+            )?;
+            let close = block.span.end - 1;
+            if region.reused_from.is_none() {
+                if region.stopped_at != edit.shift(close) {
+                    return None;
+                }
+                region.reused_from = Some(close);
+            }
+            region.apply(&mut block.statements, edit);
+            region
+        }
+    };
+    block.span.end = edit.shift(block.span.end);
+    if let Some(span) = container_span {
+        span.end = edit.shift(span.end);
+    }
+    let mut rest = stmts.split_off(index + 1);
+    shift_spans(&mut rest, edit);
+    stmts.append(&mut rest);
+    Some(region)
+}
+
+/// Whether the only token between two statements is `;`.
+fn is_delimited(source: &str, stmt: &Statement, next: &Statement) -> bool {
+    delimiter_end(source, stmt, next).is_some()
+}
+
+/// Returns the end of the `;` between two statements, if it is the only
+/// token between them.
+fn delimiter_end(source: &str, stmt: &Statement, next: &Statement) -> Option<u32> {
+    let (end, next_start) = (stmt.span().end, next.span().start);
+    if stmt.is_empty() || end > next_start {
+        return None;
+    }
+    let mut tokens = Lexer::new(&source[Span::new(end, next_start)]);
+    let semi = tokens.next().filter(|token| token.kind == Kind::Semi)?;
+    tokens.next().is_none().then_some(end + semi.span.end)
+}
+
+fn shift_spans(stmts: &mut Vec<Statement>, edit: TextEdit) {
+    let mut program =
+        Program { span: SPAN, source: "".into(), body: ProgramBody::Complex(mem::take(stmts)) };
+    traverse(&mut SpanShifter { edit }, &mut program);
+    let ProgramBody::Complex(shifted) = program.body else { unreachable!() };
+    *stmts = shifted;
+}
+
+/// Moves every span by how much longer the source code before it has become.
+struct SpanShifter {
+    edit: TextEdit,
+}
+
+impl SpanShifter {
+    fn shift(&self, span: &mut Span) {
+        *span = Span::new(self.edit.shift(span.start), self.edit.shift(span.end));
+    }
+}
+
+impl<'src> Traverse<'src> for SpanShifter {
+    fn enter_assignment_statement(&mut self, it: &mut AssignmentStatement<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_loop_statement(&mut self, it: &mut LoopStatement<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_for_each_statement(&mut self, it: &mut ForEachStatement<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_return_statement(&mut self, it: &mut ReturnStatement<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_break_statement(&mut self, it: &mut BreakStatement) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_continue_statement(&mut self, it: &mut ContinueStatement) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_empty_statement(&mut self, it: &mut EmptyStatement) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_error_statement(&mut self, it: &mut ErrorStatement) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_identifier_reference(&mut self, it: &mut Identifier<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_numeric_literal(&mut self, it: &mut NumericLiteral<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_boolean_literal(&mut self, it: &mut BooleanLiteral) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_string_literal(&mut self, it: &mut StringLiteral<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_variable_expression(&mut self, it: &mut VariableExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_parenthesized_expression(
+        &mut self,
+        it: &mut ParenthesizedExpression<'src>,
+    ) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_block_expression(&mut self, it: &mut BlockExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_binary_expression(&mut self, it: &mut BinaryExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_unary_expression(&mut self, it: &mut UnaryExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_update_expression(&mut self, it: &mut UpdateExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_ternary_expression(&mut self, it: &mut TernaryExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_conditional_expression(&mut self, it: &mut ConditionalExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_resource_expression(&mut self, it: &mut ResourceExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_array_access_expression(&mut self, it: &mut ArrayAccessExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_arrow_access_expression(&mut self, it: &mut ArrowAccessExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_call_expression(&mut self, it: &mut CallExpression<'src>) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_this_expression(&mut self, it: &mut ThisExpression) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+
+    fn enter_error_expression(&mut self, it: &mut ErrorExpression) -> Control {
+        self.shift(&mut it.span);
+        Control::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+
+    const SOURCES: &[&str] = &[
+        "v.a = 1; v.b = q.foo(v.a, 'x'); loop(10, { v.c = v.c + 1; v.d = { t.e = 1; }; }); return v.b;",
+        "t.x = 0; for_each(t.e, q.entities, { t.x = t.x + 1; break; }); v.y = t.x > 1 ? 2 : 3;;",
+        "// Comment\nv.a = 1; /* block */ { v.b = 2; ; v.c = (v.a; v.b;); }; v.d = q.bar(1, +); ) v.e = 1;",
+        "v.a = 1 + ; loop(2, { v.b = ; }); v.c = 'unterminated",
+        "math.sin(v.a)",
+    ];
+
+    const INSERTIONS: &[&str] = &[
+        "",
+        "1",
+        ";",
+        " ",
+        "}",
+        "{",
+        ")",
+        "(",
+        "v.z = ",
+        "'",
+        "/*",
+        "*/",
+        "//",
+        "\n",
+        "q.f(",
+        "loop(1, {",
+        "+ 2;",
+    ];
+
+    fn assert_reparse(old: &str, edit: TextEdit, new: &str) {
+        let previous = Parser::new(old).parse();
+        let result = Parser::new(new).reparse(previous, edit);
+        let expected = Parser::new(new).parse();
+        assert_eq!(result.program, expected.program, "{old:?} -> {new:?}");
+        assert_eq!(result.errors, expected.errors, "{old:?} -> {new:?}");
+    }
+
+    #[test]
+    fn test_reparse_matches_parse() {
+        let mut seed = 1u64;
+        let mut random = |bound: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        for old in SOURCES {
+            for _ in 0..2000 {
+                let start = random(old.len() + 1);
+                let end = (start + random(4)).min(old.len());
+                let text = INSERTIONS[random(INSERTIONS.len())];
+                let new = format!("{}{text}{}", &old[..start], &old[end..]);
+                let edit = TextEdit {
+                    span: Span::new(start as u32, end as u32),
+                    new_len: text.len() as u32,
+                };
+                assert_reparse(old, edit, &new);
+            }
+        }
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let source = String::from("v.a = 'x'; q.b(1, t.c);");
+        let result = Parser::new(&source).parse().into_owned();
+        drop(source);
+        assert_eq!(result.program, Parser::new("v.a = 'x'; q.b(1, t.c);").parse().program);
+    }
+
+    #[test]
+    fn test_reuses_statements() {
+        let old = String::from("v.a = 1; loop(10, { v.b = 2; v.c = 3; }); v.d = 'x';");
+        let new = "v.a = 1; loop(10, { v.b = 20; v.c = 3; }); v.d = 'x';";
+        let previous = Parser::new(&old).parse();
+        let edit = TextEdit { span: Span::new(26, 27), new_len: 2 };
+        let result = Parser::new(new).reparse(previous, edit);
+        // The old source code can go away while the result is still in use.
+        drop(old);
+        assert_eq!(result.program, Parser::new(new).parse().program);
+        let ProgramBody::Complex(stmts) = &result.program.body else { unreachable!() };
+        let borrows_new = |text: &Cow<str>| matches!(text, Cow::Borrowed(text) if new.as_bytes().as_ptr_range().contains(&text.as_ptr()));
+        for stmt in [&stmts[0], &stmts[2]] {
+            let Statement::Assignment(stmt) = stmt else { unreachable!() };
+            let VariableMember::Property { property } = &stmt.left.member else { unreachable!() };
+            assert!(borrows_new(&property.name));
+            match &stmt.right {
+                Expression::NumericLiteral(expr) => assert!(borrows_new(&expr.raw)),
+                Expression::StringLiteral(expr) => assert!(borrows_new(&expr.value)),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+
+use super::TextEdit;
+use crate::ast::*;
+
+/// Moves an AST from the old source code to the new one.
+///
+/// Names and strings that are not touched by the edit borrow the same text
+/// from the new source code. Everything else, such as text within the edit
+/// or text that never borrowed from the old source code, is owned. Spans are
+/// kept as they are.
+pub(super) struct Rebind<'old, 'new> {
+    pub old_source: &'old str,
+    pub source: &'new str,
+    pub edit: TextEdit,
+}
+
+impl<'new> Rebind<'_, 'new> {
+    fn text(&self, text: Cow<'_, str>) -> Cow<'new, str> {
+        match text {
+            Cow::Borrowed(text) => match self.locate(text) {
+                Some((start, end)) => Cow::Borrowed(&self.source[start..end]),
+                None => Cow::Owned(text.to_owned()),
+            },
+            Cow::Owned(text) => Cow::Owned(text),
+        }
+    }
+
+    /// Returns where `text`, which may borrow from the old source code, is
+    /// in the new source code.
+    fn locate(&self, text: &str) -> Option<(usize, usize)> {
+        let start = (text.as_ptr() as usize).checked_sub(self.old_source.as_ptr() as usize)?;
+        let end = start + text.len();
+        if end > self.old_source.len() {
+            return None;
+        }
+        if end <= self.edit.span.start as usize {
+            Some((start, end))
+        } else if start >= self.edit.span.end as usize {
+            let shift = |offset: usize| self.edit.shift(offset as u32) as usize;
+            Some((shift(start), shift(end)))
+        } else {
+            None
+        }
+    }
+
+    pub fn program_body(&self, it: ProgramBody<'_>) -> ProgramBody<'new> {
+        match it {
+            ProgramBody::Simple(expr) => ProgramBody::Simple(self.expression(expr)),
+            ProgramBody::Complex(stmts) => ProgramBody::Complex(self.statements(stmts)),
+            ProgramBody::Empty => ProgramBody::Empty,
+        }
+    }
+
+    fn statements(&self, it: Vec<Statement<'_>>) -> Vec<Statement<'new>> {
+        it.into_iter().map(|stmt| self.statement(stmt)).collect()
+    }
+
+    fn statement(&self, it: Statement<'_>) -> Statement<'new> {
+        match it {
+            Statement::Expression(it) => Statement::Expression(Box::new(self.expression(*it))),
+            Statement::Assignment(it) => {
+                let AssignmentStatement { span, left, operator, right } = *it;
+                let left = self.variable_expression(left);
+                let right = self.expression(right);
+                AssignmentStatement { span, left, operator, right }.into()
+            }
+            Statement::Loop(it) => {
+                let LoopStatement { span, count, block } = *it;
+                let count = self.expression(count);
+                LoopStatement { span, count, block: self.block_expression(block) }.into()
+            }
+            Statement::ForEach(it) => {
+                let ForEachStatement { span, variable, array, block } = *it;
+                let variable = self.variable_expression(variable);
+                let array = self.expression(array);
+                let block = self.block_expression(block);
+                ForEachStatement { span, variable, array, block }.into()
+            }
+            Statement::Return(it) => {
+                ReturnStatement { span: it.span, argument: self.expression(it.argument) }.into()
+            }
+            Statement::Break(it) => Statement::Break(it),
+            Statement::Continue(it) => Statement::Continue(it),
+            Statement::Empty(it) => Statement::Empty(it),
+            Statement::Error(it) => Statement::Error(it),
+        }
+    }
+
+    fn expression(&self, it: Expression<'_>) -> Expression<'new> {
+        match it {
+            Expression::NumericLiteral(it) => {
+                let NumericLiteral { span, value, raw } = *it;
+                NumericLiteral { span, value, raw: self.text(raw) }.into()
+            }
+            Expression::BooleanLiteral(it) => Expression::BooleanLiteral(it),
+            Expression::StringLiteral(it) => {
+                StringLiteral { span: it.span, value: self.text(it.value) }.into()
+            }
+            Expression::Variable(it) => self.variable_expression(*it).into(),
+            Expression::Parenthesized(it) => {
+                let body = match it.body {
+                    ParenthesizedBody::Single(expr) => {
+                        ParenthesizedBody::Single(self.expression(expr))
+                    }
+                    ParenthesizedBody::Multiple(stmts) => {
+                        ParenthesizedBody::Multiple(self.statements(stmts))
+                    }
+                };
+                ParenthesizedExpression { span: it.span, body }.into()
+            }
+            Expression::Block(it) => self.block_expression(*it).into(),
+            Expression::Binary(it) => {
+                let BinaryExpression { span, left, operator, right } = *it;
+                let left = self.expression(left);
+                let right = self.expression(right);
+                BinaryExpression { span, left, operator, right }.into()
+            }
+            Expression::Unary(it) => {
+                let UnaryExpression { span, operator, argument } = *it;
+                UnaryExpression { span, operator, argument: self.expression(argument) }.into()
+            }
+            Expression::Update(it) => {
+                let UpdateExpression { span, variable, operator } = *it;
+                let variable = self.variable_expression(variable);
+                Expression::Update(Box::new(UpdateExpression { span, variable, operator }))
+            }
+            Expression::Ternary(it) => {
+                let TernaryExpression { span, test, consequent, alternate } = *it;
+                let test = self.expression(test);
+                let consequent = self.expression(consequent);
+                let alternate = self.expression(alternate);
+                TernaryExpression { span, test, consequent, alternate }.into()
+            }
+            Expression::Conditional(it) => {
+                let ConditionalExpression { span, test, consequent } = *it;
+                let test = self.expression(test);
+                let consequent = self.expression(consequent);
+                ConditionalExpression { span, test, consequent }.into()
+            }
+            Expression::Resource(it) => {
+                let ResourceExpression { span, section, name } = *it;
+                ResourceExpression { span, section, name: self.identifier(name) }.into()
+            }
+            Expression::ArrayAccess(it) => {
+                let ArrayAccessExpression { span, name, index } = *it;
+                let name = self.identifier(name);
+                ArrayAccessExpression { span, name, index: self.expression(index) }.into()
+            }
+            Expression::ArrowAccess(it) => {
+                let ArrowAccessExpression { span, left, right } = *it;
+                let left = self.expression(left);
+                let right = self.expression(right);
+                ArrowAccessExpression { span, left, right }.into()
+            }
+            Expression::Call(it) => {
+                let CallExpression { span, kind, callee, arguments } = *it;
+                let callee = self.identifier(callee);
+                let arguments = arguments.map(|arguments| {
+                    arguments.into_iter().map(|arg| self.expression(arg)).collect()
+                });
+                CallExpression { span, kind, callee, arguments }.into()
+            }
+            Expression::This(it) => Expression::This(it),
+            Expression::Error(it) => Expression::Error(it),
+        }
+    }
+
+    fn identifier(&self, it: Identifier<'_>) -> Identifier<'new> {
+        Identifier { span: it.span, name: self.text(it.name) }
+    }
+
+    fn variable_expression(&self, it: VariableExpression<'_>) -> VariableExpression<'new> {
+        let VariableExpression { span, lifetime, member } = it;
+        VariableExpression { span, lifetime, member: self.variable_member(member) }
+    }
+
+    fn variable_member(&self, it: VariableMember<'_>) -> VariableMember<'new> {
+        match it {
+            VariableMember::Object { object, property } => VariableMember::Object {
+                object: Box::new(self.variable_member(*object)),
+                property: self.identifier(property),
+            },
+            VariableMember::Property { property } => {
+                VariableMember::Property { property: self.identifier(property) }
+            }
+        }
+    }
+
+    fn block_expression(&self, it: BlockExpression<'_>) -> BlockExpression<'new> {
+        BlockExpression { span: it.span, statements: self.statements(it.statements) }
+    }
+}
@@ -18,7 +18,7 @@ use crate::ast::*;
 ///         let Expression::Binary(bin_expr) = expr else { unreachable!() };
 ///         BinaryExpression {
 ///             span: SPAN,
-///             left: NumericLiteral { span: SPAN, value: 32.0, raw: "32" }.into(),
+///             left: NumericLiteral { span: SPAN, value: 32.0, raw: "32".into() }.into(),
 ///             operator: BinaryOperator::LessThan,
 ///             right: (*bin_expr).into(),
 ///         }
@@ -61,6 +61,7 @@ impl_replace_with!(
     BreakStatement,
     ContinueStatement,
     EmptyStatement,
+    ErrorStatement,
     NumericLiteral<'_>,
     BooleanLiteral,
     StringLiteral<'_>,
@@ -78,4 +79,5 @@ impl_replace_with!(
     ArrowAccessExpression<'_>,
     CallExpression<'_>,
     ThisExpression,
+    ErrorExpression,
 );
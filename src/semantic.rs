@@ -1,8 +1,11 @@
 use crate::{
+    Codegen,
     ast::*,
-    diagnostic::Diagnostic,
-    span::Span,
-    traverse::{Traverse, traverse},
+    diagnostic::{Applicability, Code, Diagnostic, Fix},
+    eval::math,
+    span::{SPAN, Span},
+    traverse::Control,
+    visit::{Visit, visit},
 };
 
 /// Traverses an AST and checks the Molang program for any semantic errors.
@@ -14,100 +17,142 @@ pub struct SemanticChecker {
 }
 
 impl SemanticChecker {
-    pub fn check(mut self, program: &mut Program) -> Vec<Diagnostic> {
-        traverse(&mut self, program);
+    pub fn check(mut self, program: &Program) -> Vec<Diagnostic> {
+        visit(&mut self, program);
         self.errors
     }
 }
 
-impl<'a> Traverse<'a> for SemanticChecker {
-    fn enter_loop_statement(&mut self, _: &mut LoopStatement<'a>) {
+impl<'a> Visit<'a> for SemanticChecker {
+    fn enter_loop_statement(&mut self, _: &LoopStatement<'a>) -> Control {
         self.loop_depth += 1;
+        Control::Continue
     }
 
-    fn exit_loop_statement(&mut self, _: &mut LoopStatement<'a>) {
+    fn exit_loop_statement(&mut self, _: &LoopStatement<'a>) {
         self.loop_depth -= 1;
     }
 
-    fn enter_for_each_statement(&mut self, it: &mut ForEachStatement<'a>) {
+    fn enter_for_each_statement(&mut self, it: &ForEachStatement<'a>) -> Control {
         self.loop_depth += 1;
         if it.variable.lifetime == VariableLifetime::Context {
-            self.errors.push(for_each_wrong_first_arg(it.variable.span));
+            self.errors.push(for_each_wrong_first_arg(&it.variable));
         }
+        Control::Continue
     }
 
-    fn exit_for_each_statement(&mut self, _: &mut ForEachStatement<'a>) {
+    fn exit_for_each_statement(&mut self, _: &ForEachStatement<'a>) {
         self.loop_depth -= 1;
     }
 
-    fn enter_block_expression(&mut self, it: &mut BlockExpression<'a>) {
+    fn enter_block_expression(&mut self, it: &BlockExpression<'a>) -> Control {
         if it.statements.is_empty() {
             self.errors.push(empty_block(it.span));
         }
+        Control::Continue
     }
 
-    fn enter_binary_expression(&mut self, it: &mut BinaryExpression<'a>) {
+    fn enter_binary_expression(&mut self, it: &BinaryExpression<'a>) -> Control {
         use BinaryOperator::*;
         use Expression::*;
-        match (&it.left, it.operator, &it.right) {
-            (StringLiteral(_), op, StringLiteral(_)) if !matches!(op, Equality | Inequality) => (),
-            (left, _, StringLiteral(_)) if !matches!(left, StringLiteral(_)) => (),
-            (StringLiteral(_), _, right) if !matches!(right, StringLiteral(_)) => (),
-            _ => return,
+        let is_illegal = match (&it.left, it.operator, &it.right) {
+            (StringLiteral(_), op, StringLiteral(_)) => !matches!(op, Equality | Inequality),
+            (left, _, StringLiteral(_)) => !matches!(left, StringLiteral(_)),
+            (StringLiteral(_), _, right) => !matches!(right, StringLiteral(_)),
+            _ => false,
+        };
+        if is_illegal {
+            self.errors.push(illegal_string_binary(it.span));
         }
-        self.errors.push(illegal_string_binary(it.span));
+        Control::Continue
     }
 
-    fn enter_assignment_statement(&mut self, it: &mut AssignmentStatement<'a>) {
+    fn enter_assignment_statement(&mut self, it: &AssignmentStatement<'a>) -> Control {
         if it.left.lifetime == VariableLifetime::Context {
-            self.errors.push(context_readonly(it.span))
+            self.errors.push(context_readonly(it.span, &it.left))
         }
+        Control::Continue
     }
 
-    fn enter_break_statement(&mut self, it: &mut BreakStatement) {
+    fn enter_break_statement(&mut self, it: &BreakStatement) -> Control {
         if self.loop_depth == 0 {
             self.errors.push(break_outside_loop(it.span));
         }
+        Control::Continue
     }
 
-    fn enter_continue_statement(&mut self, it: &mut ContinueStatement) {
+    fn enter_continue_statement(&mut self, it: &ContinueStatement) -> Control {
         if self.loop_depth == 0 {
             self.errors.push(continue_outside_loop(it.span));
         }
+        Control::Continue
     }
 
-    fn enter_update_expression(&mut self, it: &mut UpdateExpression<'a>) {
+    fn enter_call_expression(&mut self, it: &CallExpression<'a>) -> Control {
+        if it.kind == CallKind::Math {
+            let argument_count = it.arguments.as_ref().map_or(0, Vec::len);
+            if let Err(error) = math::resolve(&it.callee.name, argument_count) {
+                self.errors.push(error.with_label(it.span));
+            }
+        }
+        Control::Continue
+    }
+
+    fn enter_update_expression(&mut self, it: &UpdateExpression<'a>) -> Control {
         if it.variable.lifetime == VariableLifetime::Context {
-            self.errors.push(context_readonly(it.span))
+            self.errors.push(context_readonly(it.span, &it.variable))
         }
+        Control::Continue
     }
 }
 
 fn empty_block(span: Span) -> Diagnostic {
-    Diagnostic::error("block statement must contain at least one statement").with_label(span)
+    Diagnostic::error("block statement must contain at least one statement")
+        .with_code(Code::EmptyBlock)
+        .with_label(span)
 }
 
 fn illegal_string_binary(span: Span) -> Diagnostic {
-    Diagnostic::error("strings only support `==` and `!=` operators").with_label(span)
+    Diagnostic::error("strings only support `==` and `!=` operators")
+        .with_code(Code::IllegalStringBinary)
+        .with_label(span)
 }
 
 fn break_outside_loop(span: Span) -> Diagnostic {
     Diagnostic::error("`break` is only supported inside `loop` and `for_each` statements")
+        .with_code(Code::BreakOutsideLoop)
         .with_label(span)
 }
 
 fn continue_outside_loop(span: Span) -> Diagnostic {
     Diagnostic::error("`continue` is only supported inside `loop` and `for_each` statements")
+        .with_code(Code::ContinueOutsideLoop)
         .with_label(span)
 }
 
-fn context_readonly(span: Span) -> Diagnostic {
+fn context_readonly(span: Span, variable: &VariableExpression) -> Diagnostic {
     Diagnostic::error("`context.*` variables are read-only")
+        .with_code(Code::ContextReadonly)
         .with_help("try using `variable.*` or `temp.*` instead")
         .with_label(span)
+        .with_fix(use_variable_lifetime(variable))
 }
 
-fn for_each_wrong_first_arg(span: Span) -> Diagnostic {
+fn for_each_wrong_first_arg(variable: &VariableExpression) -> Diagnostic {
     Diagnostic::error("`for_each` first argument must be either `variable.*` or `temp.*`")
-        .with_label(span)
+        .with_code(Code::ForEachWrongFirstArg)
+        .with_label(variable.span)
+        .with_fix(use_variable_lifetime(variable))
+}
+
+/// Replaces a `context.*` variable with the `variable.*` of the same name.
+/// This may be incorrect since `temp.*` may have been intended instead.
+fn use_variable_lifetime(variable: &VariableExpression) -> Fix {
+    let span = variable.span;
+    let variable = VariableExpression { lifetime: VariableLifetime::Variable, ..variable.clone() };
+    let program =
+        Program { span: SPAN, source: "".into(), body: ProgramBody::Simple(variable.into()) };
+    let replacement = Codegen::default().build(&program);
+    Fix::new(format!("use `{replacement}`"), Applicability::MaybeIncorrect)
+        .replace(span, replacement)
 }
@@ -5,6 +5,7 @@ use miette::{LabeledSpan, SourceOffset, SourceSpan};
 pub const SPAN: Span = Span::new(0, 0);
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub start: u32,
     pub end: u32,
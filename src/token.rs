@@ -1,9 +1,28 @@
+//! Tokens of Molang source code.
+//!
+//! [`Lexer`] splits source code into the same tokens the
+//! [`Parser`][`crate::Parser`] sees, which is useful for syntax highlighting
+//! and quick scans that don't need an AST.
+//!
+//! # Example
+//!
+//! ```
+//! # use nolana::token::{Kind, Lexer};
+//! let kinds: Vec<_> = Lexer::new("v.x = 1; // one").map(|token| token.kind).collect();
+//! assert_eq!(kinds, [Kind::Variable, Kind::Dot, Kind::Identifier, Kind::Eq, Kind::Number, Kind::Semi]);
+//!
+//! let lexer = Lexer::new("v.x = 1; // one").with_trivia(true);
+//! let texts: Vec<_> = lexer.map(|token| token.text).collect();
+//! assert_eq!(texts, ["v", ".", "x", " ", "=", " ", "1", ";", " ", "// one"]);
+//! ```
+
 use logos::Logos;
 
-use crate::span::Span;
+use crate::{cst::lex_trivia, span::Span};
 
+/// The token the parser is currently at.
 #[derive(Debug, Default, Clone, Copy)]
-pub struct Token {
+pub(crate) struct Token {
     pub kind: Kind,
     pub start: u32,
     pub end: u32,
@@ -15,7 +34,75 @@ impl Token {
     }
 }
 
+/// A token yielded by [`Lexer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceToken<'src> {
+    pub kind: Kind,
+    pub span: Span,
+    /// The source code of the token.
+    pub text: &'src str,
+}
+
+/// Iterator over the tokens of Molang source code.
+///
+/// Whitespace and comments are skipped, unless [`with_trivia`] is set, in
+/// which case the tokens cover the whole source code. Characters that don't
+/// start any token are yielded as [`Kind::Unknown`].
+///
+/// [`with_trivia`]: Lexer::with_trivia
+pub struct Lexer<'src> {
+    lexer: logos::Lexer<'src, Kind>,
+    with_trivia: bool,
+    /// Tokens that have been lexed but not yielded yet, in reverse order.
+    pending: Vec<SourceToken<'src>>,
+    prev_end: usize,
+}
+
+impl<'src> Lexer<'src> {
+    pub fn new(source: &'src str) -> Self {
+        Self { lexer: Kind::lexer(source), with_trivia: false, pending: Vec::new(), prev_end: 0 }
+    }
+
+    /// Also yields [`Kind::Whitespace`], [`Kind::LineComment`] and
+    /// [`Kind::BlockComment`] tokens.
+    pub fn with_trivia(mut self, yes: bool) -> Self {
+        self.with_trivia = yes;
+        self
+    }
+}
+
+impl<'src> Iterator for Lexer<'src> {
+    type Item = SourceToken<'src>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.pending.pop() {
+            return Some(token);
+        }
+        let source = self.lexer.source();
+        let (kind, span) = match self.lexer.next() {
+            Some(kind) => (Some(kind.unwrap_or(Kind::Unknown)), self.lexer.span()),
+            None => (None, source.len()..source.len()),
+        };
+        if let Some(kind) = kind {
+            let span = Span::new(span.start as u32, span.end as u32);
+            self.pending.push(SourceToken { kind, span, text: &source[span] });
+        }
+        if self.with_trivia {
+            let trivia = lex_trivia(source, self.prev_end, span.start).into_iter().rev();
+            self.pending.extend(trivia.map(|trivia| SourceToken {
+                kind: trivia.kind.into(),
+                span: trivia.span,
+                text: trivia.text,
+            }));
+        }
+        self.prev_end = span.end;
+        self.pending.pop()
+    }
+}
+
+/// The kind of a token.
 #[derive(Debug, PartialEq, Clone, Copy, Default, Logos)]
+#[non_exhaustive]
 #[logos(skip "[ \t\n\r]+")]
 #[logos(skip "//.*")]
 #[logos(skip r"/\*[^*]*\*+([^/*][^*]*\*+)*/")]
@@ -240,6 +327,18 @@ pub enum Kind {
 
     #[token("return")]
     Return,
+
+    /// A character that doesn't start any token, such as `@`.
+    Unknown,
+
+    /// Only yielded by [`Lexer::with_trivia`].
+    Whitespace,
+
+    /// `// foo`, only yielded by [`Lexer::with_trivia`].
+    LineComment,
+
+    /// `/* foo */`, only yielded by [`Lexer::with_trivia`].
+    BlockComment,
 }
 
 impl Kind {
@@ -309,8 +408,22 @@ impl Kind {
         matches!(self, Kind::Geometry | Kind::Material | Kind::Texture)
     }
 
+    pub fn is_trivia(self) -> bool {
+        matches!(self, Kind::Whitespace | Kind::LineComment | Kind::BlockComment)
+    }
+
+    /// The right binding power of a unary operator. Unary operators bind
+    /// tighter than every binary operator.
+    pub(crate) fn prefix_binding_power(self) -> u8 {
+        match self {
+            Self::Tilde => 30,
+            Self::Bang | Self::Minus => 28,
+            _ => unreachable!("Unary Operator: {self:?}"),
+        }
+    }
+
     /// <https://bedrock.dev/docs/stable/Molang#Operator%20Precedence>
-    pub fn binding_power(self) -> Option<(u8, u8)> {
+    pub(crate) fn binding_power(self) -> Option<(u8, u8)> {
         Some(match self {
             Self::Plus2 | Self::Minus2 => (99, 0),
             Self::Tilde => (29, 30),
@@ -406,6 +519,10 @@ impl Kind {
             Kind::ForEach => "for_each",
             Kind::Loop => "loop",
             Kind::Return => "return",
+            Kind::Unknown => "unknown token",
+            Kind::Whitespace => "whitespace",
+            Kind::LineComment => "line comment",
+            Kind::BlockComment => "block comment",
         }
     }
 }
@@ -582,4 +699,33 @@ mod tests {
     fn test_whitespace() {
         assert_lexer("\t\r\n", &[]);
     }
+
+    fn assert_trivia_lexer(source: &str, expected: &[(Kind, &str)]) {
+        let tokens: Vec<_> = Lexer::new(source)
+            .with_trivia(true)
+            .map(|token| {
+                assert_eq!(&source[token.span], token.text);
+                (token.kind, token.text)
+            })
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_trivia() {
+        assert_trivia_lexer(
+            " /* a */1// b\n@",
+            &[
+                (Kind::Whitespace, " "),
+                (Kind::BlockComment, "/* a */"),
+                (Kind::Number, "1"),
+                (Kind::LineComment, "// b"),
+                (Kind::Whitespace, "\n"),
+                (Kind::Unknown, "@"),
+            ],
+        );
+        assert_trivia_lexer("/*/ x */1", &[(Kind::BlockComment, "/*/ x */"), (Kind::Number, "1")]);
+        assert_trivia_lexer("  ", &[(Kind::Whitespace, "  ")]);
+        assert_trivia_lexer("", &[]);
+    }
 }
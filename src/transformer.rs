@@ -1,20 +1,26 @@
+use std::mem;
+
 use crate::{
     ast::*,
-    replace_with::ReplaceWith,
+    fold::{Fold, fold, walk_expression, walk_program, walk_statement, walk_statements},
     span::SPAN,
-    traverse::{Traverse, traverse},
+    traverse::Control,
+    visit::{Visit, visit},
 };
 
 #[derive(Default)]
 pub struct MolangTransformer<'src> {
     scopes: Vec<Scope<'src>>,
-    program_body_transformer: ProgramBodyTransformer,
+    /// Whether a simple program is converted into a complex one, because some
+    /// of its expressions expand into statements.
+    needs_complex: bool,
 }
 
 impl<'src> MolangTransformer<'src> {
     pub fn transform(&mut self, program: &mut Program<'src>) {
-        traverse(&mut self.program_body_transformer, program);
-        traverse(self, program);
+        let source = mem::take(&mut program.source);
+        let body = mem::replace(&mut program.body, ProgramBody::Empty);
+        *program = fold(self, Program { span: program.span, source, body });
     }
 
     fn enter_scope(&mut self) {
@@ -29,149 +35,103 @@ impl<'src> MolangTransformer<'src> {
         self.scopes.last_mut().unwrap()
     }
 
-    fn transform_binary_expression(&mut self, expr: &mut Expression<'src>) {
-        if let Expression::Binary(bin_expr) = expr
-            && bin_expr.operator.is_custom()
-        {
-            let scope = self.scope();
-            expr.replace_with(|expr| {
-                let Expression::Binary(bin_expr) = expr else { unreachable!() };
-                let BinaryExpression { left, operator, right, .. } = *bin_expr;
-                match operator {
-                    BinaryOperator::Remainder => math_mod_expression(left, right),
-                    BinaryOperator::Exponential => math_pow_expression(left, right),
-                    BinaryOperator::ShiftLeft => shift_left_expression(left, right),
-                    BinaryOperator::ShiftRight => shift_right_expression(left, right),
-                    BinaryOperator::BitwiseOr
-                    | BinaryOperator::BitwiseAnd
-                    | BinaryOperator::BitwiseXor => {
-                        let index = scope.index();
-                        let bitwise_op = match operator {
-                            BinaryOperator::BitwiseOr => BitwiseOperation::Or { left, right },
-                            BinaryOperator::BitwiseAnd => BitwiseOperation::And { left, right },
-                            BinaryOperator::BitwiseXor => BitwiseOperation::Xor { left, right },
-                            _ => unreachable!("Bitwise Operation: {operator:?}"),
-                        };
-                        let (or_stmt, or_var_expr) = bitwise_operation_statement(bitwise_op, index);
-                        scope.new_statements.push((index, or_stmt));
-                        or_var_expr
-                    }
-                    _ => unreachable!(),
-                }
-            });
-        }
+    /// Adds the statements computing a bitwise operation before the current
+    /// statement and returns the variable holding the result.
+    fn bitwise_operation(&mut self, operation: BitwiseOperation<'src>) -> Expression<'src> {
+        let scope = self.scope();
+        let index = scope.index();
+        let (stmt, var_expr) = bitwise_operation_statement(operation, index);
+        scope.new_statements.push((index, stmt));
+        var_expr
     }
 
-    fn transform_assignment_statement(&mut self, stmt: &mut Statement<'src>) {
-        if let Statement::Assignment(assign_stmt) = stmt
-            && assign_stmt.operator.is_custom()
-        {
-            let mut left = assign_stmt.left.clone().into();
-            if !assign_stmt.left.is_struct() {
-                left = binary_expression(
-                    assign_stmt.left.clone().into(),
-                    BinaryOperator::Coalesce,
-                    NumericLiteral { span: SPAN, value: 0.0, raw: "0" }.into(),
-                );
+    fn transform_binary_expression(&mut self, it: BinaryExpression<'src>) -> Expression<'src> {
+        let BinaryExpression { span, left, operator, right } = it;
+        match operator {
+            BinaryOperator::Remainder => math_mod_expression(left, right),
+            BinaryOperator::Exponential => math_pow_expression(left, right),
+            BinaryOperator::ShiftLeft => shift_left_expression(left, right),
+            BinaryOperator::ShiftRight => shift_right_expression(left, right),
+            BinaryOperator::BitwiseOr => {
+                self.bitwise_operation(BitwiseOperation::Or { left, right })
             }
-
-            let operator = assign_stmt.operator;
-            assign_stmt.operator = AssignmentOperator::Assign;
-
-            let scope = self.scope();
-            match operator {
-                AssignmentOperator::Addition
-                | AssignmentOperator::Subtraction
-                | AssignmentOperator::Multiplication
-                | AssignmentOperator::Division => assign_stmt
-                    .right
-                    .replace_with(|right| binary_expression(left, operator.into(), right)),
-                AssignmentOperator::Exponential => {
-                    assign_stmt.right.replace_with(|right| math_pow_expression(left, right))
-                }
-                AssignmentOperator::Remainder => {
-                    assign_stmt.right.replace_with(|right| math_mod_expression(left, right))
-                }
-                AssignmentOperator::LogicalOr => stmt.replace_with(|stmt| {
-                    let Statement::Assignment(assign_stmt) = stmt else { unreachable!() };
-                    logical_or_assignment_statement(*assign_stmt)
-                }),
-                AssignmentOperator::LogicalAnd => stmt.replace_with(|stmt| {
-                    let Statement::Assignment(assign_stmt) = stmt else { unreachable!() };
-                    logical_and_assignment_statement(*assign_stmt)
-                }),
-                AssignmentOperator::ShiftLeft => {
-                    assign_stmt.right.replace_with(|right| shift_left_expression(left, right))
-                }
-                AssignmentOperator::ShiftRight => {
-                    assign_stmt.right.replace_with(|right| shift_right_expression(left, right))
-                }
-                AssignmentOperator::BitwiseOr
-                | AssignmentOperator::BitwiseAnd
-                | AssignmentOperator::BitwiseXor => {
-                    let index = scope.index();
-                    assign_stmt.right.replace_with(|right| {
-                        let bitwise_op = match operator {
-                            AssignmentOperator::BitwiseOr => BitwiseOperation::Or { left, right },
-                            AssignmentOperator::BitwiseAnd => BitwiseOperation::And { left, right },
-                            AssignmentOperator::BitwiseXor => BitwiseOperation::Xor { left, right },
-                            _ => unreachable!("Bitwise Operation: {operator:?}"),
-                        };
-                        let (or_stmt, or_var_expr) = bitwise_operation_statement(bitwise_op, index);
-                        scope.new_statements.push((index, or_stmt));
-                        or_var_expr
-                    })
-                }
-                AssignmentOperator::Assign => unreachable!(),
+            BinaryOperator::BitwiseAnd => {
+                self.bitwise_operation(BitwiseOperation::And { left, right })
+            }
+            BinaryOperator::BitwiseXor => {
+                self.bitwise_operation(BitwiseOperation::Xor { left, right })
             }
+            _ => BinaryExpression { span, left, operator, right }.into(),
         }
     }
 
-    fn transform_unary_expression(&mut self, expr: &mut Expression<'src>) {
-        if let Expression::Unary(unary_expr) = expr
-            && unary_expr.operator == UnaryOperator::BitwiseNot
-        {
-            let scope = self.scope();
-            let index = scope.index();
-            expr.replace_with(|expr| {
-                let Expression::Unary(unary_expr) = expr else { unreachable!() };
-                let (not_stmt, not_var_expr) = bitwise_operation_statement(
-                    BitwiseOperation::Not { right: unary_expr.argument },
-                    index,
-                );
-                scope.new_statements.push((index, not_stmt));
-                not_var_expr
-            });
+    fn transform_assignment_statement(&mut self, it: AssignmentStatement<'src>) -> Statement<'src> {
+        let AssignmentStatement { span, left: variable, operator, right } = it;
+        let mut left = variable.clone().into();
+        if !variable.is_struct() {
+            left = binary_expression(
+                variable.clone().into(),
+                BinaryOperator::Coalesce,
+                NumericLiteral { span: SPAN, value: 0.0, raw: "0".into() }.into(),
+            );
         }
-    }
 
-    fn transform_update_expression(&mut self, expr: &mut Expression<'src>) {
-        let Expression::Update(update_expr) = expr else { return };
+        let right = match operator {
+            AssignmentOperator::Addition
+            | AssignmentOperator::Subtraction
+            | AssignmentOperator::Multiplication
+            | AssignmentOperator::Division => binary_expression(left, operator.into(), right),
+            AssignmentOperator::Exponential => math_pow_expression(left, right),
+            AssignmentOperator::Remainder => math_mod_expression(left, right),
+            AssignmentOperator::ShiftLeft => shift_left_expression(left, right),
+            AssignmentOperator::ShiftRight => shift_right_expression(left, right),
+            AssignmentOperator::BitwiseOr => {
+                self.bitwise_operation(BitwiseOperation::Or { left, right })
+            }
+            AssignmentOperator::BitwiseAnd => {
+                self.bitwise_operation(BitwiseOperation::And { left, right })
+            }
+            AssignmentOperator::BitwiseXor => {
+                self.bitwise_operation(BitwiseOperation::Xor { left, right })
+            }
+            AssignmentOperator::LogicalOr
+            | AssignmentOperator::LogicalAnd
+            | AssignmentOperator::Assign => right,
+        };
+        let assign_stmt = AssignmentStatement {
+            span,
+            left: variable,
+            operator: AssignmentOperator::Assign,
+            right,
+        };
+        match operator {
+            AssignmentOperator::LogicalOr => logical_or_assignment_statement(assign_stmt),
+            AssignmentOperator::LogicalAnd => logical_and_assignment_statement(assign_stmt),
+            _ => assign_stmt.into(),
+        }
+    }
 
+    fn transform_update_expression(&mut self, it: UpdateExpression<'src>) -> Expression<'src> {
         let scope = self.scope();
         let update_stmt = AssignmentStatement {
             span: SPAN,
-            left: update_expr.variable.clone(),
+            left: it.variable.clone(),
             operator: AssignmentOperator::Assign,
             right: BinaryExpression {
                 span: SPAN,
-                left: update_expr.variable.clone().into(),
-                operator: update_expr.operator.into(),
-                right: NumericLiteral { span: SPAN, value: 1.0, raw: "1" }.into(),
+                left: it.variable.clone().into(),
+                operator: it.operator.into(),
+                right: NumericLiteral { span: SPAN, value: 1.0, raw: "1".into() }.into(),
             }
             .into(),
         }
         .into();
         scope.new_statements.push((scope.index(), update_stmt));
-
-        expr.replace_with(|expr| {
-            let Expression::Update(update_expr) = expr else { unreachable!() };
-            update_expr.variable.into()
-        });
+        it.variable.into()
     }
 
     fn optimize_statements(&mut self, stmts: &mut Vec<Statement<'src>>) {
-        if self.program_body_transformer.needs_complex {
+        if self.needs_complex {
             return;
         }
         for stmt in stmts {
@@ -183,94 +143,110 @@ impl<'src> MolangTransformer<'src> {
         }
     }
 
+    /// The last statement of a simple program that was converted into a
+    /// complex one is its value, so it has to be returned.
     fn add_return_statement(&mut self, program: &mut Program<'src>) {
-        if self.program_body_transformer.needs_complex
+        if self.needs_complex
             && let ProgramBody::Complex(stmts) = &mut program.body
         {
-            stmts.last_mut().expect("must have at least two statements").replace_with(|stmt| {
-                let Statement::Expression(expr) = stmt else {
-                    unreachable!(
-                        "simple to complex transition implies the last statement is an expression"
-                    );
-                };
-                ReturnStatement { span: SPAN, argument: *expr }.into()
-            });
+            match stmts.pop() {
+                Some(Statement::Expression(expr)) => {
+                    stmts.push(ReturnStatement { span: SPAN, argument: *expr }.into())
+                }
+                Some(stmt) => stmts.push(stmt),
+                None => (),
+            }
         }
     }
 }
 
-impl<'src> Traverse<'src> for MolangTransformer<'src> {
-    fn exit_program(&mut self, it: &mut Program<'src>) {
-        self.add_return_statement(it);
-    }
+impl<'src> Fold<'src> for MolangTransformer<'src> {
+    fn fold_program(&mut self, it: Program<'src>) -> Program<'src> {
+        self.needs_complex = it.body.is_simple() && ExpandingExpressionFinder::find(&it);
 
-    fn enter_statements(&mut self, _: &mut Vec<Statement<'src>>) {
-        self.enter_scope();
+        let body = match it.body {
+            ProgramBody::Simple(expr) if self.needs_complex => {
+                ProgramBody::Complex(vec![Statement::Expression(expr.into())])
+            }
+            body => body,
+        };
+        let mut program = walk_program(self, Program { body, ..it });
+        self.add_return_statement(&mut program);
+        program
     }
 
-    fn exit_statements(&mut self, it: &mut Vec<Statement<'src>>) {
+    fn fold_statements(&mut self, it: Vec<Statement<'src>>) -> Vec<Statement<'src>> {
+        self.enter_scope();
+        let mut stmts = walk_statements(self, it);
         let scope = self.exit_scope();
         for (index, stmt) in scope.new_statements {
-            it.insert(index, stmt);
+            stmts.insert(index, stmt);
         }
-        self.optimize_statements(it);
+        self.optimize_statements(&mut stmts);
+        stmts
     }
 
-    fn enter_statement(&mut self, it: &mut Statement<'src>) {
+    fn fold_statement(&mut self, it: Statement<'src>) -> Statement<'src> {
         self.scope().statement_count += 1;
 
-        self.transform_assignment_statement(it);
+        let stmt = match it {
+            Statement::Assignment(assign_stmt) if assign_stmt.operator.is_custom() => {
+                self.transform_assignment_statement(*assign_stmt)
+            }
+            stmt => stmt,
+        };
+        walk_statement(self, stmt)
     }
 
-    fn enter_expression(&mut self, it: &mut Expression<'src>) {
-        self.transform_unary_expression(it);
-        self.transform_update_expression(it);
-        self.transform_binary_expression(it);
+    fn fold_expression(&mut self, it: Expression<'src>) -> Expression<'src> {
+        let expr = match it {
+            Expression::Unary(unary_expr) if unary_expr.operator == UnaryOperator::BitwiseNot => {
+                self.bitwise_operation(BitwiseOperation::Not { right: unary_expr.argument })
+            }
+            Expression::Update(update_expr) => self.transform_update_expression(*update_expr),
+            Expression::Binary(bin_expr) if bin_expr.operator.is_custom() => {
+                self.transform_binary_expression(*bin_expr)
+            }
+            expr => expr,
+        };
+        walk_expression(self, expr)
     }
 }
 
 /// Some expressions will expand into entire statements. When this happens in a
 /// [`ProgramBody::Simple`], we must convert it into a [`ProgramBody::Complex`].
 #[derive(Default)]
-struct ProgramBodyTransformer {
-    is_simple: bool,
-    needs_complex: bool,
+struct ExpandingExpressionFinder {
+    found: bool,
 }
 
-impl<'src> Traverse<'src> for ProgramBodyTransformer {
-    fn enter_program(&mut self, it: &mut Program<'src>) {
-        self.is_simple = it.body.is_simple();
+impl ExpandingExpressionFinder {
+    fn find(program: &Program) -> bool {
+        let mut finder = Self::default();
+        visit(&mut finder, program);
+        finder.found
     }
 
-    fn exit_program(&mut self, it: &mut Program<'src>) {
-        if self.needs_complex && self.is_simple {
-            it.body.replace_with(|body| {
-                let ProgramBody::Simple(expr) = body else { unreachable!() };
-                ProgramBody::Complex(vec![Statement::Expression(expr.into())])
-            });
-        }
+    fn found_if(&mut self, found: bool) -> Control {
+        self.found |= found;
+        if found { Control::Stop } else { Control::Continue }
     }
+}
 
-    fn enter_binary_expression(&mut self, it: &mut BinaryExpression<'src>) {
-        if matches!(
+impl<'src> Visit<'src> for ExpandingExpressionFinder {
+    fn enter_binary_expression(&mut self, it: &BinaryExpression<'src>) -> Control {
+        self.found_if(matches!(
             it.operator,
             BinaryOperator::BitwiseOr | BinaryOperator::BitwiseAnd | BinaryOperator::BitwiseXor
-        ) && self.is_simple
-        {
-            self.needs_complex = true
-        }
+        ))
     }
 
-    fn enter_unary_expression(&mut self, it: &mut UnaryExpression<'src>) {
-        if matches!(it.operator, UnaryOperator::BitwiseNot) && self.is_simple {
-            self.needs_complex = true
-        }
+    fn enter_unary_expression(&mut self, it: &UnaryExpression<'src>) -> Control {
+        self.found_if(it.operator == UnaryOperator::BitwiseNot)
     }
 
-    fn enter_update_expression(&mut self, _: &mut UpdateExpression<'src>) {
-        if self.is_simple {
-            self.needs_complex = true;
-        }
+    fn enter_update_expression(&mut self, _: &UpdateExpression<'src>) -> Control {
+        self.found_if(true)
     }
 }
 
@@ -310,7 +286,7 @@ fn shift_left_expression<'src>(
         left,
         operator: BinaryOperator::Multiplication,
         right: math_pow_expression(
-            NumericLiteral { span: SPAN, value: 2.0, raw: "2" }.into(),
+            NumericLiteral { span: SPAN, value: 2.0, raw: "2".into() }.into(),
             right,
         ),
     }
@@ -329,7 +305,7 @@ fn shift_right_expression<'src>(
             left,
             operator: BinaryOperator::Division,
             right: math_pow_expression(
-                NumericLiteral { span: SPAN, value: 2.0, raw: "2" }.into(),
+                NumericLiteral { span: SPAN, value: 2.0, raw: "2".into() }.into(),
                 right,
             ),
         }
@@ -366,9 +342,9 @@ fn bitwise_operation_statement<'src>(
     let bit_var = variable_expression(format!("__{index}_bit"));
     let left_bit_var = variable_expression(format!("__{index}_left_bit"));
     let right_bit_var = variable_expression(format!("__{index}_right_bit"));
-    let num_0_expr: Expression = NumericLiteral { span: SPAN, value: 0.0, raw: "0" }.into();
-    let num_1_expr: Expression = NumericLiteral { span: SPAN, value: 2.0, raw: "1" }.into();
-    let num_2_expr: Expression = NumericLiteral { span: SPAN, value: 2.0, raw: "2" }.into();
+    let num_0_expr: Expression = NumericLiteral { span: SPAN, value: 0.0, raw: "0".into() }.into();
+    let num_1_expr: Expression = NumericLiteral { span: SPAN, value: 1.0, raw: "1".into() }.into();
+    let num_2_expr: Expression = NumericLiteral { span: SPAN, value: 2.0, raw: "2".into() }.into();
     let extract_bit_expr = |input_var: Expression<'src>, bit_var: Expression<'src>| {
         math_mod_expression(
             math_floor_expression(binary_expression(
@@ -455,7 +431,7 @@ fn bitwise_operation_statement<'src>(
         assignment_statement(bit_var, num_0_expr),
         LoopStatement {
             span: SPAN,
-            count: NumericLiteral { span: SPAN, value: 24.0, raw: "24" }.into(),
+            count: NumericLiteral { span: SPAN, value: 24.0, raw: "24".into() }.into(),
             block: BlockExpression { span: SPAN, statements: loop_statements },
         }
         .into(),
@@ -1,453 +1,850 @@
+use std::ops::ControlFlow::{self, Break, Continue};
+
 use crate::ast::*;
 
 /// Traverses the AST using an implementer of [`Traverse`].
 pub fn traverse<'src>(traverser: &mut impl Traverse<'src>, program: &mut Program<'src>) {
-    walk_program(traverser, program);
+    if let Some(ancestry) = traverser.ancestry() {
+        ancestry.clear();
+    }
+    let _ = walk_program(traverser, program);
+}
+
+/// Returned by `enter_*` hooks to decide how the traversal continues.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Walks the children of the node.
+    #[default]
+    Continue,
+    /// Doesn't walk the children of the node. Its `exit_*` hook is still
+    /// called.
+    SkipChildren,
+    /// Ends the traversal without calling any more hooks, including the
+    /// `exit_*` hooks of the node and its ancestors.
+    Stop,
+}
+
+/// The kind of a node with children, as tracked by [`Ancestry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    Program,
+    AssignmentStatement,
+    LoopStatement,
+    ForEachStatement,
+    ReturnStatement,
+    VariableExpression,
+    VariableMember,
+    ParenthesizedExpression,
+    BlockExpression,
+    BinaryExpression,
+    UnaryExpression,
+    UpdateExpression,
+    TernaryExpression,
+    ConditionalExpression,
+    ResourceExpression,
+    ArrayAccessExpression,
+    ArrowAccessExpression,
+    CallExpression,
+}
+
+/// The parent chain of the node being traversed.
+///
+/// Implementers opt in by returning their ancestry from
+/// [`Traverse::ancestry`]. The `enter_*` and `exit_*` hooks of a node see its
+/// ancestors, but not the node itself. The [`Statement`] and [`Expression`]
+/// enums and lists of statements are not included, so the parent of the `1`
+/// in `v.a ? 1 : 2` is a [`NodeKind::TernaryExpression`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Ancestry {
+    kinds: Vec<NodeKind>,
+}
+
+impl Ancestry {
+    /// Returns the kind of the parent node, or `None` for the program.
+    pub fn parent(&self) -> Option<NodeKind> {
+        self.kinds.last().copied()
+    }
+
+    /// Returns the kinds of the ancestors, starting with the parent.
+    pub fn iter(&self) -> impl Iterator<Item = NodeKind> + '_ {
+        self.kinds.iter().rev().copied()
+    }
+
+    /// Returns whether any ancestor is of the given kind.
+    pub fn contains(&self, kind: NodeKind) -> bool {
+        self.kinds.contains(&kind)
+    }
+
+    /// Returns the number of ancestors.
+    pub fn depth(&self) -> usize {
+        self.kinds.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.kinds.clear();
+    }
+
+    pub(crate) fn push(&mut self, kind: NodeKind) {
+        self.kinds.push(kind);
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.kinds.pop();
+    }
 }
 
 #[expect(unused_variables)]
 pub trait Traverse<'src>: Sized {
+    /// Returns the ancestry to keep up to date while traversing, if the
+    /// implementer needs to know the parents of nodes.
+    #[inline]
+    fn ancestry(&mut self) -> Option<&mut Ancestry> {
+        None
+    }
+
     #[inline]
-    fn enter_program(&mut self, it: &mut Program<'src>) {}
+    fn enter_program(&mut self, it: &mut Program<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_program(&mut self, it: &mut Program<'src>) {}
 
     #[inline]
-    fn enter_statements(&mut self, it: &mut Vec<Statement<'src>>) {}
+    fn enter_statements(&mut self, it: &mut Vec<Statement<'src>>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_statements(&mut self, it: &mut Vec<Statement<'src>>) {}
 
     #[inline]
-    fn enter_statement(&mut self, it: &mut Statement<'src>) {}
+    fn enter_statement(&mut self, it: &mut Statement<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_statement(&mut self, it: &mut Statement<'src>) {}
 
     #[inline]
-    fn enter_assignment_statement(&mut self, it: &mut AssignmentStatement<'src>) {}
+    fn enter_assignment_statement(&mut self, it: &mut AssignmentStatement<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_assignment_statement(&mut self, it: &mut AssignmentStatement<'src>) {}
 
     #[inline]
-    fn enter_loop_statement(&mut self, it: &mut LoopStatement<'src>) {}
+    fn enter_loop_statement(&mut self, it: &mut LoopStatement<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_loop_statement(&mut self, it: &mut LoopStatement<'src>) {}
 
     #[inline]
-    fn enter_for_each_statement(&mut self, it: &mut ForEachStatement<'src>) {}
+    fn enter_for_each_statement(&mut self, it: &mut ForEachStatement<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_for_each_statement(&mut self, it: &mut ForEachStatement<'src>) {}
 
     #[inline]
-    fn enter_return_statement(&mut self, it: &mut ReturnStatement<'src>) {}
+    fn enter_return_statement(&mut self, it: &mut ReturnStatement<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_return_statement(&mut self, it: &mut ReturnStatement<'src>) {}
 
     #[inline]
-    fn enter_break_statement(&mut self, it: &mut BreakStatement) {}
+    fn enter_break_statement(&mut self, it: &mut BreakStatement) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_break_statement(&mut self, it: &mut BreakStatement) {}
 
     #[inline]
-    fn enter_continue_statement(&mut self, it: &mut ContinueStatement) {}
+    fn enter_continue_statement(&mut self, it: &mut ContinueStatement) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_continue_statement(&mut self, it: &mut ContinueStatement) {}
 
     #[inline]
-    fn enter_empty_statement(&mut self, it: &mut EmptyStatement) {}
+    fn enter_empty_statement(&mut self, it: &mut EmptyStatement) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_empty_statement(&mut self, it: &mut EmptyStatement) {}
 
     #[inline]
-    fn enter_expression(&mut self, it: &mut Expression<'src>) {}
+    fn enter_error_statement(&mut self, it: &mut ErrorStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_error_statement(&mut self, it: &mut ErrorStatement) {}
+
+    #[inline]
+    fn enter_expression(&mut self, it: &mut Expression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_expression(&mut self, it: &mut Expression<'src>) {}
 
     #[inline]
-    fn enter_identifier_reference(&mut self, it: &mut Identifier<'src>) {}
+    fn enter_identifier_reference(&mut self, it: &mut Identifier<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_identifier_reference(&mut self, it: &mut Identifier<'src>) {}
 
     #[inline]
-    fn enter_numeric_literal(&mut self, it: &mut NumericLiteral<'src>) {}
+    fn enter_numeric_literal(&mut self, it: &mut NumericLiteral<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_numeric_literal(&mut self, it: &mut NumericLiteral<'src>) {}
 
     #[inline]
-    fn enter_boolean_literal(&mut self, it: &mut BooleanLiteral) {}
+    fn enter_boolean_literal(&mut self, it: &mut BooleanLiteral) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_boolean_literal(&mut self, it: &mut BooleanLiteral) {}
 
     #[inline]
-    fn enter_string_literal(&mut self, it: &mut StringLiteral<'src>) {}
+    fn enter_string_literal(&mut self, it: &mut StringLiteral<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_string_literal(&mut self, it: &mut StringLiteral<'src>) {}
 
     #[inline]
-    fn enter_variable_expression(&mut self, it: &mut VariableExpression<'src>) {}
+    fn enter_variable_expression(&mut self, it: &mut VariableExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_variable_expression(&mut self, it: &mut VariableExpression<'src>) {}
 
     #[inline]
-    fn enter_variable_member(&mut self, it: &mut VariableMember<'src>) {}
+    fn enter_variable_member(&mut self, it: &mut VariableMember<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_variable_member(&mut self, it: &mut VariableMember<'src>) {}
 
     #[inline]
-    fn enter_parenthesized_expression(&mut self, it: &mut ParenthesizedExpression<'src>) {}
+    fn enter_parenthesized_expression(
+        &mut self,
+        it: &mut ParenthesizedExpression<'src>,
+    ) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_parenthesized_expression(&mut self, it: &mut ParenthesizedExpression<'src>) {}
 
     #[inline]
-    fn enter_block_expression(&mut self, it: &mut BlockExpression<'src>) {}
+    fn enter_block_expression(&mut self, it: &mut BlockExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_block_expression(&mut self, it: &mut BlockExpression<'src>) {}
 
     #[inline]
-    fn enter_binary_expression(&mut self, it: &mut BinaryExpression<'src>) {}
+    fn enter_binary_expression(&mut self, it: &mut BinaryExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_binary_expression(&mut self, it: &mut BinaryExpression<'src>) {}
 
     #[inline]
-    fn enter_unary_expression(&mut self, it: &mut UnaryExpression<'src>) {}
+    fn enter_unary_expression(&mut self, it: &mut UnaryExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_unary_expression(&mut self, it: &mut UnaryExpression<'src>) {}
 
     #[inline]
-    fn enter_update_expression(&mut self, it: &mut UpdateExpression<'src>) {}
+    fn enter_update_expression(&mut self, it: &mut UpdateExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_update_expression(&mut self, it: &mut UpdateExpression<'src>) {}
 
     #[inline]
-    fn enter_ternary_expression(&mut self, it: &mut TernaryExpression<'src>) {}
+    fn enter_ternary_expression(&mut self, it: &mut TernaryExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_ternary_expression(&mut self, it: &mut TernaryExpression<'src>) {}
 
     #[inline]
-    fn enter_conditional_expression(&mut self, it: &mut ConditionalExpression<'src>) {}
+    fn enter_conditional_expression(&mut self, it: &mut ConditionalExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_conditional_expression(&mut self, it: &mut ConditionalExpression<'src>) {}
 
     #[inline]
-    fn enter_resource_expression(&mut self, it: &mut ResourceExpression<'src>) {}
+    fn enter_resource_expression(&mut self, it: &mut ResourceExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_resource_expression(&mut self, it: &mut ResourceExpression<'src>) {}
 
     #[inline]
-    fn enter_array_access_expression(&mut self, it: &mut ArrayAccessExpression<'src>) {}
+    fn enter_array_access_expression(&mut self, it: &mut ArrayAccessExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_array_access_expression(&mut self, it: &mut ArrayAccessExpression<'src>) {}
 
     #[inline]
-    fn enter_arrow_access_expression(&mut self, it: &mut ArrowAccessExpression<'src>) {}
+    fn enter_arrow_access_expression(&mut self, it: &mut ArrowAccessExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_arrow_access_expression(&mut self, it: &mut ArrowAccessExpression<'src>) {}
 
     #[inline]
-    fn enter_call_expression(&mut self, it: &mut CallExpression<'src>) {}
+    fn enter_call_expression(&mut self, it: &mut CallExpression<'src>) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_call_expression(&mut self, it: &mut CallExpression<'src>) {}
 
     #[inline]
-    fn enter_this_expression(&mut self, it: &mut ThisExpression) {}
+    fn enter_this_expression(&mut self, it: &mut ThisExpression) -> Control {
+        Control::Continue
+    }
 
     #[inline]
     fn exit_this_expression(&mut self, it: &mut ThisExpression) {}
+
+    #[inline]
+    fn enter_error_expression(&mut self, it: &mut ErrorExpression) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_error_expression(&mut self, it: &mut ErrorExpression) {}
 }
 
-fn walk_program<'src>(traverser: &mut impl Traverse<'src>, it: &mut Program<'src>) {
-    traverser.enter_program(it);
-    match &mut it.body {
-        ProgramBody::Simple(expr) => walk_expression(traverser, expr),
-        ProgramBody::Complex(stmts) => walk_statements(traverser, stmts),
-        ProgramBody::Empty => (),
+fn walk_program<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut Program<'src>,
+) -> ControlFlow<()> {
+    if enter(traverser.enter_program(it))? {
+        push(traverser, NodeKind::Program);
+        match &mut it.body {
+            ProgramBody::Simple(expr) => walk_expression(traverser, expr)?,
+            ProgramBody::Complex(stmts) => walk_statements(traverser, stmts)?,
+            ProgramBody::Empty => (),
+        }
+        pop(traverser);
     }
     traverser.exit_program(it);
+    Continue(())
 }
 
-fn walk_statements<'src>(traverser: &mut impl Traverse<'src>, it: &mut Vec<Statement<'src>>) {
-    traverser.enter_statements(it);
-    for stmt in it.iter_mut() {
-        walk_statement(traverser, stmt);
+fn walk_statements<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut Vec<Statement<'src>>,
+) -> ControlFlow<()> {
+    if enter(traverser.enter_statements(it))? {
+        for stmt in it.iter_mut() {
+            walk_statement(traverser, stmt)?;
+        }
     }
     traverser.exit_statements(it);
+    Continue(())
 }
 
-fn walk_statement<'src>(traverser: &mut impl Traverse<'src>, it: &mut Statement<'src>) {
-    traverser.enter_statement(it);
-    match it {
-        Statement::Expression(it) => walk_expression(traverser, it),
-        Statement::Assignment(it) => walk_assignment_statement(traverser, it),
-        Statement::Loop(it) => walk_loop_statement(traverser, it),
-        Statement::ForEach(it) => walk_for_each_statement(traverser, it),
-        Statement::Return(it) => walk_return_statement(traverser, it),
-        Statement::Break(it) => walk_break_statement(traverser, it),
-        Statement::Continue(it) => walk_continue_statement(traverser, it),
-        Statement::Empty(it) => walk_empty_statement(traverser, it),
+fn walk_statement<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut Statement<'src>,
+) -> ControlFlow<()> {
+    if enter(traverser.enter_statement(it))? {
+        match it {
+            Statement::Expression(it) => walk_expression(traverser, it)?,
+            Statement::Assignment(it) => walk_assignment_statement(traverser, it)?,
+            Statement::Loop(it) => walk_loop_statement(traverser, it)?,
+            Statement::ForEach(it) => walk_for_each_statement(traverser, it)?,
+            Statement::Return(it) => walk_return_statement(traverser, it)?,
+            Statement::Break(it) => walk_break_statement(traverser, it)?,
+            Statement::Continue(it) => walk_continue_statement(traverser, it)?,
+            Statement::Empty(it) => walk_empty_statement(traverser, it)?,
+            Statement::Error(it) => walk_error_statement(traverser, it)?,
+        }
     }
     traverser.exit_statement(it);
+    Continue(())
 }
 
 fn walk_assignment_statement<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut AssignmentStatement<'src>,
-) {
-    traverser.enter_assignment_statement(it);
-    walk_variable_expression(traverser, &mut it.left);
-    walk_expression(traverser, &mut it.right);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_assignment_statement(it))? {
+        push(traverser, NodeKind::AssignmentStatement);
+        walk_variable_expression(traverser, &mut it.left)?;
+        walk_expression(traverser, &mut it.right)?;
+        pop(traverser);
+    }
     traverser.exit_assignment_statement(it);
+    Continue(())
 }
 
-fn walk_loop_statement<'src>(traverser: &mut impl Traverse<'src>, it: &mut LoopStatement<'src>) {
-    traverser.enter_loop_statement(it);
-    walk_expression(traverser, &mut it.count);
-    walk_block_expression(traverser, &mut it.block);
+fn walk_loop_statement<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut LoopStatement<'src>,
+) -> ControlFlow<()> {
+    if enter(traverser.enter_loop_statement(it))? {
+        push(traverser, NodeKind::LoopStatement);
+        walk_expression(traverser, &mut it.count)?;
+        walk_block_expression(traverser, &mut it.block)?;
+        pop(traverser);
+    }
     traverser.exit_loop_statement(it);
+    Continue(())
 }
 
 fn walk_for_each_statement<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut ForEachStatement<'src>,
-) {
-    traverser.enter_for_each_statement(it);
-    walk_variable_expression(traverser, &mut it.variable);
-    walk_expression(traverser, &mut it.array);
-    walk_block_expression(traverser, &mut it.block);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_for_each_statement(it))? {
+        push(traverser, NodeKind::ForEachStatement);
+        walk_variable_expression(traverser, &mut it.variable)?;
+        walk_expression(traverser, &mut it.array)?;
+        walk_block_expression(traverser, &mut it.block)?;
+        pop(traverser);
+    }
     traverser.exit_for_each_statement(it);
+    Continue(())
 }
 
 fn walk_return_statement<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut ReturnStatement<'src>,
-) {
-    traverser.enter_return_statement(it);
-    walk_expression(traverser, &mut it.argument);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_return_statement(it))? {
+        push(traverser, NodeKind::ReturnStatement);
+        walk_expression(traverser, &mut it.argument)?;
+        pop(traverser);
+    }
     traverser.exit_return_statement(it);
+    Continue(())
 }
 
-fn walk_break_statement<'src>(traverser: &mut impl Traverse<'src>, it: &mut BreakStatement) {
-    traverser.enter_break_statement(it);
+fn walk_break_statement<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut BreakStatement,
+) -> ControlFlow<()> {
+    enter(traverser.enter_break_statement(it))?;
     traverser.exit_break_statement(it);
+    Continue(())
 }
 
-fn walk_continue_statement<'src>(traverser: &mut impl Traverse<'src>, it: &mut ContinueStatement) {
-    traverser.enter_continue_statement(it);
+fn walk_continue_statement<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut ContinueStatement,
+) -> ControlFlow<()> {
+    enter(traverser.enter_continue_statement(it))?;
     traverser.exit_continue_statement(it);
+    Continue(())
 }
 
-fn walk_empty_statement<'src>(traverser: &mut impl Traverse<'src>, it: &mut EmptyStatement) {
-    traverser.enter_empty_statement(it);
+fn walk_empty_statement<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut EmptyStatement,
+) -> ControlFlow<()> {
+    enter(traverser.enter_empty_statement(it))?;
     traverser.exit_empty_statement(it);
+    Continue(())
+}
+
+fn walk_error_statement<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut ErrorStatement,
+) -> ControlFlow<()> {
+    enter(traverser.enter_error_statement(it))?;
+    traverser.exit_error_statement(it);
+    Continue(())
 }
 
-fn walk_expression<'src>(traverser: &mut impl Traverse<'src>, it: &mut Expression<'src>) {
-    traverser.enter_expression(it);
-    match it {
-        Expression::NumericLiteral(it) => walk_numeric_literal(traverser, it),
-        Expression::BooleanLiteral(it) => walk_boolean_literal(traverser, it),
-        Expression::StringLiteral(it) => walk_string_literal(traverser, it),
-        Expression::Variable(it) => walk_variable_expression(traverser, it),
-        Expression::Parenthesized(it) => walk_parenthesized_expression(traverser, it),
-        Expression::Block(it) => walk_block_expression(traverser, it),
-        Expression::Binary(it) => walk_binary_expression(traverser, it),
-        Expression::Unary(it) => walk_unary_expression(traverser, it),
-        Expression::Update(it) => walk_update_expression(traverser, it),
-        Expression::Ternary(it) => walk_ternary_expression(traverser, it),
-        Expression::Conditional(it) => walk_conditional_expression(traverser, it),
-        Expression::Resource(it) => walk_resource_expression(traverser, it),
-        Expression::ArrayAccess(it) => walk_array_access_expression(traverser, it),
-        Expression::ArrowAccess(it) => walk_arrow_access_expression(traverser, it),
-        Expression::Call(it) => walk_call_expression(traverser, it),
-        Expression::This(it) => walk_this_expression(traverser, it),
+fn walk_expression<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut Expression<'src>,
+) -> ControlFlow<()> {
+    if enter(traverser.enter_expression(it))? {
+        match it {
+            Expression::NumericLiteral(it) => walk_numeric_literal(traverser, it)?,
+            Expression::BooleanLiteral(it) => walk_boolean_literal(traverser, it)?,
+            Expression::StringLiteral(it) => walk_string_literal(traverser, it)?,
+            Expression::Variable(it) => walk_variable_expression(traverser, it)?,
+            Expression::Parenthesized(it) => walk_parenthesized_expression(traverser, it)?,
+            Expression::Block(it) => walk_block_expression(traverser, it)?,
+            Expression::Binary(it) => walk_binary_expression(traverser, it)?,
+            Expression::Unary(it) => walk_unary_expression(traverser, it)?,
+            Expression::Update(it) => walk_update_expression(traverser, it)?,
+            Expression::Ternary(it) => walk_ternary_expression(traverser, it)?,
+            Expression::Conditional(it) => walk_conditional_expression(traverser, it)?,
+            Expression::Resource(it) => walk_resource_expression(traverser, it)?,
+            Expression::ArrayAccess(it) => walk_array_access_expression(traverser, it)?,
+            Expression::ArrowAccess(it) => walk_arrow_access_expression(traverser, it)?,
+            Expression::Call(it) => walk_call_expression(traverser, it)?,
+            Expression::This(it) => walk_this_expression(traverser, it)?,
+            Expression::Error(it) => walk_error_expression(traverser, it)?,
+        }
     }
     traverser.exit_expression(it);
+    Continue(())
 }
 
-fn walk_identifier_reference<'src>(traverser: &mut impl Traverse<'src>, it: &mut Identifier<'src>) {
-    traverser.enter_identifier_reference(it);
+fn walk_identifier_reference<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut Identifier<'src>,
+) -> ControlFlow<()> {
+    enter(traverser.enter_identifier_reference(it))?;
     traverser.exit_identifier_reference(it);
+    Continue(())
 }
 
-fn walk_boolean_literal<'src>(traverser: &mut impl Traverse<'src>, it: &mut BooleanLiteral) {
-    traverser.enter_boolean_literal(it);
+fn walk_boolean_literal<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut BooleanLiteral,
+) -> ControlFlow<()> {
+    enter(traverser.enter_boolean_literal(it))?;
     traverser.exit_boolean_literal(it);
+    Continue(())
 }
 
-fn walk_numeric_literal<'src>(traverser: &mut impl Traverse<'src>, it: &mut NumericLiteral<'src>) {
-    traverser.enter_numeric_literal(it);
+fn walk_numeric_literal<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut NumericLiteral<'src>,
+) -> ControlFlow<()> {
+    enter(traverser.enter_numeric_literal(it))?;
     traverser.exit_numeric_literal(it);
+    Continue(())
 }
 
-fn walk_string_literal<'src>(traverser: &mut impl Traverse<'src>, it: &mut StringLiteral<'src>) {
-    traverser.enter_string_literal(it);
+fn walk_string_literal<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut StringLiteral<'src>,
+) -> ControlFlow<()> {
+    enter(traverser.enter_string_literal(it))?;
     traverser.exit_string_literal(it);
+    Continue(())
 }
 
 fn walk_variable_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut VariableExpression<'src>,
-) {
-    traverser.enter_variable_expression(it);
-    walk_variable_member(traverser, &mut it.member);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_variable_expression(it))? {
+        push(traverser, NodeKind::VariableExpression);
+        walk_variable_member(traverser, &mut it.member)?;
+        pop(traverser);
+    }
     traverser.exit_variable_expression(it);
+    Continue(())
 }
 
-fn walk_variable_member<'src>(traverser: &mut impl Traverse<'src>, it: &mut VariableMember<'src>) {
-    traverser.enter_variable_member(it);
-    match it {
-        VariableMember::Object { object, property, .. } => {
-            walk_variable_member(traverser, object);
-            walk_identifier_reference(traverser, property);
-        }
-        VariableMember::Property { property, .. } => {
-            walk_identifier_reference(traverser, property);
+fn walk_variable_member<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut VariableMember<'src>,
+) -> ControlFlow<()> {
+    if enter(traverser.enter_variable_member(it))? {
+        push(traverser, NodeKind::VariableMember);
+        match it {
+            VariableMember::Object { object, property, .. } => {
+                walk_variable_member(traverser, object)?;
+                walk_identifier_reference(traverser, property)?;
+            }
+            VariableMember::Property { property, .. } => {
+                walk_identifier_reference(traverser, property)?;
+            }
         }
+        pop(traverser);
     }
     traverser.exit_variable_member(it);
+    Continue(())
 }
 
 fn walk_parenthesized_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut ParenthesizedExpression<'src>,
-) {
-    traverser.enter_parenthesized_expression(it);
-    match &mut it.body {
-        ParenthesizedBody::Single(expression) => {
-            walk_expression(traverser, expression);
-        }
-        ParenthesizedBody::Multiple(statements) => {
-            walk_statements(traverser, statements);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_parenthesized_expression(it))? {
+        push(traverser, NodeKind::ParenthesizedExpression);
+        match &mut it.body {
+            ParenthesizedBody::Single(expression) => {
+                walk_expression(traverser, expression)?;
+            }
+            ParenthesizedBody::Multiple(statements) => {
+                walk_statements(traverser, statements)?;
+            }
         }
+        pop(traverser);
     }
     traverser.exit_parenthesized_expression(it);
+    Continue(())
 }
 
 fn walk_block_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut BlockExpression<'src>,
-) {
-    traverser.enter_block_expression(it);
-    walk_statements(traverser, &mut it.statements);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_block_expression(it))? {
+        push(traverser, NodeKind::BlockExpression);
+        walk_statements(traverser, &mut it.statements)?;
+        pop(traverser);
+    }
     traverser.exit_block_expression(it);
+    Continue(())
 }
 
 fn walk_binary_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut BinaryExpression<'src>,
-) {
-    traverser.enter_binary_expression(it);
-    walk_expression(traverser, &mut it.left);
-    walk_expression(traverser, &mut it.right);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_binary_expression(it))? {
+        push(traverser, NodeKind::BinaryExpression);
+        walk_expression(traverser, &mut it.left)?;
+        walk_expression(traverser, &mut it.right)?;
+        pop(traverser);
+    }
     traverser.exit_binary_expression(it);
+    Continue(())
 }
 
 fn walk_unary_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut UnaryExpression<'src>,
-) {
-    traverser.enter_unary_expression(it);
-    walk_expression(traverser, &mut it.argument);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_unary_expression(it))? {
+        push(traverser, NodeKind::UnaryExpression);
+        walk_expression(traverser, &mut it.argument)?;
+        pop(traverser);
+    }
     traverser.exit_unary_expression(it);
+    Continue(())
 }
 
 fn walk_update_expression<'src>(
-    visitor: &mut impl Traverse<'src>,
+    traverser: &mut impl Traverse<'src>,
     it: &mut UpdateExpression<'src>,
-) {
-    visitor.enter_update_expression(it);
-    walk_variable_expression(visitor, &mut it.variable);
-    visitor.exit_update_expression(it);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_update_expression(it))? {
+        push(traverser, NodeKind::UpdateExpression);
+        walk_variable_expression(traverser, &mut it.variable)?;
+        pop(traverser);
+    }
+    traverser.exit_update_expression(it);
+    Continue(())
 }
 
 fn walk_ternary_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut TernaryExpression<'src>,
-) {
-    traverser.enter_ternary_expression(it);
-    walk_expression(traverser, &mut it.test);
-    walk_expression(traverser, &mut it.consequent);
-    walk_expression(traverser, &mut it.alternate);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_ternary_expression(it))? {
+        push(traverser, NodeKind::TernaryExpression);
+        walk_expression(traverser, &mut it.test)?;
+        walk_expression(traverser, &mut it.consequent)?;
+        walk_expression(traverser, &mut it.alternate)?;
+        pop(traverser);
+    }
     traverser.exit_ternary_expression(it);
+    Continue(())
 }
 
 fn walk_conditional_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut ConditionalExpression<'src>,
-) {
-    traverser.enter_conditional_expression(it);
-    walk_expression(traverser, &mut it.test);
-    walk_expression(traverser, &mut it.consequent);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_conditional_expression(it))? {
+        push(traverser, NodeKind::ConditionalExpression);
+        walk_expression(traverser, &mut it.test)?;
+        walk_expression(traverser, &mut it.consequent)?;
+        pop(traverser);
+    }
     traverser.exit_conditional_expression(it);
+    Continue(())
 }
 
 fn walk_resource_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut ResourceExpression<'src>,
-) {
-    traverser.enter_resource_expression(it);
-    walk_identifier_reference(traverser, &mut it.name);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_resource_expression(it))? {
+        push(traverser, NodeKind::ResourceExpression);
+        walk_identifier_reference(traverser, &mut it.name)?;
+        pop(traverser);
+    }
     traverser.exit_resource_expression(it);
+    Continue(())
 }
 
 fn walk_array_access_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut ArrayAccessExpression<'src>,
-) {
-    traverser.enter_array_access_expression(it);
-    walk_identifier_reference(traverser, &mut it.name);
-    walk_expression(traverser, &mut it.index);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_array_access_expression(it))? {
+        push(traverser, NodeKind::ArrayAccessExpression);
+        walk_identifier_reference(traverser, &mut it.name)?;
+        walk_expression(traverser, &mut it.index)?;
+        pop(traverser);
+    }
     traverser.exit_array_access_expression(it);
+    Continue(())
 }
 
 fn walk_arrow_access_expression<'src>(
     traverser: &mut impl Traverse<'src>,
     it: &mut ArrowAccessExpression<'src>,
-) {
-    traverser.enter_arrow_access_expression(it);
-    walk_expression(traverser, &mut it.left);
-    walk_expression(traverser, &mut it.right);
+) -> ControlFlow<()> {
+    if enter(traverser.enter_arrow_access_expression(it))? {
+        push(traverser, NodeKind::ArrowAccessExpression);
+        walk_expression(traverser, &mut it.left)?;
+        walk_expression(traverser, &mut it.right)?;
+        pop(traverser);
+    }
     traverser.exit_arrow_access_expression(it);
+    Continue(())
 }
 
-fn walk_call_expression<'src>(traverser: &mut impl Traverse<'src>, it: &mut CallExpression<'src>) {
-    traverser.enter_call_expression(it);
-    walk_identifier_reference(traverser, &mut it.callee);
-    if let Some(args) = &mut it.arguments {
-        for arg in args {
-            walk_expression(traverser, arg);
+fn walk_call_expression<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut CallExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(traverser.enter_call_expression(it))? {
+        push(traverser, NodeKind::CallExpression);
+        walk_identifier_reference(traverser, &mut it.callee)?;
+        if let Some(args) = &mut it.arguments {
+            for arg in args {
+                walk_expression(traverser, arg)?;
+            }
         }
+        pop(traverser);
     }
     traverser.exit_call_expression(it);
+    Continue(())
 }
 
-fn walk_this_expression<'src>(traverser: &mut impl Traverse<'src>, it: &mut ThisExpression) {
-    traverser.enter_this_expression(it);
+fn walk_this_expression<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut ThisExpression,
+) -> ControlFlow<()> {
+    enter(traverser.enter_this_expression(it))?;
     traverser.exit_this_expression(it);
+    Continue(())
+}
+
+fn walk_error_expression<'src>(
+    traverser: &mut impl Traverse<'src>,
+    it: &mut ErrorExpression,
+) -> ControlFlow<()> {
+    enter(traverser.enter_error_expression(it))?;
+    traverser.exit_error_expression(it);
+    Continue(())
+}
+
+/// Returns whether the children of a node should be walked, or breaks if the
+/// traversal was stopped.
+#[inline]
+pub(crate) fn enter(control: Control) -> ControlFlow<(), bool> {
+    match control {
+        Control::Continue => Continue(true),
+        Control::SkipChildren => Continue(false),
+        Control::Stop => Break(()),
+    }
+}
+
+#[inline]
+fn push<'src>(traverser: &mut impl Traverse<'src>, kind: NodeKind) {
+    if let Some(ancestry) = traverser.ancestry() {
+        ancestry.push(kind);
+    }
+}
+
+#[inline]
+fn pop<'src>(traverser: &mut impl Traverse<'src>) {
+    if let Some(ancestry) = traverser.ancestry() {
+        ancestry.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    /// Records the numbers it enters along with the kinds of their ancestors.
+    #[derive(Default)]
+    struct Recorder {
+        ancestry: Ancestry,
+        numbers: Vec<(String, Vec<NodeKind>)>,
+    }
+
+    impl<'src> Traverse<'src> for Recorder {
+        fn ancestry(&mut self) -> Option<&mut Ancestry> {
+            Some(&mut self.ancestry)
+        }
+
+        fn enter_numeric_literal(&mut self, it: &mut NumericLiteral<'src>) -> Control {
+            self.numbers.push((it.raw.to_string(), self.ancestry.iter().collect()));
+            if it.value == 9.0 { Control::Stop } else { Control::Continue }
+        }
+
+        fn enter_block_expression(&mut self, _: &mut BlockExpression<'src>) -> Control {
+            Control::SkipChildren
+        }
+    }
+
+    #[test]
+    fn test_control_and_ancestry() {
+        let source = "v.a = v.b ? 1 : -2; loop(3, { v.c = 4; }); q.d(9); v.e = 5;";
+        let mut program = Parser::new(source).parse().program;
+        let mut recorder = Recorder::default();
+        traverse(&mut recorder, &mut program);
+        use NodeKind::*;
+        assert_eq!(
+            recorder.numbers,
+            [
+                ("1".to_string(), vec![TernaryExpression, AssignmentStatement, Program]),
+                (
+                    "2".to_string(),
+                    vec![UnaryExpression, TernaryExpression, AssignmentStatement, Program]
+                ),
+                ("3".to_string(), vec![LoopStatement, Program]),
+                ("9".to_string(), vec![CallExpression, Program]),
+            ]
+        );
+    }
 }
@@ -0,0 +1,772 @@
+use std::ops::ControlFlow::{self, Continue};
+
+use crate::{
+    ast::*,
+    traverse::{Ancestry, Control, NodeKind, enter},
+};
+
+/// Visits the AST using an implementer of [`Visit`].
+pub fn visit<'src>(visitor: &mut impl Visit<'src>, program: &Program<'src>) {
+    if let Some(ancestry) = visitor.ancestry() {
+        ancestry.clear();
+    }
+    let _ = walk_program(visitor, program);
+}
+
+/// Like [`Traverse`](crate::traverse::Traverse), but with shared references,
+/// for analyses that don't change the AST. They can run on a borrowed AST, such
+/// as one behind an `Arc` or one that is visited by several threads at once.
+#[expect(unused_variables)]
+pub trait Visit<'src>: Sized {
+    /// Returns the ancestry to keep up to date while visiting, if the
+    /// implementer needs to know the parents of nodes.
+    #[inline]
+    fn ancestry(&mut self) -> Option<&mut Ancestry> {
+        None
+    }
+
+    #[inline]
+    fn enter_program(&mut self, it: &Program<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_program(&mut self, it: &Program<'src>) {}
+
+    #[inline]
+    fn enter_statements(&mut self, it: &[Statement<'src>]) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_statements(&mut self, it: &[Statement<'src>]) {}
+
+    #[inline]
+    fn enter_statement(&mut self, it: &Statement<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_statement(&mut self, it: &Statement<'src>) {}
+
+    #[inline]
+    fn enter_assignment_statement(&mut self, it: &AssignmentStatement<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_assignment_statement(&mut self, it: &AssignmentStatement<'src>) {}
+
+    #[inline]
+    fn enter_loop_statement(&mut self, it: &LoopStatement<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_loop_statement(&mut self, it: &LoopStatement<'src>) {}
+
+    #[inline]
+    fn enter_for_each_statement(&mut self, it: &ForEachStatement<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_for_each_statement(&mut self, it: &ForEachStatement<'src>) {}
+
+    #[inline]
+    fn enter_return_statement(&mut self, it: &ReturnStatement<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_return_statement(&mut self, it: &ReturnStatement<'src>) {}
+
+    #[inline]
+    fn enter_break_statement(&mut self, it: &BreakStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_break_statement(&mut self, it: &BreakStatement) {}
+
+    #[inline]
+    fn enter_continue_statement(&mut self, it: &ContinueStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_continue_statement(&mut self, it: &ContinueStatement) {}
+
+    #[inline]
+    fn enter_empty_statement(&mut self, it: &EmptyStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_empty_statement(&mut self, it: &EmptyStatement) {}
+
+    #[inline]
+    fn enter_error_statement(&mut self, it: &ErrorStatement) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_error_statement(&mut self, it: &ErrorStatement) {}
+
+    #[inline]
+    fn enter_expression(&mut self, it: &Expression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_expression(&mut self, it: &Expression<'src>) {}
+
+    #[inline]
+    fn enter_identifier_reference(&mut self, it: &Identifier<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_identifier_reference(&mut self, it: &Identifier<'src>) {}
+
+    #[inline]
+    fn enter_numeric_literal(&mut self, it: &NumericLiteral<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_numeric_literal(&mut self, it: &NumericLiteral<'src>) {}
+
+    #[inline]
+    fn enter_boolean_literal(&mut self, it: &BooleanLiteral) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_boolean_literal(&mut self, it: &BooleanLiteral) {}
+
+    #[inline]
+    fn enter_string_literal(&mut self, it: &StringLiteral<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_string_literal(&mut self, it: &StringLiteral<'src>) {}
+
+    #[inline]
+    fn enter_variable_expression(&mut self, it: &VariableExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_variable_expression(&mut self, it: &VariableExpression<'src>) {}
+
+    #[inline]
+    fn enter_variable_member(&mut self, it: &VariableMember<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_variable_member(&mut self, it: &VariableMember<'src>) {}
+
+    #[inline]
+    fn enter_parenthesized_expression(&mut self, it: &ParenthesizedExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_parenthesized_expression(&mut self, it: &ParenthesizedExpression<'src>) {}
+
+    #[inline]
+    fn enter_block_expression(&mut self, it: &BlockExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_block_expression(&mut self, it: &BlockExpression<'src>) {}
+
+    #[inline]
+    fn enter_binary_expression(&mut self, it: &BinaryExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_binary_expression(&mut self, it: &BinaryExpression<'src>) {}
+
+    #[inline]
+    fn enter_unary_expression(&mut self, it: &UnaryExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_unary_expression(&mut self, it: &UnaryExpression<'src>) {}
+
+    #[inline]
+    fn enter_update_expression(&mut self, it: &UpdateExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_update_expression(&mut self, it: &UpdateExpression<'src>) {}
+
+    #[inline]
+    fn enter_ternary_expression(&mut self, it: &TernaryExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_ternary_expression(&mut self, it: &TernaryExpression<'src>) {}
+
+    #[inline]
+    fn enter_conditional_expression(&mut self, it: &ConditionalExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_conditional_expression(&mut self, it: &ConditionalExpression<'src>) {}
+
+    #[inline]
+    fn enter_resource_expression(&mut self, it: &ResourceExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_resource_expression(&mut self, it: &ResourceExpression<'src>) {}
+
+    #[inline]
+    fn enter_array_access_expression(&mut self, it: &ArrayAccessExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_array_access_expression(&mut self, it: &ArrayAccessExpression<'src>) {}
+
+    #[inline]
+    fn enter_arrow_access_expression(&mut self, it: &ArrowAccessExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_arrow_access_expression(&mut self, it: &ArrowAccessExpression<'src>) {}
+
+    #[inline]
+    fn enter_call_expression(&mut self, it: &CallExpression<'src>) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_call_expression(&mut self, it: &CallExpression<'src>) {}
+
+    #[inline]
+    fn enter_this_expression(&mut self, it: &ThisExpression) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_this_expression(&mut self, it: &ThisExpression) {}
+
+    #[inline]
+    fn enter_error_expression(&mut self, it: &ErrorExpression) -> Control {
+        Control::Continue
+    }
+
+    #[inline]
+    fn exit_error_expression(&mut self, it: &ErrorExpression) {}
+}
+
+fn walk_program<'src>(visitor: &mut impl Visit<'src>, it: &Program<'src>) -> ControlFlow<()> {
+    if enter(visitor.enter_program(it))? {
+        push(visitor, NodeKind::Program);
+        match &it.body {
+            ProgramBody::Simple(expr) => walk_expression(visitor, expr)?,
+            ProgramBody::Complex(stmts) => walk_statements(visitor, stmts)?,
+            ProgramBody::Empty => (),
+        }
+        pop(visitor);
+    }
+    visitor.exit_program(it);
+    Continue(())
+}
+
+fn walk_statements<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &[Statement<'src>],
+) -> ControlFlow<()> {
+    if enter(visitor.enter_statements(it))? {
+        for stmt in it.iter() {
+            walk_statement(visitor, stmt)?;
+        }
+    }
+    visitor.exit_statements(it);
+    Continue(())
+}
+
+fn walk_statement<'src>(visitor: &mut impl Visit<'src>, it: &Statement<'src>) -> ControlFlow<()> {
+    if enter(visitor.enter_statement(it))? {
+        match it {
+            Statement::Expression(it) => walk_expression(visitor, it)?,
+            Statement::Assignment(it) => walk_assignment_statement(visitor, it)?,
+            Statement::Loop(it) => walk_loop_statement(visitor, it)?,
+            Statement::ForEach(it) => walk_for_each_statement(visitor, it)?,
+            Statement::Return(it) => walk_return_statement(visitor, it)?,
+            Statement::Break(it) => walk_break_statement(visitor, it)?,
+            Statement::Continue(it) => walk_continue_statement(visitor, it)?,
+            Statement::Empty(it) => walk_empty_statement(visitor, it)?,
+            Statement::Error(it) => walk_error_statement(visitor, it)?,
+        }
+    }
+    visitor.exit_statement(it);
+    Continue(())
+}
+
+fn walk_assignment_statement<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &AssignmentStatement<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_assignment_statement(it))? {
+        push(visitor, NodeKind::AssignmentStatement);
+        walk_variable_expression(visitor, &it.left)?;
+        walk_expression(visitor, &it.right)?;
+        pop(visitor);
+    }
+    visitor.exit_assignment_statement(it);
+    Continue(())
+}
+
+fn walk_loop_statement<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &LoopStatement<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_loop_statement(it))? {
+        push(visitor, NodeKind::LoopStatement);
+        walk_expression(visitor, &it.count)?;
+        walk_block_expression(visitor, &it.block)?;
+        pop(visitor);
+    }
+    visitor.exit_loop_statement(it);
+    Continue(())
+}
+
+fn walk_for_each_statement<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ForEachStatement<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_for_each_statement(it))? {
+        push(visitor, NodeKind::ForEachStatement);
+        walk_variable_expression(visitor, &it.variable)?;
+        walk_expression(visitor, &it.array)?;
+        walk_block_expression(visitor, &it.block)?;
+        pop(visitor);
+    }
+    visitor.exit_for_each_statement(it);
+    Continue(())
+}
+
+fn walk_return_statement<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ReturnStatement<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_return_statement(it))? {
+        push(visitor, NodeKind::ReturnStatement);
+        walk_expression(visitor, &it.argument)?;
+        pop(visitor);
+    }
+    visitor.exit_return_statement(it);
+    Continue(())
+}
+
+fn walk_break_statement<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &BreakStatement,
+) -> ControlFlow<()> {
+    enter(visitor.enter_break_statement(it))?;
+    visitor.exit_break_statement(it);
+    Continue(())
+}
+
+fn walk_continue_statement<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ContinueStatement,
+) -> ControlFlow<()> {
+    enter(visitor.enter_continue_statement(it))?;
+    visitor.exit_continue_statement(it);
+    Continue(())
+}
+
+fn walk_empty_statement<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &EmptyStatement,
+) -> ControlFlow<()> {
+    enter(visitor.enter_empty_statement(it))?;
+    visitor.exit_empty_statement(it);
+    Continue(())
+}
+
+fn walk_error_statement<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ErrorStatement,
+) -> ControlFlow<()> {
+    enter(visitor.enter_error_statement(it))?;
+    visitor.exit_error_statement(it);
+    Continue(())
+}
+
+fn walk_expression<'src>(visitor: &mut impl Visit<'src>, it: &Expression<'src>) -> ControlFlow<()> {
+    if enter(visitor.enter_expression(it))? {
+        match it {
+            Expression::NumericLiteral(it) => walk_numeric_literal(visitor, it)?,
+            Expression::BooleanLiteral(it) => walk_boolean_literal(visitor, it)?,
+            Expression::StringLiteral(it) => walk_string_literal(visitor, it)?,
+            Expression::Variable(it) => walk_variable_expression(visitor, it)?,
+            Expression::Parenthesized(it) => walk_parenthesized_expression(visitor, it)?,
+            Expression::Block(it) => walk_block_expression(visitor, it)?,
+            Expression::Binary(it) => walk_binary_expression(visitor, it)?,
+            Expression::Unary(it) => walk_unary_expression(visitor, it)?,
+            Expression::Update(it) => walk_update_expression(visitor, it)?,
+            Expression::Ternary(it) => walk_ternary_expression(visitor, it)?,
+            Expression::Conditional(it) => walk_conditional_expression(visitor, it)?,
+            Expression::Resource(it) => walk_resource_expression(visitor, it)?,
+            Expression::ArrayAccess(it) => walk_array_access_expression(visitor, it)?,
+            Expression::ArrowAccess(it) => walk_arrow_access_expression(visitor, it)?,
+            Expression::Call(it) => walk_call_expression(visitor, it)?,
+            Expression::This(it) => walk_this_expression(visitor, it)?,
+            Expression::Error(it) => walk_error_expression(visitor, it)?,
+        }
+    }
+    visitor.exit_expression(it);
+    Continue(())
+}
+
+fn walk_identifier_reference<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &Identifier<'src>,
+) -> ControlFlow<()> {
+    enter(visitor.enter_identifier_reference(it))?;
+    visitor.exit_identifier_reference(it);
+    Continue(())
+}
+
+fn walk_boolean_literal<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &BooleanLiteral,
+) -> ControlFlow<()> {
+    enter(visitor.enter_boolean_literal(it))?;
+    visitor.exit_boolean_literal(it);
+    Continue(())
+}
+
+fn walk_numeric_literal<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &NumericLiteral<'src>,
+) -> ControlFlow<()> {
+    enter(visitor.enter_numeric_literal(it))?;
+    visitor.exit_numeric_literal(it);
+    Continue(())
+}
+
+fn walk_string_literal<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &StringLiteral<'src>,
+) -> ControlFlow<()> {
+    enter(visitor.enter_string_literal(it))?;
+    visitor.exit_string_literal(it);
+    Continue(())
+}
+
+fn walk_variable_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &VariableExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_variable_expression(it))? {
+        push(visitor, NodeKind::VariableExpression);
+        walk_variable_member(visitor, &it.member)?;
+        pop(visitor);
+    }
+    visitor.exit_variable_expression(it);
+    Continue(())
+}
+
+fn walk_variable_member<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &VariableMember<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_variable_member(it))? {
+        push(visitor, NodeKind::VariableMember);
+        match it {
+            VariableMember::Object { object, property, .. } => {
+                walk_variable_member(visitor, object)?;
+                walk_identifier_reference(visitor, property)?;
+            }
+            VariableMember::Property { property, .. } => {
+                walk_identifier_reference(visitor, property)?;
+            }
+        }
+        pop(visitor);
+    }
+    visitor.exit_variable_member(it);
+    Continue(())
+}
+
+fn walk_parenthesized_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ParenthesizedExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_parenthesized_expression(it))? {
+        push(visitor, NodeKind::ParenthesizedExpression);
+        match &it.body {
+            ParenthesizedBody::Single(expression) => {
+                walk_expression(visitor, expression)?;
+            }
+            ParenthesizedBody::Multiple(statements) => {
+                walk_statements(visitor, statements)?;
+            }
+        }
+        pop(visitor);
+    }
+    visitor.exit_parenthesized_expression(it);
+    Continue(())
+}
+
+fn walk_block_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &BlockExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_block_expression(it))? {
+        push(visitor, NodeKind::BlockExpression);
+        walk_statements(visitor, &it.statements)?;
+        pop(visitor);
+    }
+    visitor.exit_block_expression(it);
+    Continue(())
+}
+
+fn walk_binary_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &BinaryExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_binary_expression(it))? {
+        push(visitor, NodeKind::BinaryExpression);
+        walk_expression(visitor, &it.left)?;
+        walk_expression(visitor, &it.right)?;
+        pop(visitor);
+    }
+    visitor.exit_binary_expression(it);
+    Continue(())
+}
+
+fn walk_unary_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &UnaryExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_unary_expression(it))? {
+        push(visitor, NodeKind::UnaryExpression);
+        walk_expression(visitor, &it.argument)?;
+        pop(visitor);
+    }
+    visitor.exit_unary_expression(it);
+    Continue(())
+}
+
+fn walk_update_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &UpdateExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_update_expression(it))? {
+        push(visitor, NodeKind::UpdateExpression);
+        walk_variable_expression(visitor, &it.variable)?;
+        pop(visitor);
+    }
+    visitor.exit_update_expression(it);
+    Continue(())
+}
+
+fn walk_ternary_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &TernaryExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_ternary_expression(it))? {
+        push(visitor, NodeKind::TernaryExpression);
+        walk_expression(visitor, &it.test)?;
+        walk_expression(visitor, &it.consequent)?;
+        walk_expression(visitor, &it.alternate)?;
+        pop(visitor);
+    }
+    visitor.exit_ternary_expression(it);
+    Continue(())
+}
+
+fn walk_conditional_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ConditionalExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_conditional_expression(it))? {
+        push(visitor, NodeKind::ConditionalExpression);
+        walk_expression(visitor, &it.test)?;
+        walk_expression(visitor, &it.consequent)?;
+        pop(visitor);
+    }
+    visitor.exit_conditional_expression(it);
+    Continue(())
+}
+
+fn walk_resource_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ResourceExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_resource_expression(it))? {
+        push(visitor, NodeKind::ResourceExpression);
+        walk_identifier_reference(visitor, &it.name)?;
+        pop(visitor);
+    }
+    visitor.exit_resource_expression(it);
+    Continue(())
+}
+
+fn walk_array_access_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ArrayAccessExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_array_access_expression(it))? {
+        push(visitor, NodeKind::ArrayAccessExpression);
+        walk_identifier_reference(visitor, &it.name)?;
+        walk_expression(visitor, &it.index)?;
+        pop(visitor);
+    }
+    visitor.exit_array_access_expression(it);
+    Continue(())
+}
+
+fn walk_arrow_access_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ArrowAccessExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_arrow_access_expression(it))? {
+        push(visitor, NodeKind::ArrowAccessExpression);
+        walk_expression(visitor, &it.left)?;
+        walk_expression(visitor, &it.right)?;
+        pop(visitor);
+    }
+    visitor.exit_arrow_access_expression(it);
+    Continue(())
+}
+
+fn walk_call_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &CallExpression<'src>,
+) -> ControlFlow<()> {
+    if enter(visitor.enter_call_expression(it))? {
+        push(visitor, NodeKind::CallExpression);
+        walk_identifier_reference(visitor, &it.callee)?;
+        if let Some(args) = &it.arguments {
+            for arg in args {
+                walk_expression(visitor, arg)?;
+            }
+        }
+        pop(visitor);
+    }
+    visitor.exit_call_expression(it);
+    Continue(())
+}
+
+fn walk_this_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ThisExpression,
+) -> ControlFlow<()> {
+    enter(visitor.enter_this_expression(it))?;
+    visitor.exit_this_expression(it);
+    Continue(())
+}
+
+fn walk_error_expression<'src>(
+    visitor: &mut impl Visit<'src>,
+    it: &ErrorExpression,
+) -> ControlFlow<()> {
+    enter(visitor.enter_error_expression(it))?;
+    visitor.exit_error_expression(it);
+    Continue(())
+}
+
+#[inline]
+fn push<'src>(visitor: &mut impl Visit<'src>, kind: NodeKind) {
+    if let Some(ancestry) = visitor.ancestry() {
+        ancestry.push(kind);
+    }
+}
+
+#[inline]
+fn pop<'src>(visitor: &mut impl Visit<'src>) {
+    if let Some(ancestry) = visitor.ancestry() {
+        ancestry.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    /// Records the hooks it is called with, in order.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl<'src> Visit<'src> for Recorder {
+        fn enter_statement(&mut self, _: &Statement<'src>) -> Control {
+            self.events.push("statement".to_string());
+            Control::Continue
+        }
+
+        fn exit_statement(&mut self, _: &Statement<'src>) {
+            self.events.push("/statement".to_string());
+        }
+
+        fn enter_binary_expression(&mut self, _: &BinaryExpression<'src>) -> Control {
+            self.events.push("binary".to_string());
+            Control::Continue
+        }
+
+        fn exit_binary_expression(&mut self, _: &BinaryExpression<'src>) {
+            self.events.push("/binary".to_string());
+        }
+
+        fn enter_numeric_literal(&mut self, it: &NumericLiteral<'src>) -> Control {
+            self.events.push(it.raw.to_string());
+            if it.value == 9.0 { Control::Stop } else { Control::Continue }
+        }
+
+        fn enter_block_expression(&mut self, _: &BlockExpression<'src>) -> Control {
+            self.events.push("block".to_string());
+            Control::SkipChildren
+        }
+
+        fn exit_block_expression(&mut self, _: &BlockExpression<'src>) {
+            self.events.push("/block".to_string());
+        }
+    }
+
+    #[test]
+    fn test_order_and_control() {
+        let source = "v.a = 1 + 2; { v.b = 3; }; q.c(9); v.d = 4;";
+        let program = Parser::new(source).parse().program;
+        let mut recorder = Recorder::default();
+        visit(&mut recorder, &program);
+        assert_eq!(
+            recorder.events,
+            [
+                "statement",
+                "binary",
+                "1",
+                "2",
+                "/binary",
+                "/statement",
+                "statement",
+                "block",
+                "/block",
+                "/statement",
+                "statement",
+                "9",
+            ]
+        );
+    }
+}
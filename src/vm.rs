@@ -0,0 +1,498 @@
+//! Bytecode representation of Molang programs and a stack based virtual
+//! machine to execute it.
+//!
+//! Compiling a [`Program`][`crate::ast::Program`] into a [`Chunk`] once and
+//! running it many times is considerably faster than walking the AST with an
+//! [`Interpreter`][`crate::eval::Interpreter`]. Chunks own all of their data,
+//! so they can be cached independently of the source code.
+//!
+//! # Example
+//!
+//! ```
+//! # use nolana::{Parser, eval::Value, vm::{Compiler, Vm}};
+//! let program = Parser::new("t.x = 0; loop(10, { t.x = t.x + 2; }); return t.x;").parse().program;
+//! let chunk = Compiler::default().compile(&program).unwrap();
+//! let mut vm = Vm::new();
+//! assert_eq!(vm.run(&chunk).unwrap(), Value::Number(20.0));
+//! ```
+
+mod compiler;
+
+use std::{collections::HashMap, fmt};
+
+pub use compiler::Compiler;
+
+use crate::{
+    ast::{BinaryOperator, UpdateOperator, VariableLifetime},
+    diagnostic::Result,
+    eval::{
+        EntityRef, Value, array_index, binary_operation, entity_variable_assignment,
+        expected_array, expected_entity, expected_number, loop_iterations,
+        math::{MathFunction, Random},
+        query::{NoQueries, QueryCall, QueryProvider},
+        unknown_array,
+        variables::Variables,
+    },
+    span::Span,
+};
+
+/// A single bytecode instruction. Jump targets are indices into the code of
+/// the [`Chunk`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    /// Pushes a number.
+    Number(f32),
+    /// Pushes a value from the constants of the [`Chunk`].
+    Constant(u32),
+    /// Pushes the value of `this`.
+    This,
+    /// Discards the top of the stack.
+    Pop,
+    /// Pushes a variable, or `0.0` if it is unset.
+    Load(u32),
+    /// Pops a value and stores it in a variable.
+    Store(u32),
+    /// Pushes a variable and jumps to `target` if it is set. Otherwise, falls
+    /// through to the right side of `??`.
+    Coalesce {
+        variable: u32,
+        target: u32,
+    },
+    /// Increments or decrements a variable and pushes its new value.
+    Update {
+        variable: u32,
+        operator: UpdateOperator,
+    },
+    /// Pops two values and pushes the result of a non short-circuiting operator.
+    Binary(BinaryOperator),
+    /// `!`
+    Not,
+    /// `-`
+    Negate,
+    /// `~`
+    BitwiseNot,
+    Jump(u32),
+    /// Pops a value and jumps if it is falsy.
+    JumpIfFalse(u32),
+    /// Pops a value and jumps if it is truthy.
+    JumpIfTrue(u32),
+    /// Pops the function's arguments and pushes its result.
+    Math(MathFunction),
+    /// Pops `arguments` values, if any, and pushes the result of a query whose
+    /// name is in the names of the [`Chunk`].
+    Query {
+        name: u32,
+        arguments: Option<u32>,
+    },
+    /// Pops an index and pushes an element of an array whose name is in the
+    /// names of the [`Chunk`].
+    Array(u32),
+    /// Pops an entity that queries will target until [`Instruction::ExitTarget`].
+    EnterTarget,
+    ExitTarget,
+    /// Pops the iteration count of a `loop`.
+    LoopInit,
+    /// Jumps to `exit` when the `loop` is over.
+    LoopNext {
+        exit: u32,
+    },
+    /// Pops the array of a `for_each`.
+    ForEachInit,
+    /// Stores the next element in a variable, or jumps to `exit` when the
+    /// `for_each` is over.
+    ForEachNext {
+        variable: u32,
+        exit: u32,
+    },
+    /// Discards the state of the innermost `loop` or `for_each`.
+    LoopExit,
+    /// Pops the result of the program and stops execution.
+    Return,
+}
+
+/// A compiled Molang program.
+///
+/// Chunks can only be created by the [`Compiler`], which keeps their code,
+/// spans, constants and names consistent with each other. [`Vm::run`] relies
+/// on this instead of checking every instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    code: Vec<Instruction>,
+    /// The span of the source node each instruction was compiled from, used
+    /// for diagnostics.
+    spans: Vec<Span>,
+    constants: Vec<Value>,
+    /// Variables referenced by the code, with their lowercase member path.
+    variables: Vec<(VariableLifetime, Box<[String]>)>,
+    /// Lowercase names of queries and arrays referenced by the code.
+    names: Vec<String>,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Self {
+            code: Vec::new(),
+            spans: Vec::new(),
+            constants: Vec::new(),
+            variables: Vec::new(),
+            names: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let variable = |index: u32| {
+            let (lifetime, path) = &self.variables[index as usize];
+            format!("{}.{}", lifetime.as_str_long(), path.join("."))
+        };
+        for (index, instruction) in self.code.iter().enumerate() {
+            write!(f, "{index:>4}  ")?;
+            match *instruction {
+                Instruction::Number(value) => write!(f, "Number {value}")?,
+                Instruction::Constant(constant) => {
+                    write!(f, "Constant {}", self.constants[constant as usize])?
+                }
+                Instruction::Load(index) => write!(f, "Load {}", variable(index))?,
+                Instruction::Store(index) => write!(f, "Store {}", variable(index))?,
+                Instruction::Coalesce { variable: index, target } => {
+                    write!(f, "Coalesce {} -> {target}", variable(index))?
+                }
+                Instruction::Update { variable: index, operator } => {
+                    write!(f, "Update {}{}", variable(index), operator.as_str())?
+                }
+                Instruction::Binary(operator) => write!(f, "Binary {}", operator.as_str())?,
+                Instruction::Jump(target) => write!(f, "Jump -> {target}")?,
+                Instruction::JumpIfFalse(target) => write!(f, "JumpIfFalse -> {target}")?,
+                Instruction::JumpIfTrue(target) => write!(f, "JumpIfTrue -> {target}")?,
+                Instruction::Query { name, arguments } => {
+                    write!(f, "Query query.{}", self.names[name as usize])?;
+                    if let Some(arguments) = arguments {
+                        write!(f, " ({arguments})")?;
+                    }
+                }
+                Instruction::Array(name) => write!(f, "Array array.{}", self.names[name as usize])?,
+                Instruction::LoopNext { exit } => write!(f, "LoopNext -> {exit}")?,
+                Instruction::ForEachNext { variable: index, exit } => {
+                    write!(f, "ForEachNext {} -> {exit}", variable(index))?
+                }
+                instruction => write!(f, "{instruction:?}")?,
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of a running `loop` or `for_each`.
+#[derive(Debug)]
+struct LoopFrame {
+    state: LoopState,
+    /// The height of the value and target stacks when the loop started.
+    /// `break` and `continue` may jump out of a partially evaluated
+    /// expression, so both stacks are restored on every iteration.
+    stack: usize,
+    targets: usize,
+}
+
+#[derive(Debug)]
+enum LoopState {
+    Count(u32),
+    ForEach(std::vec::IntoIter<Value>),
+}
+
+/// Executes [`Chunk`]s.
+///
+/// Like [`Interpreter`][`crate::eval::Interpreter`], a [`Vm`] is meant to be
+/// kept per entity since it owns the entity's [`Variables`]. Its stacks are
+/// preallocated and reused across runs.
+#[derive(Debug)]
+pub struct Vm<Q = NoQueries> {
+    stack: Vec<Value>,
+    loops: Vec<LoopFrame>,
+    targets: Vec<EntityRef>,
+    variables: Variables,
+    arrays: HashMap<String, Vec<Value>>,
+    this: Value,
+    random: Random,
+    queries: Q,
+}
+
+impl Vm {
+    /// Creates a new [`Vm`] without a query provider.
+    pub fn new() -> Self {
+        Self::with_stack_capacity(64)
+    }
+
+    /// Creates a new [`Vm`] whose value stack can grow up to `capacity` values
+    /// without reallocating.
+    pub fn with_stack_capacity(capacity: usize) -> Self {
+        Self {
+            stack: Vec::with_capacity(capacity),
+            loops: Vec::with_capacity(8),
+            targets: Vec::with_capacity(8),
+            variables: Variables::default(),
+            arrays: HashMap::new(),
+            this: Value::default(),
+            random: Random::default(),
+            queries: NoQueries,
+        }
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Q: QueryProvider> Vm<Q> {
+    /// Sets the provider used to answer `query.*` calls.
+    pub fn with_queries<T: QueryProvider>(self, queries: T) -> Vm<T> {
+        Vm {
+            stack: self.stack,
+            loops: self.loops,
+            targets: self.targets,
+            variables: self.variables,
+            arrays: self.arrays,
+            this: self.this,
+            random: self.random,
+            queries,
+        }
+    }
+
+    /// Returns the query provider.
+    pub fn queries_mut(&mut self) -> &mut Q {
+        &mut self.queries
+    }
+
+    /// Sets the variables of the entity this VM runs for.
+    pub fn with_variables(mut self, variables: Variables) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    pub fn variables_mut(&mut self) -> &mut Variables {
+        &mut self.variables
+    }
+
+    /// Sets the value returned by `this`.
+    pub fn with_this(mut self, value: impl Into<Value>) -> Self {
+        self.this = value.into();
+        self
+    }
+
+    /// Seeds the generator used by `math.random` and friends.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.random = Random::new(seed);
+        self
+    }
+
+    /// Defines an array that can be accessed with `array.<name>[index]`.
+    pub fn with_array(mut self, name: impl Into<String>, values: Vec<Value>) -> Self {
+        self.arrays.insert(name.into().to_ascii_lowercase(), values);
+        self
+    }
+
+    /// Runs a chunk and returns its result. `temp.*` variables are cleared
+    /// beforehand.
+    pub fn run(&mut self, chunk: &Chunk) -> Result<Value> {
+        self.stack.clear();
+        self.loops.clear();
+        self.targets.clear();
+        self.variables.clear_temp();
+
+        let mut pc = 0;
+        loop {
+            let instruction = chunk.code[pc];
+            let span = chunk.spans[pc];
+            pc += 1;
+            match instruction {
+                Instruction::Number(value) => self.stack.push(Value::Number(value)),
+                Instruction::Constant(index) => {
+                    self.stack.push(chunk.constants[index as usize].clone())
+                }
+                Instruction::This => self.stack.push(self.this.clone()),
+                Instruction::Pop => {
+                    self.pop();
+                }
+                Instruction::Load(index) => {
+                    let value = self.load(chunk, index, span)?.unwrap_or_default();
+                    self.stack.push(value);
+                }
+                Instruction::Store(index) => {
+                    let value = self.pop();
+                    self.store(chunk, index, value, span)?;
+                }
+                Instruction::Coalesce { variable, target } => {
+                    if let Some(value) = self.load(chunk, variable, span)? {
+                        self.stack.push(value);
+                        pc = target as usize;
+                    }
+                }
+                Instruction::Update { variable, operator } => {
+                    let value = self.load(chunk, variable, span)?.unwrap_or_default();
+                    let value = binary_operation(value, operator.into(), 1.0.into(), span)?;
+                    self.store(chunk, variable, value.clone(), span)?;
+                    self.stack.push(value);
+                }
+                Instruction::Binary(operator) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(binary_operation(left, operator, right, span)?);
+                }
+                Instruction::Not => {
+                    let value = self.pop();
+                    self.stack.push((!value.is_truthy()).into());
+                }
+                Instruction::Negate => {
+                    let value = self.pop_number(span)?;
+                    self.stack.push(Value::Number(-value));
+                }
+                Instruction::BitwiseNot => {
+                    let value = self.pop_number(span)?;
+                    self.stack.push(Value::Number(!(value as i32) as f32));
+                }
+                Instruction::Jump(target) => pc = target as usize,
+                Instruction::JumpIfFalse(target) => {
+                    if !self.pop().is_truthy() {
+                        pc = target as usize;
+                    }
+                }
+                Instruction::JumpIfTrue(target) => {
+                    if self.pop().is_truthy() {
+                        pc = target as usize;
+                    }
+                }
+                Instruction::Math(function) => {
+                    let mut args = [0.0; 3];
+                    let arity = function.arity();
+                    for index in (0..arity).rev() {
+                        args[index] = self.pop_number(span)?;
+                    }
+                    let value = function.call(&args[..arity], &mut self.random);
+                    self.stack.push(Value::Number(value));
+                }
+                Instruction::Query { name, arguments } => {
+                    let start = self.stack.len() - arguments.unwrap_or_default() as usize;
+                    let call = QueryCall {
+                        name: &chunk.names[name as usize],
+                        arguments: arguments.map(|_| &self.stack[start..]),
+                        target: self.targets.last().copied(),
+                    };
+                    let value = self.queries.query(call).map_err(|error| {
+                        if error.labels.is_none() { error.with_label(span) } else { error }
+                    })?;
+                    self.stack.truncate(start);
+                    self.stack.push(value);
+                }
+                Instruction::Array(name) => {
+                    let index = self.pop_number(span)?;
+                    let name = &chunk.names[name as usize];
+                    let Some(values) = self.arrays.get(name).filter(|values| !values.is_empty())
+                    else {
+                        return Err(unknown_array(name, span));
+                    };
+                    self.stack.push(values[array_index(index, values.len())].clone());
+                }
+                Instruction::EnterTarget => {
+                    let Value::Entity(entity) = self.pop() else {
+                        return Err(expected_entity(span));
+                    };
+                    self.targets.push(entity);
+                }
+                Instruction::ExitTarget => {
+                    self.targets.pop();
+                }
+                Instruction::LoopInit => {
+                    let count = self.pop_number(span)?;
+                    self.enter_loop(LoopState::Count(loop_iterations(count)));
+                }
+                Instruction::LoopNext { exit } => {
+                    let frame = self.restore_loop();
+                    match &mut frame.state {
+                        LoopState::Count(0) => pc = exit as usize,
+                        LoopState::Count(count) => *count -= 1,
+                        LoopState::ForEach(_) => unreachable!("`LoopNext` inside of `for_each`"),
+                    }
+                }
+                Instruction::ForEachInit => {
+                    let Value::Array(values) = self.pop() else {
+                        return Err(expected_array(span));
+                    };
+                    self.enter_loop(LoopState::ForEach(values.into_iter()));
+                }
+                Instruction::ForEachNext { variable, exit } => {
+                    let frame = self.restore_loop();
+                    let LoopState::ForEach(values) = &mut frame.state else {
+                        unreachable!("`ForEachNext` inside of `loop`");
+                    };
+                    match values.next() {
+                        Some(value) => self.store(chunk, variable, value, span)?,
+                        None => pc = exit as usize,
+                    }
+                }
+                Instruction::LoopExit => {
+                    self.restore_loop();
+                    self.loops.pop();
+                }
+                Instruction::Return => return Ok(self.pop()),
+            }
+        }
+    }
+
+    fn enter_loop(&mut self, state: LoopState) {
+        let frame = LoopFrame { state, stack: self.stack.len(), targets: self.targets.len() };
+        self.loops.push(frame);
+    }
+
+    /// Discards values left over by a `break` or `continue` and returns the
+    /// innermost loop.
+    fn restore_loop(&mut self) -> &mut LoopFrame {
+        let frame = self.loops.last_mut().expect("compiler must keep loops balanced");
+        self.stack.truncate(frame.stack);
+        self.targets.truncate(frame.targets);
+        frame
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("compiler must keep the stack balanced")
+    }
+
+    #[inline]
+    fn pop_number(&mut self, span: Span) -> Result<f32> {
+        match self.pop() {
+            Value::Number(value) => Ok(value),
+            value => Err(expected_number(&value, span)),
+        }
+    }
+
+    /// `variable.*` inside of [`Instruction::EnterTarget`] belongs to the
+    /// target entity, so it is read through the query provider.
+    #[inline]
+    fn load(&mut self, chunk: &Chunk, index: u32, span: Span) -> Result<Option<Value>> {
+        let (lifetime, path) = &chunk.variables[index as usize];
+        match self.targets.last() {
+            Some(&entity) if *lifetime == VariableLifetime::Variable => {
+                self.queries.entity_variable(entity, &path.join(".")).map_err(|error| {
+                    if error.labels.is_none() { error.with_label(span) } else { error }
+                })
+            }
+            _ => Ok(self.variables.get(*lifetime, path).cloned()),
+        }
+    }
+
+    #[inline]
+    fn store(&mut self, chunk: &Chunk, index: u32, value: Value, span: Span) -> Result<()> {
+        let (lifetime, path) = &chunk.variables[index as usize];
+        if !self.targets.is_empty() && *lifetime == VariableLifetime::Variable {
+            return Err(entity_variable_assignment(&path.join("."), span));
+        }
+        self.variables.set(*lifetime, path, value).map_err(|error| error.with_label(span))
+    }
+}
@@ -0,0 +1,354 @@
+use super::{Chunk, Instruction};
+use crate::{
+    ast::*,
+    diagnostic::Result,
+    eval::{
+        Value, break_outside_loop, continue_outside_loop, math, syntax_error, variables::lowercase,
+    },
+    span::Span,
+};
+
+/// Compiles a [`Program`] into a [`Chunk`].
+///
+/// Math calls are resolved and `break`/`continue` statements are checked at
+/// compile time, so these errors are reported even if the offending code is
+/// never reached.
+#[derive(Debug)]
+pub struct Compiler {
+    chunk: Chunk,
+    loops: Vec<LoopLabels>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self { chunk: Chunk::new(), loops: Vec::new() }
+    }
+}
+
+/// Jump targets of the innermost `loop` or `for_each`.
+#[derive(Debug)]
+struct LoopLabels {
+    /// The `LoopNext` or `ForEachNext` instruction, which `continue` jumps to.
+    next: u32,
+    /// Jumps emitted by `break`, which are patched once the loop's exit is
+    /// known.
+    breaks: Vec<usize>,
+}
+
+impl Compiler {
+    pub fn compile(mut self, program: &Program) -> Result<Chunk> {
+        match &program.body {
+            ProgramBody::Simple(expr) => self.compile_expression(expr)?,
+            ProgramBody::Complex(stmts) => {
+                self.compile_statements(stmts)?;
+                self.emit(Instruction::Number(0.0), program.span);
+            }
+            ProgramBody::Empty => {
+                self.emit(Instruction::Number(0.0), program.span);
+            }
+        }
+        self.emit(Instruction::Return, program.span);
+        Ok(self.chunk)
+    }
+
+    fn compile_statements(&mut self, stmts: &[Statement]) -> Result<()> {
+        for stmt in stmts {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<()> {
+        match stmt {
+            Statement::Expression(expr) => {
+                self.compile_expression(expr)?;
+                self.emit(Instruction::Pop, expr.span());
+            }
+            Statement::Assignment(stmt) => self.compile_assignment_statement(stmt)?,
+            Statement::Loop(stmt) => {
+                self.compile_expression(&stmt.count)?;
+                self.emit(Instruction::LoopInit, stmt.count.span());
+                let next = self.emit(Instruction::LoopNext { exit: 0 }, stmt.span);
+                self.compile_loop_body(next, &stmt.block.statements, stmt.span)?;
+            }
+            Statement::ForEach(stmt) => {
+                self.compile_expression(&stmt.array)?;
+                self.emit(Instruction::ForEachInit, stmt.array.span());
+                let variable = self.variable(&stmt.variable);
+                let next = self.emit(Instruction::ForEachNext { variable, exit: 0 }, stmt.span);
+                self.compile_loop_body(next, &stmt.block.statements, stmt.span)?;
+            }
+            Statement::Return(stmt) => {
+                self.compile_expression(&stmt.argument)?;
+                self.emit(Instruction::Return, stmt.span);
+            }
+            Statement::Break(stmt) => {
+                let Some(labels) = self.loops.last_mut() else {
+                    return Err(break_outside_loop(stmt.span));
+                };
+                labels.breaks.push(self.chunk.code.len());
+                self.emit(Instruction::Jump(0), stmt.span);
+            }
+            Statement::Continue(stmt) => {
+                let Some(labels) = self.loops.last() else {
+                    return Err(continue_outside_loop(stmt.span));
+                };
+                let next = labels.next;
+                self.emit(Instruction::Jump(next), stmt.span);
+            }
+            Statement::Empty(_) => (),
+            Statement::Error(stmt) => return Err(syntax_error(stmt.span)),
+        }
+        Ok(())
+    }
+
+    /// Compiles the block of a loop whose `LoopNext` or `ForEachNext`
+    /// instruction is at `next`, then patches its exit.
+    fn compile_loop_body(&mut self, next: u32, stmts: &[Statement], span: Span) -> Result<()> {
+        self.loops.push(LoopLabels { next, breaks: Vec::new() });
+        self.compile_statements(stmts)?;
+        self.emit(Instruction::Jump(next), span);
+        let labels = self.loops.pop().unwrap();
+
+        let exit = self.emit(Instruction::LoopExit, span);
+        match &mut self.chunk.code[next as usize] {
+            Instruction::LoopNext { exit: target }
+            | Instruction::ForEachNext { exit: target, .. } => *target = exit,
+            _ => unreachable!(),
+        }
+        for index in labels.breaks {
+            self.patch(index, exit);
+        }
+        Ok(())
+    }
+
+    fn compile_assignment_statement(&mut self, stmt: &AssignmentStatement) -> Result<()> {
+        let variable = self.variable(&stmt.left);
+        match stmt.operator {
+            AssignmentOperator::Assign => self.compile_expression(&stmt.right)?,
+            AssignmentOperator::LogicalOr | AssignmentOperator::LogicalAnd => {
+                self.emit(Instruction::Load(variable), stmt.left.span);
+                let jump = if stmt.operator == AssignmentOperator::LogicalOr {
+                    self.emit(Instruction::JumpIfTrue(0), stmt.span)
+                } else {
+                    self.emit(Instruction::JumpIfFalse(0), stmt.span)
+                };
+                self.compile_expression(&stmt.right)?;
+                self.emit(Instruction::Store(variable), stmt.left.span);
+                let end = self.position();
+                self.patch(jump as usize, end);
+                return Ok(());
+            }
+            operator => {
+                self.emit(Instruction::Load(variable), stmt.left.span);
+                self.compile_expression(&stmt.right)?;
+                self.emit(Instruction::Binary(operator.into()), stmt.span);
+            }
+        }
+        self.emit(Instruction::Store(variable), stmt.left.span);
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expr: &Expression) -> Result<()> {
+        match expr {
+            Expression::NumericLiteral(expr) => {
+                self.emit(Instruction::Number(expr.value), expr.span);
+            }
+            Expression::BooleanLiteral(expr) => {
+                self.emit(Instruction::Number(if expr.value { 1.0 } else { 0.0 }), expr.span);
+            }
+            Expression::StringLiteral(expr) => {
+                let constant = self.constant(expr.value.as_ref().into());
+                self.emit(Instruction::Constant(constant), expr.span);
+            }
+            Expression::Variable(expr) => {
+                let variable = self.variable(expr);
+                self.emit(Instruction::Load(variable), expr.span);
+            }
+            Expression::Parenthesized(expr) => match &expr.body {
+                ParenthesizedBody::Single(expr) => self.compile_expression(expr)?,
+                ParenthesizedBody::Multiple(stmts) => {
+                    self.compile_statements(stmts)?;
+                    self.emit(Instruction::Number(0.0), expr.span);
+                }
+            },
+            Expression::Block(expr) => {
+                self.compile_statements(&expr.statements)?;
+                self.emit(Instruction::Number(0.0), expr.span);
+            }
+            Expression::Binary(expr) => self.compile_binary_expression(expr)?,
+            Expression::Unary(expr) => {
+                self.compile_expression(&expr.argument)?;
+                let instruction = match expr.operator {
+                    UnaryOperator::Not => Instruction::Not,
+                    UnaryOperator::Negate => Instruction::Negate,
+                    UnaryOperator::BitwiseNot => Instruction::BitwiseNot,
+                };
+                self.emit(instruction, expr.argument.span());
+            }
+            Expression::Update(expr) => {
+                let variable = self.variable(&expr.variable);
+                self.emit(Instruction::Update { variable, operator: expr.operator }, expr.span);
+            }
+            Expression::Ternary(expr) => {
+                self.compile_expression(&expr.test)?;
+                let alternate = self.emit(Instruction::JumpIfFalse(0), expr.span);
+                self.compile_expression(&expr.consequent)?;
+                let end = self.emit(Instruction::Jump(0), expr.span);
+                self.patch(alternate as usize, self.position());
+                self.compile_expression(&expr.alternate)?;
+                self.patch(end as usize, self.position());
+            }
+            Expression::Conditional(expr) => {
+                self.compile_expression(&expr.test)?;
+                let alternate = self.emit(Instruction::JumpIfFalse(0), expr.span);
+                self.compile_expression(&expr.consequent)?;
+                let end = self.emit(Instruction::Jump(0), expr.span);
+                self.patch(alternate as usize, self.position());
+                self.emit(Instruction::Number(0.0), expr.span);
+                self.patch(end as usize, self.position());
+            }
+            Expression::Resource(expr) => {
+                let value = format!("{}.{}", expr.section.as_str(), lowercase(&expr.name.name));
+                let constant = self.constant(value.into());
+                self.emit(Instruction::Constant(constant), expr.span);
+            }
+            Expression::ArrayAccess(expr) => {
+                self.compile_expression(&expr.index)?;
+                let name = self.name(&expr.name.name);
+                self.emit(Instruction::Array(name), expr.span);
+            }
+            Expression::ArrowAccess(expr) => {
+                self.compile_expression(&expr.left)?;
+                self.emit(Instruction::EnterTarget, expr.left.span());
+                self.compile_expression(&expr.right)?;
+                self.emit(Instruction::ExitTarget, expr.span);
+            }
+            Expression::Call(expr) => self.compile_call_expression(expr)?,
+            Expression::This(expr) => {
+                self.emit(Instruction::This, expr.span);
+            }
+            Expression::Error(expr) => return Err(syntax_error(expr.span)),
+        }
+        Ok(())
+    }
+
+    fn compile_binary_expression(&mut self, expr: &BinaryExpression) -> Result<()> {
+        match expr.operator {
+            BinaryOperator::And | BinaryOperator::Or => {
+                let (jump, short_circuit): (fn(u32) -> Instruction, _) =
+                    if expr.operator == BinaryOperator::And {
+                        (Instruction::JumpIfFalse, 0.0)
+                    } else {
+                        (Instruction::JumpIfTrue, 1.0)
+                    };
+                self.compile_expression(&expr.left)?;
+                let left = self.emit(jump(0), expr.span);
+                self.compile_expression(&expr.right)?;
+                let right = self.emit(jump(0), expr.span);
+                self.emit(Instruction::Number(1.0 - short_circuit), expr.span);
+                let end = self.emit(Instruction::Jump(0), expr.span);
+                self.patch(left as usize, self.position());
+                self.patch(right as usize, self.position());
+                self.emit(Instruction::Number(short_circuit), expr.span);
+                self.patch(end as usize, self.position());
+            }
+            BinaryOperator::Coalesce => match &expr.left {
+                Expression::Variable(left) => {
+                    let variable = self.variable(left);
+                    let coalesce =
+                        self.emit(Instruction::Coalesce { variable, target: 0 }, expr.span);
+                    self.compile_expression(&expr.right)?;
+                    self.patch(coalesce as usize, self.position());
+                }
+                // Only unset variables fall through to the right side.
+                left => self.compile_expression(left)?,
+            },
+            operator => {
+                self.compile_expression(&expr.left)?;
+                self.compile_expression(&expr.right)?;
+                self.emit(Instruction::Binary(operator), expr.span);
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_call_expression(&mut self, expr: &CallExpression) -> Result<()> {
+        let arguments = expr.arguments.as_deref();
+        for argument in arguments.unwrap_or_default() {
+            self.compile_expression(argument)?;
+        }
+        match expr.kind {
+            CallKind::Math => {
+                let function =
+                    math::resolve(&expr.callee.name, arguments.unwrap_or_default().len())
+                        .map_err(|error| error.with_label(expr.span))?;
+                self.emit(Instruction::Math(function), expr.span);
+            }
+            CallKind::Query => {
+                let name = self.name(&expr.callee.name);
+                let arguments = arguments.map(|arguments| arguments.len() as u32);
+                self.emit(Instruction::Query { name, arguments }, expr.span);
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends an instruction and returns its index.
+    fn emit(&mut self, instruction: Instruction, span: Span) -> u32 {
+        let index = self.position();
+        self.chunk.code.push(instruction);
+        self.chunk.spans.push(span);
+        index
+    }
+
+    /// The index of the next instruction.
+    fn position(&self) -> u32 {
+        self.chunk.code.len() as u32
+    }
+
+    /// Points the jump at `index` to `target`.
+    fn patch(&mut self, index: usize, target: u32) {
+        match &mut self.chunk.code[index] {
+            Instruction::Jump(jump)
+            | Instruction::JumpIfFalse(jump)
+            | Instruction::JumpIfTrue(jump)
+            | Instruction::Coalesce { target: jump, .. } => *jump = target,
+            _ => unreachable!("instruction at {index} is not a jump"),
+        }
+    }
+
+    fn constant(&mut self, value: Value) -> u32 {
+        intern(&mut self.chunk.constants, value)
+    }
+
+    fn name(&mut self, name: &str) -> u32 {
+        intern(&mut self.chunk.names, name.to_ascii_lowercase())
+    }
+
+    fn variable(&mut self, variable: &VariableExpression) -> u32 {
+        fn path(member: &VariableMember, names: &mut Vec<String>) {
+            match member {
+                VariableMember::Object { object, property } => {
+                    path(object, names);
+                    names.push(property.name.to_ascii_lowercase());
+                }
+                VariableMember::Property { property } => {
+                    names.push(property.name.to_ascii_lowercase())
+                }
+            }
+        }
+        let mut names = Vec::new();
+        path(&variable.member, &mut names);
+        intern(&mut self.chunk.variables, (variable.lifetime, names.into_boxed_slice()))
+    }
+}
+
+/// Returns the index of `item` in `items`, appending it if needed.
+fn intern<T: PartialEq>(items: &mut Vec<T>, item: T) -> u32 {
+    let index = items.iter().position(|other| *other == item).unwrap_or_else(|| {
+        items.push(item);
+        items.len() - 1
+    });
+    index as u32
+}
@@ -1,7 +1,30 @@
 use std::{fs, path::Path};
 
 use insta::Settings;
-use nolana::{Codegen, CodegenOptions, MolangTransformer, Parser, semantic::SemanticChecker};
+use nolana::{
+    Codegen, CodegenOptions, ConstantFolder, Formatter, MolangTransformer, Parser,
+    ast::Program,
+    cst::{Cst, Trivia},
+    diagnostic::{Result, apply_fixes},
+    eval::{
+        EntityRef, Interpreter, Value,
+        query::{QueryCall, QueryProvider},
+    },
+    json,
+    line_index::LineIndex,
+    lint::{Level, Linter},
+    semantic::SemanticChecker,
+    vm::{Compiler, Vm},
+};
+#[cfg(feature = "arena")]
+use nolana::{
+    arena::{self, Bump},
+    ast,
+    traverse::Control,
+    visit::{self, Visit},
+};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
 
 fn with_settings(f: impl FnOnce()) {
     let mut settings = Settings::clone_current();
@@ -23,18 +46,180 @@ fn read_and_codegen(path: &Path) -> String {
     Codegen::default().build(&result.program)
 }
 
+/// Asserts that printing a program and parsing it back yields the same code.
+fn assert_round_trip(program: &Program) {
+    let code = Codegen::default().build(program);
+    let result = Parser::new(&code).parse();
+    assert!(result.errors.is_empty(), "{code}");
+    assert_eq!(code, Codegen::default().build(&result.program));
+}
+
+fn read_and_cst(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap();
+    let cst = Cst::new(&source);
+    assert_eq!(cst.to_string(), source);
+    let texts = |trivia: &[_]| trivia.iter().map(|trivia: &Trivia| trivia.text).collect::<Vec<_>>();
+    cst.tokens()
+        .iter()
+        .map(|token| {
+            let (leading, trailing) = (texts(&token.leading_trivia), texts(&token.trailing_trivia));
+            format!("{leading:?} {:?} {trailing:?}\n", token.text)
+        })
+        .collect()
+}
+
+fn read_and_format(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap();
+    let output = Formatter::default().format(&source).unwrap();
+    assert_eq!(output, Formatter::default().format(&output).unwrap(), "not idempotent");
+    output
+}
+
 fn read_and_semantic(path: &Path) -> String {
     let source = fs::read_to_string(path).unwrap();
-    let mut result = Parser::new(&source).parse();
-    let diagnostics = SemanticChecker::default().check(&mut result.program);
+    let result = Parser::new(&source).parse();
+    let diagnostics = SemanticChecker::default().check(&result.program);
     format!("{diagnostics:#?}")
 }
 
+fn read_and_lint(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap();
+    let name = path.file_name().unwrap().to_str().unwrap();
+    let result = Parser::new(&source).parse();
+    assert!(result.errors.is_empty());
+    let mut linter = Linter::default();
+    linter.set_level("no_exponent", Level::Warn).unwrap();
+    let index = LineIndex::new(&source);
+    let diagnostics = linter.lint(&result.program);
+    let mut output = String::new();
+    for diagnostic in &diagnostics {
+        output += &format!("{}\n", diagnostic.to_short_string(name, &index));
+    }
+    let fixed = apply_fixes(&source, &diagnostics);
+    if fixed != source {
+        output += &format!("\n{fixed}");
+    }
+    output
+}
+
 fn read_and_transform(path: &Path) -> String {
     let source = fs::read_to_string(path).unwrap();
     let mut result = Parser::new(&source).parse();
     MolangTransformer::default().transform(&mut result.program);
-    Codegen::default().with_options(CodegenOptions { minify: false }).build(&result.program)
+    Codegen::default()
+        .with_options(CodegenOptions { minify: false, ..Default::default() })
+        .build(&result.program)
+}
+
+fn read_and_fold(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap();
+    let mut result = Parser::new(&source).parse();
+    assert!(result.errors.is_empty());
+    ConstantFolder::default().fold(&mut result.program);
+    Codegen::default()
+        .with_options(CodegenOptions { minify: false, ..Default::default() })
+        .build(&result.program)
+}
+
+fn read_and_check_json(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap();
+    let name = path.file_name().unwrap().to_str().unwrap();
+    let index = LineIndex::new(&source);
+    let mut output = String::new();
+    for molang in json::extract(&source).unwrap_or_default() {
+        output += &format!("{} {:?}\n", molang.pointer, molang.source);
+    }
+    for diagnostic in json::check(&source) {
+        output += &format!("{}\n", diagnostic.to_short_string(name, &index));
+    }
+    output
+}
+
+/// A mock entity with two nearby entities: `entity#1` and `entity#2`.
+fn mock_queries(call: QueryCall) -> Result<Value> {
+    Ok(match (call.name, call.target) {
+        ("anim_time", None) => 1.5.into(),
+        ("life_time", None) => 10.0.into(),
+        ("is_on_ground", None) => true.into(),
+        ("get_nearby_entities", None) => {
+            Value::Array(vec![EntityRef(1).into(), EntityRef(2).into()])
+        }
+        ("health", Some(EntityRef(id))) => (id as f32 * 10.0).into(),
+        ("is_name_any", _) => {
+            let names = call.arguments.unwrap_or_default();
+            names.iter().any(|name| name.as_str() == Some("steve")).into()
+        }
+        _ => return Err(call.unknown()),
+    })
+}
+
+/// [`mock_queries`], where `entity#n` has `v.x` set to `n * 100`.
+struct MockEntities;
+
+impl QueryProvider for MockEntities {
+    fn query(&mut self, call: QueryCall) -> Result<Value> {
+        mock_queries(call)
+    }
+
+    fn entity_variable(&mut self, entity: EntityRef, name: &str) -> Result<Option<Value>> {
+        Ok((name == "x").then(|| (entity.0 as f32 * 100.0).into()))
+    }
+}
+
+fn read_and_eval(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap();
+    let result = Parser::new(&source).parse();
+    assert!(result.errors.is_empty());
+    let mut interpreter = Interpreter::new().with_queries(MockEntities);
+    interpreter.variables_mut().set_context(&["item_slot"], "main_hand".into());
+    let value = interpreter.eval(&result.program);
+    format!("{value:#?}")
+}
+
+/// Evaluates a program after transforming it, to check that the transformed
+/// code computes the same result.
+fn read_and_transform_eval(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap();
+    let mut result = Parser::new(&source).parse();
+    assert!(result.errors.is_empty());
+    MolangTransformer::default().transform(&mut result.program);
+    let value = Interpreter::new().eval(&result.program);
+    format!("{value:#?}")
+}
+
+fn read_and_compile(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap();
+    let result = Parser::new(&source).parse();
+    assert!(result.errors.is_empty());
+    match Compiler::default().compile(&result.program) {
+        Ok(chunk) => chunk.to_string(),
+        Err(error) => format!("{error:#?}"),
+    }
+}
+
+/// Runs a program through both the [`Interpreter`] and the [`Vm`], asserting
+/// that they agree on its result.
+fn read_and_compare(path: &Path) {
+    let source = fs::read_to_string(path).unwrap();
+    let result = Parser::new(&source).parse();
+    assert!(result.errors.is_empty());
+
+    let mut interpreter = Interpreter::new().with_queries(MockEntities);
+    interpreter.variables_mut().set_context(&["item_slot"], "main_hand".into());
+    let expected = interpreter.eval(&result.program);
+
+    let mut vm = Vm::new().with_queries(MockEntities);
+    vm.variables_mut().set_context(&["item_slot"], "main_hand".into());
+    let actual = Compiler::default().compile(&result.program).and_then(|chunk| vm.run(&chunk));
+
+    match (expected, actual) {
+        (Ok(expected), Ok(actual)) => assert_eq!(expected, actual, "{}", path.display()),
+        (Err(expected), Err(actual)) => {
+            assert_eq!(expected.message, actual.message, "{}", path.display())
+        }
+        (expected, actual) => panic!("{}: {expected:?} != {actual:?}", path.display()),
+    }
+    assert_eq!(interpreter.variables(), vm.variables(), "{}", path.display());
 }
 
 #[test]
@@ -46,6 +231,121 @@ fn test_parser() {
     });
 }
 
+/// Records the spans of the statements and expressions it enters.
+#[cfg(feature = "arena")]
+#[derive(Default)]
+struct SpanRecorder {
+    spans: Vec<(u32, u32)>,
+}
+
+#[cfg(feature = "arena")]
+impl<'src> Visit<'src> for SpanRecorder {
+    fn enter_statement(&mut self, it: &ast::Statement<'src>) -> Control {
+        self.spans.push((it.span().start, it.span().end));
+        Control::Continue
+    }
+
+    fn enter_expression(&mut self, it: &ast::Expression<'src>) -> Control {
+        self.spans.push((it.span().start, it.span().end));
+        Control::Continue
+    }
+}
+
+#[cfg(feature = "arena")]
+impl<'arena, 'src> arena::visit::Visit<'arena, 'src> for SpanRecorder {
+    fn enter_statement(&mut self, it: &arena::Statement<'arena, 'src>) -> Control {
+        self.spans.push((it.span().start, it.span().end));
+        Control::Continue
+    }
+
+    fn enter_expression(&mut self, it: &arena::Expression<'arena, 'src>) -> Control {
+        self.spans.push((it.span().start, it.span().end));
+        Control::Continue
+    }
+}
+
+/// The arena AST has the same shape as the boxed one, so their `Debug` output
+/// must match, it must convert to the same boxed AST, and it must be visited
+/// in the same order.
+#[cfg(feature = "arena")]
+#[test]
+fn test_arena_parser() {
+    insta::glob!("parser/*.nolana", |path| {
+        let source = fs::read_to_string(path).unwrap();
+        let arena = Bump::new();
+        let result = arena::Parser::new(&arena, &source).parse();
+        assert_eq!(format!("{result:#?}"), read_and_parse(path));
+
+        let program = Parser::new(&source).parse().program;
+        assert_eq!(ast::Program::from(&result.program), program);
+        let mut expected = SpanRecorder::default();
+        visit::visit(&mut expected, &program);
+        let mut recorder = SpanRecorder::default();
+        arena::visit::visit(&mut recorder, &result.program);
+        assert_eq!(recorder.spans, expected.spans);
+    });
+}
+
+/// Compares both parsers on randomly mutated fixtures, which reach error
+/// recovery paths that the fixtures themselves don't.
+#[cfg(feature = "arena")]
+#[test]
+fn test_arena_parser_fuzz() {
+    let tokens: Vec<_> = "v.a t.b.c q.foo math.sin geometry.default 1 2.5 'a' true this ( ) { } \
+        [ ] ; , . = += ??= + - * / ** << & | ^ ~ ! && || == < >= ? : ?? -> ++ -- loop for_each \
+        return break continue @"
+        .split_whitespace()
+        .collect();
+    // A fixed xorshift generator, so that failures can be reproduced.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize % bound
+    };
+    let floor_char_boundary = |source: &str, index: usize| {
+        (0..=index.min(source.len())).rev().find(|&index| source.is_char_boundary(index)).unwrap()
+    };
+    let mut arena = Bump::new();
+    insta::glob!("parser/*.nolana", |path| {
+        let fixture = fs::read_to_string(path).unwrap();
+        for _ in 0..200 {
+            let mut source = fixture.clone();
+            for _ in 0..1 + next(3) {
+                let at = floor_char_boundary(&source, next(source.len() + 1));
+                if next(2) == 0 {
+                    source.insert_str(at, tokens[next(tokens.len())]);
+                } else {
+                    let end = floor_char_boundary(&source, at + next(8));
+                    source.replace_range(at..end, "");
+                }
+            }
+            arena.reset();
+            let result = arena::Parser::new(&arena, &source).parse();
+            let expected = format!("{:#?}", Parser::new(&source).parse());
+            assert_eq!(format!("{result:#?}"), expected, "source: {source:?}");
+        }
+    });
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    insta::glob!("parser/*.nolana", |path| {
+        let source = fs::read_to_string(path).unwrap();
+        let program = Parser::new(&source).parse().program;
+        let json = serde_json::to_value(&program).unwrap();
+        assert_eq!(Program::deserialize(&json).unwrap(), program);
+    });
+    let source = r#"v.a = 'a"b\c'; return v.a;"#;
+    let program = Parser::new(source).parse().program;
+    let json = serde_json::to_string(&program).unwrap();
+    let deserialized: Program = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, program);
+    assert_eq!(Codegen::default().build(&deserialized), r#"v.a='a"b\c';return v.a;"#);
+}
+
 #[test]
 fn test_codegen() {
     with_settings(|| {
@@ -55,6 +355,35 @@ fn test_codegen() {
     });
 }
 
+#[test]
+fn test_codegen_round_trip() {
+    insta::glob!("{codegen,transformer}/*.nolana", |path| {
+        let source = fs::read_to_string(path).unwrap();
+        let mut result = Parser::new(&source).parse();
+        assert_round_trip(&result.program);
+        MolangTransformer::default().transform(&mut result.program);
+        assert_round_trip(&result.program);
+    });
+}
+
+#[test]
+fn test_cst() {
+    with_settings(|| {
+        insta::glob!("cst/*.nolana", |path| {
+            insta::assert_snapshot!(read_and_cst(path));
+        });
+    });
+}
+
+#[test]
+fn test_formatter() {
+    with_settings(|| {
+        insta::glob!("formatter/*.nolana", |path| {
+            insta::assert_snapshot!(read_and_format(path));
+        });
+    });
+}
+
 #[test]
 fn test_semantic() {
     with_settings(|| {
@@ -64,6 +393,15 @@ fn test_semantic() {
     });
 }
 
+#[test]
+fn test_lint() {
+    with_settings(|| {
+        insta::glob!("lint/*.nolana", |path| {
+            insta::assert_snapshot!(read_and_lint(path));
+        });
+    });
+}
+
 #[test]
 fn test_transformer() {
     with_settings(|| {
@@ -72,3 +410,66 @@ fn test_transformer() {
         });
     });
 }
+
+#[test]
+fn test_constant_folder() {
+    with_settings(|| {
+        insta::glob!("constant_folder/*.nolana", |path| {
+            insta::assert_snapshot!(read_and_fold(path));
+        });
+    });
+}
+
+#[test]
+fn test_json() {
+    with_settings(|| {
+        insta::glob!("json/*.json", |path| {
+            insta::assert_snapshot!(read_and_check_json(path));
+        });
+    });
+}
+
+#[test]
+fn test_eval() {
+    with_settings(|| {
+        insta::glob!("eval/*.nolana", |path| {
+            insta::assert_snapshot!(read_and_eval(path));
+        });
+    });
+}
+
+/// Without [`QueryProvider::entity_variable`], reading another entity's
+/// variables fails instead of reading the current entity's.
+#[test]
+fn test_entity_variable_unsupported() {
+    let source = "v.x = 1; for_each(t.entity, q.get_nearby_entities, { return t.entity->v.x; });";
+    let program = Parser::new(source).parse().program;
+    let error = Interpreter::new().with_queries(mock_queries).eval(&program).unwrap_err();
+    assert_eq!(error.message, "cannot read `variable.x` of another entity");
+    let chunk = Compiler::default().compile(&program).unwrap();
+    let error = Vm::new().with_queries(mock_queries).run(&chunk).unwrap_err();
+    assert_eq!(error.message, "cannot read `variable.x` of another entity");
+}
+
+#[test]
+fn test_transformer_eval() {
+    with_settings(|| {
+        insta::glob!("transformer_eval/*.nolana", |path| {
+            insta::assert_snapshot!(read_and_transform_eval(path));
+        });
+    });
+}
+
+#[test]
+fn test_vm() {
+    with_settings(|| {
+        insta::glob!("vm/*.nolana", |path| {
+            insta::assert_snapshot!(read_and_compile(path));
+        });
+    });
+}
+
+#[test]
+fn test_vm_matches_interpreter() {
+    insta::glob!("eval/*.nolana", read_and_compare);
+}